@@ -0,0 +1,34 @@
+use super::*;
+use crate::database;
+
+pub fn backup(mut args: pico_args::Arguments) -> ! {
+    let to = args
+        .opt_free_from_str::<PathBuf>()
+        .unwrap_or_exit(|err| eprintln!("ERROR! invalid backup path: {}", err));
+    finish(args);
+
+    let to = to.unwrap_or_else(|| {
+        let dir = get_data_path().join("backups");
+        std::fs::create_dir_all(&dir).unwrap_or_exit(|err| {
+            eprintln!("ERROR! cannot create backup directory: {}", err);
+        });
+        dir.join(format!(
+            "shaken-{}.db",
+            time::OffsetDateTime::now().format("%Y%m%d-%H%M%S")
+        ))
+    });
+
+    let path = get_database_path();
+    block_on(async {
+        let pool = database::connect(&path).await.unwrap_or_exit(|err| {
+            eprintln!("ERROR! cannot open database at {}: {}", path.display(), err);
+        });
+        database::backup(&pool, &to).await.unwrap_or_exit(|err| {
+            eprintln!("ERROR! cannot back up the database: {}", err);
+        });
+    });
+
+    println!("backed up the database to..");
+    println!("{}", to.display());
+    exit(0)
+}
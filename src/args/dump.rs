@@ -1,39 +1,27 @@
 use super::*;
+use crate::database;
 
 pub fn dump() -> ! {
-    use std::io::{copy, BufReader, Cursor};
-    use std::process::{Command, Output, Stdio};
+    use sqlx::prelude::*;
 
-    let db = get_data_path().join("database.db");
+    let path = get_database_path();
+    let schema = block_on(async {
+        let pool = database::connect(&path).await.unwrap_or_exit(|err| {
+            eprintln!("ERROR! cannot open database at {}: {}", path.display(), err);
+        });
 
-    // sqlite3 database.db .dump
-    // if -cmd is used then it waits for stdin for its repl
-    // and piping dev null to stdin doesn't stop this
-    match Command::new("sqlite3")
-        .args(&[db.to_string_lossy().as_ref(), ".dump"])
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .spawn()
-        .and_then(|cmd| cmd.wait_with_output())
-        .unwrap_or_exit(|err| eprintln!("ERROR: failed to run the `sqlite3` command: {}", err))
-    {
-        Output { status, stdout, .. } if status.success() => {
-            let output = std::io::stdout();
-            let mut output = output.lock();
+        sqlx::query("SELECT sql FROM sqlite_master WHERE sql IS NOT NULL ORDER BY type DESC, name")
+            .fetch_all(&mut &pool)
+            .await
+            .unwrap_or_exit(|err| eprintln!("ERROR! cannot read the schema: {}", err))
+            .into_iter()
+            .map(|row| row.get::<String, _>(0))
+            .collect::<Vec<_>>()
+    });
 
-            copy(&mut BufReader::new(Cursor::new(stdout)), &mut output).unwrap_or_exit(|err| {
-                eprintln!("ERROR: cannot copy `sqlite3` output to stdout: {}", err);
-            });
+    for sql in schema {
+        println!("{};", sql);
+    }
 
-            exit(0)
-        }
-        Output { status, stderr, .. } => {
-            eprintln!(
-                "ERROR! `sqlite3` returned an error: {}",
-                status.code().unwrap()
-            );
-            eprintln!("{}", String::from_utf8_lossy(&stderr));
-            exit(1);
-        }
-    };
+    exit(0)
 }
@@ -0,0 +1,56 @@
+use super::*;
+use crate::{
+    database,
+    export::{Export, Format},
+    migrations,
+};
+
+pub fn export(mut args: pico_args::Arguments) -> ! {
+    let format = args
+        .opt_value_from_str::<_, Format>("--format")
+        .unwrap_or_exit(|err| eprintln!("ERROR! {}", err))
+        .unwrap_or(Format::Toml);
+    let output = args
+        .opt_value_from_str::<_, PathBuf>(["-o", "--output"])
+        .unwrap_or_exit(|err| eprintln!("ERROR! invalid output path: {}", err));
+    finish(args);
+
+    let path = get_database_path();
+    let data = block_on(async {
+        let pool = database::connect(&path).await.unwrap_or_exit(|err| {
+            eprintln!("ERROR! cannot open database at {}: {}", path.display(), err);
+        });
+
+        // the export would be missing whatever the pending migrations add
+        let status = migrations::read_status(&pool)
+            .await
+            .unwrap_or_exit(|err| eprintln!("ERROR! cannot get migration status: {}", err));
+        if !status.pending.is_empty() {
+            eprintln!(
+                "ERROR! the database is at schema version {}, but the latest is {}",
+                status.version(),
+                migrations::latest()
+            );
+            eprintln!("run `shaken migrate` before exporting it");
+            exit(1)
+        }
+
+        Export::from_database(&pool)
+            .await
+            .and_then(|export| export.to_string(format))
+            .unwrap_or_exit(|err| eprintln!("ERROR! cannot export the database: {}", err))
+    });
+
+    match output {
+        Some(output) => {
+            std::fs::write(&output, data).unwrap_or_exit(|err| {
+                eprintln!("ERROR! cannot write to {}: {}", output.display(), err);
+            });
+            println!("exported the database to..");
+            println!("{}", output.display());
+        }
+        None => print!("{}", data),
+    }
+
+    exit(0)
+}
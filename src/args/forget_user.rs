@@ -0,0 +1,51 @@
+use super::*;
+use crate::{database, forget_user, Tracker};
+
+pub fn forget_user(mut args: pico_args::Arguments) -> ! {
+    let user = args
+        .free_from_str::<String>()
+        .unwrap_or_exit(|err| eprintln!("ERROR! a user id or login must be provided: {}", err));
+    finish(args);
+
+    let path = get_database_path();
+    let (id, forgotten) = block_on(async {
+        let pool = database::open(&path).await.unwrap_or_exit(|err| {
+            eprintln!("ERROR! cannot open database at {}: {}", path.display(), err);
+        });
+
+        let tracker = Tracker::load(pool.clone())
+            .await
+            .unwrap_or_exit(|err| eprintln!("ERROR! cannot load the known users: {}", err));
+        let id = tracker
+            .resolve(&user)
+            .await
+            .unwrap_or_exit(|err| eprintln!("ERROR! cannot look up '{}': {}", user, err))
+            .unwrap_or_else(|| {
+                eprintln!("ERROR! '{}' hasn't been seen. use their user id", user);
+                exit(1)
+            });
+
+        let forgotten = forget_user(&pool, id)
+            .await
+            .unwrap_or_exit(|err| eprintln!("ERROR! cannot forget {}: {}", id, err));
+        (id, forgotten)
+    });
+
+    let deleted = forgotten.deleted.iter().map(|(_, n)| n).sum::<u64>();
+    let anonymized = forgotten.anonymized.iter().map(|(_, n)| n).sum::<u64>();
+    println!(
+        "forgot user {} ({} rows deleted, {} rows anonymized)",
+        id, deleted, anonymized
+    );
+    for (table, n) in forgotten.deleted {
+        println!("{: >8} deleted from {}", n, table);
+    }
+    for (table, n) in forgotten.anonymized {
+        println!("{: >8} anonymized in {}", n, table);
+    }
+    if deleted + anonymized > 0 {
+        println!("restart the bot if it's running, so it doesn't keep them in memory");
+    }
+
+    exit(0)
+}
@@ -0,0 +1,51 @@
+use super::*;
+use crate::{database, modules::history::History};
+
+pub fn history(mut args: pico_args::Arguments) -> ! {
+    let room = args
+        .opt_value_from_str::<_, i64>("--room")
+        .unwrap_or_exit(|err| eprintln!("ERROR! --room must be a room id: {}", err));
+    let limit = args
+        .opt_value_from_str::<_, i64>("--limit")
+        .unwrap_or_exit(|err| eprintln!("ERROR! --limit must be a number: {}", err))
+        .unwrap_or(50);
+    let command = args
+        .opt_free_from_str::<String>()
+        .unwrap_or_exit(|err| eprintln!("ERROR! invalid command name: {}", err))
+        .map(|name| format!("!{}", name.trim_start_matches('!')));
+    finish(args);
+
+    let path = get_database_path();
+    let revisions = block_on(async {
+        let pool = database::open(&path).await.unwrap_or_exit(|err| {
+            eprintln!("ERROR! cannot open database at {}: {}", path.display(), err);
+        });
+        History::recent(pool, room, command.as_deref(), limit)
+            .await
+            .unwrap_or_exit(|err| eprintln!("ERROR! cannot read the history: {}", err))
+    });
+
+    if revisions.is_empty() {
+        println!("no history was found");
+        exit(0)
+    }
+
+    // oldest first, like a log
+    for rev in revisions.iter().rev() {
+        println!(
+            "{} room: {} user: {} {} {}",
+            rev.at.format("%F %T"),
+            rev.room,
+            rev.actor,
+            rev.action.past_tense(),
+            rev.name,
+        );
+        match (rev.previous_name(), rev.previous_body()) {
+            (Some(name), _) if name != rev.name => println!("    was named: {}", name),
+            (_, Some(body)) => println!("    was: {}", body),
+            _ => {}
+        }
+    }
+
+    exit(0)
+}
@@ -0,0 +1,105 @@
+use super::*;
+use crate::{
+    database, migrations,
+    modules::import::{self, Format, Imported, Source},
+};
+
+pub fn import(mut args: pico_args::Arguments) -> ! {
+    let source = args
+        .value_from_str::<_, Source>("--from")
+        .unwrap_or_exit(|err| eprintln!("ERROR! --from: {}", err));
+    let room = args
+        .value_from_str::<_, u64>("--room")
+        .unwrap_or_exit(|err| eprintln!("ERROR! --room must be a room id: {}", err));
+    let owner = args
+        .value_from_str::<_, u64>("--owner")
+        .unwrap_or_exit(|err| eprintln!("ERROR! --owner must be a user id: {}", err));
+    let format = args
+        .opt_value_from_str::<_, Format>("--format")
+        .unwrap_or_exit(|err| eprintln!("ERROR! --format: {}", err));
+    let dry_run = args.contains("--dry-run");
+    let file = args
+        .free_from_str::<PathBuf>()
+        .unwrap_or_exit(|err| eprintln!("ERROR! an export file must be provided: {}", err));
+    finish(args);
+
+    let format = format
+        .or_else(|| file.extension()?.to_str()?.parse().ok())
+        .unwrap_or_else(|| {
+            eprintln!("ERROR! cannot guess the format of the file, use --format");
+            exit(1)
+        });
+
+    let data = std::fs::read_to_string(&file)
+        .unwrap_or_exit(|err| eprintln!("ERROR! cannot read {}: {}", file.display(), err));
+    let commands = import::parse(source, format, &data)
+        .unwrap_or_exit(|err| eprintln!("ERROR! cannot parse {}: {}", file.display(), err));
+
+    let path = get_database_path();
+    let report = block_on(async {
+        let pool = if dry_run {
+            dry_run_database(&path).await
+        } else {
+            database::open(&path).await.unwrap_or_exit(|err| {
+                eprintln!("ERROR! cannot open database at {}: {}", path.display(), err);
+            })
+        };
+        import::import(pool, room, owner, commands, dry_run)
+            .await
+            .unwrap_or_exit(|err| eprintln!("ERROR! cannot import the commands: {}", err))
+    });
+
+    fn print(heading: &str, commands: &[Imported]) {
+        if commands.is_empty() {
+            return;
+        }
+        println!("{} ({})", heading, commands.len());
+        for command in commands {
+            println!("  {} -- {}", command.name, command.body);
+            for var in &command.unsupported {
+                println!("    unsupported variable: {}", var);
+            }
+        }
+        println!();
+    }
+
+    let added = if dry_run { "would add" } else { "added" };
+    print(added, &report.added);
+    print("skipped, reserved by a built-in command", &report.builtin);
+    print("skipped, already exists", &report.existing);
+
+    if dry_run {
+        println!("nothing was written. run it again without --dry-run to import them");
+    }
+
+    exit(0)
+}
+
+/// Open the database without creating, migrating or backing it up
+async fn dry_run_database(path: &Path) -> sqlx::SqlitePool {
+    if !path.is_file() {
+        eprintln!("ERROR! cannot find database at..");
+        eprintln!("{}", path.display());
+        exit(1)
+    }
+
+    let pool = database::connect(path).await.unwrap_or_exit(|err| {
+        eprintln!("ERROR! cannot open database at {}: {}", path.display(), err);
+    });
+
+    // what's in the database wouldn't be what the import sees after migrating
+    let status = migrations::read_status(&pool)
+        .await
+        .unwrap_or_exit(|err| eprintln!("ERROR! cannot get migration status: {}", err));
+    if !status.pending.is_empty() {
+        eprintln!(
+            "ERROR! the database is at schema version {}, but the latest is {}",
+            status.version(),
+            migrations::latest()
+        );
+        eprintln!("run `shaken migrate` before importing into it");
+        exit(1)
+    }
+
+    pool
+}
@@ -0,0 +1,88 @@
+use super::*;
+use crate::{database, ArchivedMessage, ChatArchive};
+
+use time::{Date, OffsetDateTime};
+
+pub fn logs(mut args: pico_args::Arguments) -> ! {
+    let from = args
+        .opt_value_from_fn("--from", parse_date)
+        .unwrap_or_exit(|err| eprintln!("ERROR! --from must be a date (2020-04-01): {}", err))
+        .unwrap_or_else(|| OffsetDateTime::from_unix_timestamp(0));
+    let to = args
+        .opt_value_from_fn("--to", parse_date)
+        .unwrap_or_exit(|err| eprintln!("ERROR! --to must be a date (2020-04-01): {}", err))
+        // the whole day is included
+        .map(|to| to + time::Duration::days(1))
+        .unwrap_or_else(|| OffsetDateTime::now() + time::Duration::days(1));
+    let search = args
+        .opt_value_from_str::<_, String>("--search")
+        .unwrap_or_exit(|err| eprintln!("ERROR! invalid search: {}", err));
+    let limit = args
+        .opt_value_from_str::<_, i64>("--limit")
+        .unwrap_or_exit(|err| eprintln!("ERROR! --limit must be a number: {}", err))
+        .unwrap_or(100);
+    let json = match args
+        .opt_value_from_str::<_, String>("--format")
+        .unwrap_or_exit(|err| eprintln!("ERROR! invalid format: {}", err))
+        .as_deref()
+    {
+        None | Some("text") => false,
+        Some("json") => true,
+        Some(format) => {
+            eprintln!("ERROR! unknown format: '{}'. use 'text' or 'json'", format);
+            exit(1)
+        }
+    };
+    let room = args
+        .free_from_str::<String>()
+        .unwrap_or_exit(|err| eprintln!("ERROR! a room must be provided: {}", err));
+    finish(args);
+
+    let channel = format!("#{}", room.trim_start_matches('#').to_lowercase());
+
+    let path = get_database_path();
+    let messages = block_on(async {
+        let pool = database::open(&path).await.unwrap_or_exit(|err| {
+            eprintln!("ERROR! cannot open database at {}: {}", path.display(), err);
+        });
+
+        let archive = ChatArchive::new(pool);
+        let messages = match &search {
+            Some(words) => {
+                archive
+                    .search(&channel, words, from, to, limit)
+                    .await
+                    .map(|mut msgs| {
+                        // the search is newest first
+                        msgs.reverse();
+                        msgs
+                    })
+            }
+            None => archive.range(&channel, from, to).await,
+        };
+        messages.unwrap_or_exit(|err| eprintln!("ERROR! cannot read the chat log: {}", err))
+    });
+
+    if json {
+        let data = serde_json::to_string_pretty(&messages)
+            .unwrap_or_exit(|err| eprintln!("ERROR! cannot serialize the chat log: {}", err));
+        println!("{}", data);
+        exit(0)
+    }
+
+    if messages.is_empty() {
+        println!("no messages were found for {}", channel);
+        exit(0)
+    }
+
+    for ArchivedMessage { at, name, data, .. } in messages {
+        let at = OffsetDateTime::from_unix_timestamp(at);
+        println!("{} <{}> {}", at.format("%F %T"), name, data);
+    }
+
+    exit(0)
+}
+
+fn parse_date(input: &str) -> anyhow::Result<OffsetDateTime> {
+    Ok(Date::parse(input, "%F")?.midnight().assume_utc())
+}
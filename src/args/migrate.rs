@@ -0,0 +1,53 @@
+use super::*;
+use crate::{database, migrations};
+
+pub fn migrate(mut args: pico_args::Arguments) -> ! {
+    let status_only = args.contains("--status");
+    finish(args);
+
+    let path = get_database_path();
+
+    block_on(async move {
+        let pool = database::connect(&path).await.unwrap_or_exit(|err| {
+            eprintln!("ERROR! cannot open database at {}: {}", path.display(), err);
+        });
+
+        let status = migrations::status(&pool).await.unwrap_or_exit(|err| {
+            eprintln!("ERROR! cannot get migration status: {}", err);
+        });
+
+        println!("database is at..");
+        println!("{}", path.display());
+        println!();
+        println!(
+            "schema version: {} (latest is {})",
+            status.version(),
+            migrations::latest()
+        );
+
+        for applied in &status.applied {
+            let at = time::OffsetDateTime::from_unix_timestamp(applied.applied_at);
+            println!(
+                "  applied: {:>4} {} at {}",
+                applied.version,
+                applied.name,
+                at.format("%F %T")
+            );
+        }
+        for pending in &status.pending {
+            println!("  pending: {:>4} {}", pending.version, pending.name);
+        }
+
+        if status_only || status.pending.is_empty() {
+            return;
+        }
+
+        println!();
+        let applied = migrations::upgrade(&pool, Some(&path))
+            .await
+            .unwrap_or_exit(|err| eprintln!("ERROR! cannot migrate database: {}", err));
+        println!("applied {} migration(s)", applied.len());
+    });
+
+    exit(0)
+}
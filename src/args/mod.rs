@@ -6,10 +6,18 @@ use template::{FileStore, MemoryStore, PartialStore};
 use std::path::{Path, PathBuf};
 use std::process::exit;
 
+mod backup;
 mod dirs;
 mod dump;
 mod edit;
+mod export;
+mod forget_user;
+mod history;
+mod import;
 mod init;
+mod logs;
+mod migrate;
+mod restore;
 mod templates;
 mod unknown;
 mod verify;
@@ -22,11 +30,38 @@ flags:
     -v, --version   prints the version
 
 subcommands:
+    backup [file]   writes a copy of the database to `file` (or to the data directory)
     config          opens the `shaken.toml` in your editor
     dirs            prints the configuration and data directories
     dump            dump the database to stdout (schema)
     edit            opens the `user_templates.toml` in your editor
+    export          exports the user commands, triggers, counters and quotes to stdout
+        --format    either `toml` (the default) or `json`
+        -o, --output
+                    write it to this file instead
+    forget-user <user>
+                    deletes everything stored about a user, by their id or login.
+                    what they did to other users' data is kept, without them
+    history [command]
+                    prints the changes made to user commands, oldest first
+        --room      only show changes in this room
+        --limit     how many of the most recent changes to show (50 by default)
+    import <file>   imports user commands exported from another bot
+        --from      `nightbot`, `streamelements` or `streamlabs`
+        --room      the id of the room to add them to
+        --owner     the id of the user who will own them
+        --format    either `csv` or `json` (guessed from the file extension)
+        --dry-run   only show what would be imported
     init            initialize the config files
+    logs <room>     prints the archived chat of a room, oldest first
+        --from      the first day to include (e.g. 2020-04-01)
+        --to        the last day to include
+        --search    only messages containing all of these words
+        --limit     how many messages a search finds (100 by default)
+        --format    either `text` (the default) or `json`
+    migrate         apply any pending database migrations
+        --status    only show which migrations have been applied
+    restore <file>  replaces the database with this backup
     templates       print out the default templates
 ";
 
@@ -53,18 +88,27 @@ pub fn handle_args() -> (Config, DefaultTemplateStore) {
         exit(0);
     }
 
-    let cmd = args.subcommand();
-    args.finish().unwrap_or_exit(|err| {
-        eprintln!("invalid arguments provided: {}", err);
-    });
-
-    let cmd = cmd
-        .as_ref()
-        .map(|s| s.as_deref())
+    let cmd = args
+        .subcommand()
         // this happens when its not valid utf-8
         .unwrap_or_exit(|err| eprintln!("cannot parse subcommand: {}", err));
 
-    match cmd {
+    // these parse their own flags
+    match cmd.as_deref() {
+        Some("backup") => backup::backup(args),
+        Some("export") => export::export(args),
+        Some("forget-user") => forget_user::forget_user(args),
+        Some("history") => history::history(args),
+        Some("import") => import::import(args),
+        Some("logs") => logs::logs(args),
+        Some("migrate") => migrate::migrate(args),
+        Some("restore") => restore::restore(args),
+        _ => {}
+    }
+
+    finish(args);
+
+    match cmd.as_deref() {
         Some("config") => edit::config(),
         Some("edit") => edit::templates(),
 
@@ -79,6 +123,22 @@ pub fn handle_args() -> (Config, DefaultTemplateStore) {
     }
 }
 
+fn finish(args: pico_args::Arguments) {
+    args.finish().unwrap_or_exit(|err| {
+        eprintln!("invalid arguments provided: {}", err);
+    });
+}
+
+/// Run a future to completion, for subcommands that use the database
+fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+    tokio::runtime::Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()
+        .unwrap_or_exit(|err| eprintln!("ERROR! cannot create the runtime: {}", err))
+        .block_on(fut)
+}
+
 fn get_config_path() -> PathBuf {
     Directories::config().unwrap_or_exit(|err| {
         eprintln!("ERROR! cannot get configuration directory: {}", err);
@@ -91,6 +151,12 @@ fn get_data_path() -> PathBuf {
     })
 }
 
+fn get_database_path() -> PathBuf {
+    crate::database::default_path().unwrap_or_exit(|err| {
+        eprintln!("ERROR! cannot get database path: {}", err);
+    })
+}
+
 fn maybe_exit(maybes: impl IntoIterator<Item = bool>) -> bool {
     let mut iter = maybes.into_iter();
     let head = iter.next().unwrap();
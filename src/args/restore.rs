@@ -0,0 +1,82 @@
+use super::*;
+use crate::{database, migrations};
+
+pub fn restore(mut args: pico_args::Arguments) -> ! {
+    let from = args
+        .free_from_str::<PathBuf>()
+        .unwrap_or_exit(|err| eprintln!("ERROR! a backup file must be provided: {}", err));
+    finish(args);
+
+    if !from.is_file() {
+        eprintln!("ERROR! cannot find backup file at..");
+        eprintln!("{}", from.display());
+        exit(1)
+    }
+
+    let path = get_database_path();
+    block_on(async {
+        // nothing should be written to the backup
+        let backup = database::connect_read_only(&from)
+            .await
+            .unwrap_or_exit(|err| {
+                eprintln!("ERROR! cannot open backup at {}: {}", from.display(), err);
+            });
+
+        // this fails if the backup is from a newer version
+        let status = migrations::read_status(&backup)
+            .await
+            .unwrap_or_exit(|err| {
+                eprintln!("ERROR! cannot restore {}: {}", from.display(), err);
+            });
+
+        // keep what we're about to replace
+        let current = database::connect(&path).await.unwrap_or_exit(|err| {
+            eprintln!("ERROR! cannot open database at {}: {}", path.display(), err);
+        });
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(
+            ".pre-restore-{}.bak",
+            time::OffsetDateTime::now().timestamp()
+        ));
+        let saved = path.with_file_name(name);
+        database::backup(&current, &saved)
+            .await
+            .unwrap_or_exit(|err| eprintln!("ERROR! cannot back up the database: {}", err));
+        current.close().await;
+
+        println!("the previous database was saved to..");
+        println!("{}", saved.display());
+        println!();
+
+        // copy it through sqlite so it's consistent, then swap it in
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".restore");
+        let staged = path.with_file_name(name);
+        let _ = std::fs::remove_file(&staged);
+        database::backup(&backup, &staged)
+            .await
+            .unwrap_or_exit(|err| eprintln!("ERROR! cannot copy the backup: {}", err));
+        backup.close().await;
+
+        for ext in &["-wal", "-shm"] {
+            let mut name = path.file_name().unwrap_or_default().to_os_string();
+            name.push(ext);
+            let _ = std::fs::remove_file(path.with_file_name(name));
+        }
+        std::fs::rename(&staged, &path)
+            .unwrap_or_exit(|err| eprintln!("ERROR! cannot replace the database: {}", err));
+
+        println!("restored the database from..");
+        println!("{}", from.display());
+        if status.version() < migrations::latest() {
+            println!();
+            println!(
+                "the backup is at schema version {}, it'll be migrated to version {} on the next start",
+                status.version(),
+                migrations::latest()
+            );
+        }
+    });
+
+    exit(0)
+}
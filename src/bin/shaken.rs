@@ -6,9 +6,12 @@ use shaken::{
     Bot, Directories,
 };
 
-use std::{path::PathBuf, sync::Arc};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 use twitchchat::{Dispatcher, Runner, Status};
 
+/// How long the modules have to save what they're holding on to when the bot stops
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
 fn handle_startup() -> anyhow::Result<(Secrets, Config, DefaultTemplateStore)> {
     // this uses reverse order (least specific to most specific)
     // the last one will always override previous ones
@@ -25,12 +28,19 @@ fn handle_startup() -> anyhow::Result<(Secrets, Config, DefaultTemplateStore)> {
     Ok((secrets, config, templates))
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let (mut secrets, config, templates) = handle_startup()?;
+fn main() -> anyhow::Result<()> {
+    // the subcommands create their own runtimes, so this has to happen first
+    let (secrets, config, templates) = handle_startup()?;
+    tokio::runtime::Runtime::new()?.block_on(run(secrets, config, templates))
+}
 
-    let db_file = Directories::data()?.join("shaken.db");
-    // let pool = sqlx::SqlitePool::new(db_file.to_string_lossy().to_string().as_str()).await?;
+async fn run(
+    mut secrets: Secrets,
+    config: Config,
+    templates: DefaultTemplateStore,
+) -> anyhow::Result<()> {
+    // this applies any pending migrations
+    let pool = shaken::database::open(shaken::database::default_path()?).await?;
 
     let mut watcher = shaken::watcher::Watcher::new()?;
     let file = args::get_config_file_path()?;
@@ -41,18 +51,25 @@ async fn main() -> anyhow::Result<()> {
         mut state,
         command_map: commands,
         passive_list: passives,
+        timer_list: timers,
+        event_map: events,
         config: handle,
-        // pool,
+        bus,
+        pool,
         ..
     } = modules::ModuleInit::initialize(
         &mut secrets, // secret store
-        // pool,
-        handle, // configuration handle
+        pool,         // the database
+        handle,       // configuration handle
     )
     .await?;
 
     state.insert(handle);
-    // state.insert(pool);
+    state.insert(pool);
+
+    // the room state is shared between the bot and the responder
+    let channels = shaken::Channels::default();
+    state.insert(channels.clone());
 
     // create required twitchchat stuff
     let dispatcher = Dispatcher::new();
@@ -62,6 +79,7 @@ async fn main() -> anyhow::Result<()> {
     let responder = shaken::WriterResponder::new(
         control.writer().clone(), //
         resolver::new_resolver(templates)?,
+        channels,
     );
     // and make it log its actions
     let responder = shaken::LoggingResponder::new(responder);
@@ -73,6 +91,9 @@ async fn main() -> anyhow::Result<()> {
     )
     .await?;
 
+    // the bot owns the bus, but the modules are told through it when the bot stops
+    let shutdown = bus.clone();
+
     // create the bot future
     let bot = Bot::new(
         config,     // the bot configuration // TODO: make this 'reactive' (use the 'watch' module)
@@ -80,30 +101,44 @@ async fn main() -> anyhow::Result<()> {
         dispatcher, // the event dispatcher
         commands,   // the command map
         passives,   // the passive list
+        timers,     // the timer list
+        events,     // the twitch event handlers
+        bus,        // the event bus shared with the modules
     )
     .run(responder, state); // run the bot with this responder and this initial state
 
     // TODO maybe join instead of select
-    tokio::select! {
+    let result = tokio::select! {
         // run the twitchchat loop to completion
         status = runner.run(conn) => {
-            match status.map_err(|err| {
-                log::error!("error running: {}", err);
-                err
-            })? {
-                Status::Canceled => log::info!("runner stopped"),
-                Status::Eof => log::info!("runner ended"),
-            }
+            status
+                .map(|status| match status {
+                    Status::Canceled => log::info!("runner stopped"),
+                    Status::Eof => log::info!("runner ended"),
+                })
+                .map_err(|err| {
+                    log::error!("error running: {}", err);
+                    anyhow::Error::from(err)
+                })
         }
         // run the bot loop to completion
         result = bot => {
-            if let Err(err) = result {
-                log::error!("error running bot: {}", err);
-                return Err(err);
+            match &result {
+                Err(err) => log::error!("error running bot: {}", err),
+                Ok(..) => log::info!("bot is done running"),
             }
-            log::info!("bot is done running")
+            result
+        }
+        // or until we're told to stop
+        _ = tokio::signal::ctrl_c() => {
+            log::info!("stopping the bot");
+            Ok(())
         }
+    };
+
+    if !shutdown.shutdown(SHUTDOWN_TIMEOUT).await {
+        log::warn!("the modules didn't finish saving before the bot stopped");
     }
 
-    Ok(())
+    result
 }
@@ -1,10 +1,17 @@
 use std::sync::Arc;
 
-use super::{Command, CommandMap, Config, Context, Passive, PassiveList, Responder, State};
+use super::{
+    handler::{Notice, Pattern, WrappedTimer},
+    Channels, ChatHistory, ChatMessage, Cheer, Command, CommandMap, Config, Context, EventBus,
+    EventMap, Host, OptOuts, Passive, PassiveList, Responder, Room, State, Timer, TimerList,
+    Tracker, User,
+};
 
-use futures::prelude::*;
+use futures::{future::AbortHandle, prelude::*};
+use regex::Regex;
+use std::collections::HashMap;
 use tokio::sync::RwLock;
-use twitchchat::{events, messages, Control, Dispatcher};
+use twitchchat::{events, messages, Control, Dispatcher, EventStream};
 
 pub struct Bot<R: Responder + Send + 'static> {
     control: Control,
@@ -12,6 +19,9 @@ pub struct Bot<R: Responder + Send + 'static> {
     config: Config,
     command_map: CommandMap<R>,
     passive_list: PassiveList<R>,
+    timer_list: TimerList<R>,
+    event_map: EventMap<R>,
+    bus: EventBus,
 
     _spoopy: std::marker::PhantomData<R>,
 }
@@ -20,12 +30,16 @@ impl<R> Bot<R>
 where
     R: Responder + Send + 'static,
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         config: Config,
         control: Control,
         dispatcher: Dispatcher,
         command_map: CommandMap<R>,
         passive_list: PassiveList<R>,
+        timer_list: TimerList<R>,
+        event_map: EventMap<R>,
+        bus: EventBus,
     ) -> Self {
         Self {
             config,
@@ -33,6 +47,9 @@ where
             dispatcher,
             command_map,
             passive_list,
+            timer_list,
+            event_map,
+            bus,
 
             _spoopy: Default::default(),
         }
@@ -60,10 +77,26 @@ where
             user_id,
             color
         );
+
+        let our_user = User {
+            name: display_name.clone().unwrap().to_string().into(),
+            id: user_id.parse()?,
+        };
+        let mention = Pattern::mention_regex(&self.config.user_name);
         state.insert(info);
 
+        let channels = state.get::<Channels>().cloned().unwrap_or_default();
+        let history = state.get::<ChatHistory>().cloned().unwrap_or_default();
+        let tracker = state.get::<Tracker>().cloned();
+        let opt_outs = state.get::<OptOuts>().cloned();
         let state = Arc::new(RwLock::new(state));
 
+        // subscribe to these before joining so we don't miss the initial ROOMSTATE
+        let timer_room_states = self.dispatcher.subscribe::<events::RoomState>();
+        let parts = self.dispatcher.subscribe::<events::Part>();
+        let room_states = self.dispatcher.subscribe::<events::RoomState>();
+        let channel_states = self.dispatcher.subscribe::<events::RoomState>();
+
         tokio::pin! {
             let active = self.dispatch_actives(
                 responder.clone(),
@@ -72,34 +105,89 @@ where
             );
 
             let passive = self.dispatch_passives(
+                responder.clone(),
+                state.clone(),
+                config.clone(),
+                mention,
+            );
+
+            let notices = self.dispatch_notices(
+                responder.clone(),
+                state.clone(),
+                config.clone(),
+                room_states,
+            );
+
+            let timers = self.dispatch_timers(
                 responder,
                 state.clone(),
                 config,
+                our_user,
+                timer_room_states,
+                parts,
             );
+
+            let events = self.republish_events();
+
+            let channels = self.track_channels(channels, channel_states);
+
+            let history = self.record_history(history, opt_outs.clone());
+
+            let identities = self.track_identities(tracker.clone(), opt_outs);
         }
 
         for room in &self.config.rooms {
+            // rooms can be configured by id or by a name they used to have
+            let room = match &tracker {
+                Some(tracker) => tracker.resolve_room(room).await?,
+                None => None,
+            }
+            .unwrap_or_else(|| room.clone());
+
             log::debug!("joining: {}", room);
-            writer.join(room).await?;
+            writer.join(&room).await?;
         }
 
         tokio::select! {
             _ = &mut active => { }
             _ = &mut passive => { }
+            _ = &mut timers => { }
+            _ = &mut notices => { }
+            _ = &mut events => { }
+            _ = &mut channels => { }
+            _ = &mut history => { }
+            _ = &mut identities => { }
         }
 
         Ok(())
     }
 
-    async fn dispatch_passives(&self, responder: R, state: Arc<RwLock<State>>, config: Config) {
+    async fn dispatch_passives(
+        &self,
+        responder: R,
+        state: Arc<RwLock<State>>,
+        config: Config,
+        mention: Regex,
+    ) {
         let mut passive = self.dispatcher.subscribe::<events::Privmsg>();
         while let Some(passive) = passive.next().await.and_then(Passive::new) {
-            let state = Context::new(passive, Arc::clone(&state), config.clone());
-            for passive in self.passive_list.iter() {
-                log::trace!("dispatching to: {:?}", passive);
-                let fut = passive
+            let passive = passive.with_mention(&mention);
+            for handler in self.passive_list.iter() {
+                let matches = match handler.pattern.find(&passive.message.data, &mention) {
+                    Some(matches) => matches,
+                    None => continue,
+                };
+
+                log::trace!("dispatching to: {:?}", handler);
+                let state = Context::new(
+                    passive.with_matches(matches),
+                    Arc::clone(&state),
+                    config.clone(),
+                    self.bus.clone(),
+                );
+                let fut = handler
                     .inner
-                    .call(state.clone(), responder.clone())
+                    .call(state, responder.clone())
                     .inspect_err(|err| {
                         if err.is::<crate::util::DontCareSigil>() {
                             return;
@@ -120,7 +208,12 @@ where
                 None => continue,
             };
 
-            let state = Context::new(cmd.clone(), Arc::clone(&state), config.clone());
+            let state = Context::new(
+                cmd.clone(),
+                Arc::clone(&state),
+                config.clone(),
+                self.bus.clone(),
+            );
             for command in self.command_map.find(&*cmd.head) {
                 log::info!("dispatching to: {:?}", command);
                 let fut = command
@@ -136,4 +229,308 @@ where
             }
         }
     }
+
+    async fn dispatch_timers(
+        &self,
+        responder: R,
+        state: Arc<RwLock<State>>,
+        config: Config,
+        our_user: User<'static>,
+        // we get a ROOMSTATE when we join a room, and it has the room id
+        mut room_states: EventStream<Arc<messages::RoomState<'static>>>,
+        mut parts: EventStream<Arc<messages::Part<'static>>>,
+    ) {
+        // when this is dropped (e.g. the bot stops) all of the timers are stopped
+        let mut running = RunningTimers::default();
+
+        // NOTE: every timer runs in every joined room. there is no per-room module
+        // enablement in the bot yet, so like commands and passives, a timer that
+        // should only run in some rooms checks that itself (e.g. with a whitelist)
+        loop {
+            tokio::select! {
+                Some(msg) = room_states.next() => {
+                    // ROOMSTATE is also sent when the room settings change
+                    if running.contains(&msg.channel) {
+                        continue;
+                    }
+
+                    let id = match msg.tags.get("room-id").and_then(|s| s.parse().ok()) {
+                        Some(id) => id,
+                        None => continue,
+                    };
+
+                    let room = Room {
+                        name: msg.channel.to_string().into(),
+                        id,
+                    };
+
+                    for timer in self.timer_list.iter() {
+                        log::debug!("starting {:?} in {}", timer, room);
+                        let context = Context::new(
+                            Timer::new(room.clone(), our_user.clone()),
+                            Arc::clone(&state),
+                            config.clone(),
+                            self.bus.clone(),
+                        );
+                        let (fut, handle) = future::abortable(
+                            run_timer(timer.clone(), context, responder.clone())
+                        );
+                        tokio::spawn(fut);
+                        running.insert(&msg.channel, handle);
+                    }
+                }
+
+                Some(msg) = parts.next() => {
+                    if msg.name.eq_ignore_ascii_case(&config.user_name) {
+                        log::debug!("stopping timers for {}", msg.channel);
+                        running.stop(&msg.channel);
+                    }
+                }
+
+                else => break,
+            }
+        }
+    }
+
+    async fn dispatch_notices(
+        &self,
+        responder: R,
+        state: Arc<RwLock<State>>,
+        config: Config,
+        mut room_states: EventStream<Arc<messages::RoomState<'static>>>,
+    ) {
+        let mut notices = self.dispatcher.subscribe::<events::UserNotice>();
+        let mut hosts = self.dispatcher.subscribe::<events::HostTarget>();
+        let mut messages = self.dispatcher.subscribe::<events::Privmsg>();
+
+        // HOSTTARGET doesn't have a room id
+        let mut room_ids = HashMap::new();
+
+        loop {
+            tokio::select! {
+                Some(msg) = room_states.next() => {
+                    if let Some(id) = msg.tags.get("room-id").and_then(|s| s.parse().ok()) {
+                        room_ids.insert(msg.channel.to_string(), id);
+                    }
+                }
+
+                Some(msg) = notices.next() => {
+                    match Notice::from_user_notice(&msg) {
+                        Some(Notice::Sub(ev)) => {
+                            self.dispatch_event(ev, &responder, &state, &config)
+                        }
+                        Some(Notice::GiftSub(ev)) => {
+                            self.dispatch_event(ev, &responder, &state, &config)
+                        }
+                        Some(Notice::Raid(ev)) => {
+                            self.dispatch_event(ev, &responder, &state, &config)
+                        }
+                        Some(Notice::Ritual(ev)) => {
+                            self.dispatch_event(ev, &responder, &state, &config)
+                        }
+                        None => {
+                            log::trace!("unhandled USERNOTICE: {:?}", msg.tags.get("msg-id"))
+                        }
+                    }
+                }
+
+                Some(msg) = hosts.next() => {
+                    if let Some(ev) = Host::from_host_target(&msg, &room_ids) {
+                        self.dispatch_event(ev, &responder, &state, &config);
+                    }
+                }
+
+                Some(msg) = messages.next() => {
+                    if let Some(ev) = Cheer::from_privmsg(msg) {
+                        self.dispatch_event(ev, &responder, &state, &config);
+                    }
+                }
+
+                else => break,
+            }
+        }
+    }
+
+    fn dispatch_event<E>(
+        &self,
+        event: E,
+        responder: &R,
+        state: &Arc<RwLock<State>>,
+        config: &Config,
+    ) where
+        E: Clone + Send + Sync + 'static,
+    {
+        for handler in self.event_map.find::<E>() {
+            log::trace!("dispatching to: {:?}", handler);
+            let context = Context::new(
+                event.clone(),
+                Arc::clone(state),
+                config.clone(),
+                self.bus.clone(),
+            );
+            let fut = handler
+                .inner
+                .call(context, responder.clone())
+                .inspect_err(|err| {
+                    if err.is::<crate::util::DontCareSigil>() {
+                        return;
+                    }
+                    log::error!("cannot run event handler: {}", err);
+                });
+            tokio::spawn(fut);
+        }
+    }
+
+    async fn track_channels(
+        &self,
+        channels: Channels,
+        mut room_states: EventStream<Arc<messages::RoomState<'static>>>,
+    ) {
+        let mut user_states = self.dispatcher.subscribe::<events::UserState>();
+        let mut notices = self.dispatcher.subscribe::<events::Notice>();
+        let mut parts = self.dispatcher.subscribe::<events::Part>();
+
+        loop {
+            tokio::select! {
+                Some(msg) = room_states.next() => {
+                    channels.update_room_state(&msg.channel, |key: &str| {
+                        msg.tags.get(key).map(|s| s.to_string())
+                    });
+                }
+
+                Some(msg) = user_states.next() => {
+                    channels.update_user_state(&msg.channel, |key: &str| {
+                        msg.tags.get(key).map(|s| s.to_string())
+                    });
+                }
+
+                Some(msg) = notices.next() => {
+                    if let Some(id) = msg.tags.get("msg-id") {
+                        channels.notice(&msg.channel, id, &msg.message);
+                    }
+                }
+
+                Some(msg) = parts.next() => {
+                    if msg.name.eq_ignore_ascii_case(&self.config.user_name) {
+                        channels.remove(&msg.channel);
+                    }
+                }
+
+                else => break,
+            }
+        }
+    }
+
+    async fn record_history(&self, history: ChatHistory, opt_outs: Option<OptOuts>) {
+        let mut messages = self.dispatcher.subscribe::<events::Privmsg>();
+        while let Some(msg) = messages.next().await {
+            let msg = match ChatMessage::from_privmsg(&msg) {
+                Some(msg) => msg,
+                None => continue,
+            };
+            if opt_outs.as_ref().map_or(false, |o| o.contains(msg.user_id)) {
+                continue;
+            }
+            history.push(msg);
+        }
+    }
+
+    async fn track_identities(&self, tracker: Option<Tracker>, opt_outs: Option<OptOuts>) {
+        let tracker = match tracker {
+            Some(tracker) => tracker,
+            None => return futures::future::pending().await,
+        };
+
+        let mut messages = self.dispatcher.subscribe::<events::Privmsg>();
+        while let Some(msg) = messages.next().await {
+            if let (Some(id), Some(room)) = (msg.user_id(), msg.room_id()) {
+                // the room is still needed to join it, but the user asked not to be remembered
+                if !opt_outs.as_ref().map_or(false, |o| o.contains(id)) {
+                    let name = msg
+                        .display_name()
+                        .map(|s| s.to_string())
+                        .unwrap_or_else(|| msg.name.to_string());
+                    if let Err(err) = tracker.observe(id, &msg.name, &name).await {
+                        log::warn!("cannot track {} ({}): {}", msg.name, id, err);
+                    }
+                }
+                if let Err(err) = tracker.observe_room(room, &msg.channel).await {
+                    log::warn!("cannot track {} ({}): {}", msg.channel, room, err);
+                }
+            }
+        }
+    }
+
+    async fn republish_events(&self) {
+        let mut joins = self.dispatcher.subscribe::<events::Join>();
+        let mut parts = self.dispatcher.subscribe::<events::Part>();
+        let mut notices = self.dispatcher.subscribe::<events::UserNotice>();
+
+        loop {
+            tokio::select! {
+                Some(msg) = joins.next() => { self.bus.publish_arc(msg); }
+                Some(msg) = parts.next() => { self.bus.publish_arc(msg); }
+                Some(msg) = notices.next() => { self.bus.publish_arc(msg); }
+                else => break,
+            }
+        }
+    }
+}
+
+async fn run_timer<R>(timer: WrappedTimer<R>, context: Context<Timer>, responder: R)
+where
+    R: Responder + Send + 'static,
+{
+    loop {
+        let delay = match timer.schedule.next_delay(time::OffsetDateTime::now()) {
+            Some(delay) => delay,
+            None => {
+                log::warn!("{:?} will never run again in {}", timer, context.room());
+                break;
+            }
+        };
+
+        tokio::time::delay_for(delay).await;
+
+        log::trace!("dispatching to: {:?} in {}", timer, context.room());
+        let fut = timer
+            .inner
+            .call(context.clone(), responder.clone())
+            .inspect_err(|err| {
+                if err.is::<crate::util::DontCareSigil>() {
+                    return;
+                }
+                log::error!("cannot run timer: {}", err);
+            });
+        tokio::spawn(fut);
+    }
+}
+
+#[derive(Default)]
+struct RunningTimers {
+    map: HashMap<String, Vec<AbortHandle>>,
+}
+
+impl RunningTimers {
+    fn contains(&self, room: &str) -> bool {
+        self.map.contains_key(room)
+    }
+
+    fn insert(&mut self, room: &str, handle: AbortHandle) {
+        self.map.entry(room.to_string()).or_default().push(handle)
+    }
+
+    fn stop(&mut self, room: &str) {
+        for handle in self.map.remove(room).into_iter().flatten() {
+            handle.abort()
+        }
+    }
+}
+
+impl Drop for RunningTimers {
+    fn drop(&mut self) {
+        for handle in self.map.drain().flat_map(|(_, v)| v) {
+            handle.abort()
+        }
+    }
 }
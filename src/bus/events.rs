@@ -0,0 +1,43 @@
+//! Events published on the [`EventBus`](../struct.EventBus.html) by the bot
+//!
+//! Chat events from twitchchat are republished as-is, so modules don't each
+//! have to subscribe to the `Dispatcher`
+
+/// Someone joined a room
+pub type Join = twitchchat::messages::Join<'static>;
+
+/// Someone left a room
+pub type Part = twitchchat::messages::Part<'static>;
+
+/// A USERNOTICE (subs, raids, rituals, ..) was sent to a room
+pub type UserNotice = twitchchat::messages::UserNotice<'static>;
+
+/// A counter was created or changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct CounterChanged {
+    pub room: u64,
+    pub name: String,
+    /// `None` if the counter was just created
+    pub previous: Option<i64>,
+    pub value: i64,
+}
+
+/// These users were in a live room when its chatters were sampled
+///
+/// This is published every time a live room is sampled, even if nobody was there
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchTimeSampled {
+    pub room: u64,
+    pub users: Vec<u64>,
+    /// How much watch time each of them was given
+    pub seconds: u64,
+}
+
+/// The bot is shutting down
+///
+/// Subscribers should save anything they're holding on to and then drop it,
+/// [`EventBus::shutdown`](../struct.EventBus.html#method.shutdown) waits until every copy is dropped
+#[derive(Debug)]
+pub struct Shutdown {
+    pub(super) _done: tokio::sync::mpsc::Sender<()>,
+}
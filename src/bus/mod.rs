@@ -0,0 +1,156 @@
+use futures::prelude::*;
+use tokio::sync::mpsc;
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+pub mod events;
+
+type Senders<T> = Vec<mpsc::UnboundedSender<Arc<T>>>;
+
+/// A typed publish/subscribe bus shared between modules
+///
+/// Any `Send + Sync + 'static` type can be published, and every active
+/// subscriber for that type gets a shared copy of it.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    map: Arc<Mutex<HashMap<TypeId, Box<dyn Any + Send>>>>,
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("types", &self.map.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl EventBus {
+    /// Subscribe to events of type `T`
+    pub fn subscribe<T>(&self) -> Subscription<T>
+    where
+        T: Send + Sync + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.map
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Senders::<T>::new()))
+            .downcast_mut::<Senders<T>>()
+            .expect("senders must be keyed by their type")
+            .push(tx);
+        Subscription { rx }
+    }
+
+    /// Publish an event, returning how many subscribers received it
+    pub fn publish<T>(&self, event: T) -> usize
+    where
+        T: Send + Sync + 'static,
+    {
+        self.publish_arc(Arc::new(event))
+    }
+
+    /// Publish an already shared event, returning how many subscribers received it
+    pub fn publish_arc<T>(&self, event: Arc<T>) -> usize
+    where
+        T: Send + Sync + 'static,
+    {
+        let mut map = self.map.lock().unwrap();
+        let senders = match map
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|senders| senders.downcast_mut::<Senders<T>>())
+        {
+            Some(senders) => senders,
+            None => return 0,
+        };
+
+        // drop any subscribers that have gone away
+        senders.retain(|tx| tx.send(Arc::clone(&event)).is_ok());
+        senders.len()
+    }
+
+    /// Publish a [`Shutdown`](./events/struct.Shutdown.html), then wait for its subscribers to be done with it
+    ///
+    /// This returns `false` if they weren't done before the timeout
+    pub async fn shutdown(&self, timeout: std::time::Duration) -> bool {
+        let (done, mut rx) = mpsc::channel(1);
+        self.publish(events::Shutdown { _done: done });
+        // this ends once every copy of the event, and so the sender, has been dropped
+        tokio::time::timeout(timeout, rx.recv()).await.is_ok()
+    }
+}
+
+/// A stream of events of type `T` from an [`EventBus`](./struct.EventBus.html)
+pub struct Subscription<T> {
+    rx: mpsc::UnboundedReceiver<Arc<T>>,
+}
+
+impl<T> std::fmt::Debug for Subscription<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subscription")
+            .field("type", &crate::util::type_name::<T>())
+            .finish()
+    }
+}
+
+impl<T> Stream for Subscription<T> {
+    type Item = Arc<T>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn publish_subscribe() {
+        #[derive(Debug, PartialEq)]
+        struct Foo(i32);
+        #[derive(Debug, PartialEq)]
+        struct Bar(&'static str);
+
+        let bus = EventBus::default();
+        assert_eq!(bus.publish(Foo(0)), 0);
+
+        let mut a = bus.subscribe::<Foo>();
+        let mut b = bus.subscribe::<Foo>();
+        let mut c = bus.subscribe::<Bar>();
+
+        assert_eq!(bus.publish(Foo(42)), 2);
+        assert_eq!(bus.publish(Bar("hello")), 1);
+
+        assert_eq!(*a.next().await.unwrap(), Foo(42));
+        assert_eq!(*b.next().await.unwrap(), Foo(42));
+        assert_eq!(*c.next().await.unwrap(), Bar("hello"));
+
+        drop(b);
+        assert_eq!(bus.publish(Foo(43)), 1);
+        assert_eq!(*a.next().await.unwrap(), Foo(43));
+    }
+
+    #[tokio::test]
+    async fn shutdown() {
+        let bus = EventBus::default();
+        let timeout = std::time::Duration::from_millis(100);
+        assert!(bus.shutdown(timeout).await);
+
+        // it isn't done until the event is dropped
+        let mut shutdown = bus.subscribe::<events::Shutdown>();
+        assert!(!bus.shutdown(timeout).await);
+
+        let waiting = tokio::spawn({
+            let bus = bus.clone();
+            async move { bus.shutdown(timeout).await }
+        });
+        shutdown.next().await.unwrap(); // the first one
+        let event = shutdown.next().await.unwrap();
+        drop(event);
+        assert!(waiting.await.unwrap());
+    }
+}
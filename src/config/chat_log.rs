@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatLog {
+    /// The rooms that have their chat archived, nothing is archived by default
+    pub rooms: HashSet<String>,
+    /// How many days of chat to keep, `0` keeps it forever
+    pub retention_days: u64,
+}
+
+impl ChatLog {
+    pub fn is_logged(&self, room: &str) -> bool {
+        self.rooms.contains(room.trim_start_matches('#'))
+    }
+}
+
+impl Default for ChatLog {
+    fn default() -> Self {
+        Self {
+            rooms: HashSet::new(),
+            retention_days: 30,
+        }
+    }
+}
@@ -13,6 +13,12 @@ pub use whatsong::WhatSong;
 mod shakespeare;
 pub use shakespeare::Shakespeare;
 
+mod chat_log;
+pub use chat_log::ChatLog;
+
+mod points;
+pub use points::Points;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub user_name: String,
@@ -20,6 +26,10 @@ pub struct Config {
     pub rooms: Vec<String>,
     pub shakespeare: Shakespeare,
     pub whatsong: WhatSong,
+    #[serde(default)]
+    pub chat_log: ChatLog,
+    #[serde(default)]
+    pub points: Points,
 }
 
 impl Config {
@@ -30,6 +40,8 @@ impl Config {
             rooms: vec!["#museun".into()],
             shakespeare: Default::default(),
             whatsong: Default::default(),
+            chat_log: Default::default(),
+            points: Default::default(),
         };
         std::fs::write(path, toml::to_string_pretty(&default)?)?;
         Ok(())
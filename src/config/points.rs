@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Points {
+    /// What the points are called in chat
+    pub name: String,
+    /// How many points are earned for each minute of watching a live stream
+    pub per_minute: u64,
+    /// How many points are earned for chatting while the stream is live
+    pub per_message: u64,
+    /// How many seconds must pass before chatting earns points again
+    pub message_cooldown: u64,
+    /// What subscribers' earnings are multiplied by
+    pub subscriber_multiplier: f64,
+}
+
+impl Points {
+    /// What the user earns from `base` points
+    pub fn earned(&self, base: u64, subscriber: bool) -> i64 {
+        match subscriber {
+            true => (base as f64 * self.subscriber_multiplier).round() as i64,
+            false => base as i64,
+        }
+    }
+}
+
+impl Default for Points {
+    fn default() -> Self {
+        Self {
+            name: "points".into(),
+            per_minute: 1,
+            per_message: 5,
+            message_cooldown: 60,
+            subscriber_multiplier: 2.0,
+        }
+    }
+}
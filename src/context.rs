@@ -1,4 +1,4 @@
-use crate::{Config, RespondableContext, Room, State, User};
+use crate::{Config, EventBus, RespondableContext, Room, State, User};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -9,17 +9,29 @@ pub struct Context<Args> {
     pub args: Args,
     pub config: Config, // TODO use a watch here
     state: Arc<RwLock<State>>,
+    bus: EventBus,
 }
 
 impl<Args> Context<Args> {
-    pub(super) fn new(args: Args, state: Arc<RwLock<State>>, config: Config) -> Self {
+    pub(super) fn new(
+        args: Args,
+        state: Arc<RwLock<State>>,
+        config: Config,
+        bus: EventBus,
+    ) -> Self {
         Self {
             args,
             config,
             state,
+            bus,
         }
     }
 
+    /// The event bus shared between all of the modules
+    pub fn bus(&self) -> &EventBus {
+        &self.bus
+    }
+
     pub async fn get_our_user(&self) -> crate::User<'_> {
         let state = self.state().await;
         let info = state
@@ -95,3 +107,15 @@ common_short_hand! {
     Command, //
     Passive
 }
+
+impl Context<crate::Passive> {
+    pub fn matches(&self) -> &crate::Matches {
+        self.args.matches()
+    }
+}
+
+impl Context<crate::Timer> {
+    pub fn room(&self) -> Room<'_> {
+        self.args.room()
+    }
+}
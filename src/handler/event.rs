@@ -0,0 +1,106 @@
+use crate::handler::DynHandler;
+use crate::{Handler, Responder};
+
+use futures::prelude::*;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub struct WrappedEvent<E, R> {
+    pub inner: Arc<DynHandler<E, R>>,
+    pub id: usize,
+}
+
+impl<E, R> Clone for WrappedEvent<E, R> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            id: self.id,
+        }
+    }
+}
+
+impl<E, R> std::fmt::Debug for WrappedEvent<E, R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WrappedEvent")
+            .field("event", &crate::util::type_name::<E>())
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+/// Handlers for typed Twitch events (subs, raids, cheers, ..)
+pub struct EventMap<R> {
+    inner: HashMap<TypeId, Box<dyn Any>>,
+    id: usize,
+    _phantom: std::marker::PhantomData<R>,
+}
+
+impl<R> std::fmt::Debug for EventMap<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventMap")
+            .field("events", &self.inner.len())
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+impl<R: Responder + Send + 'static> Default for EventMap<R> {
+    fn default() -> Self {
+        let (inner, id, _phantom) = Default::default();
+        Self {
+            inner,
+            id,
+            _phantom,
+        }
+    }
+}
+
+impl<R: Responder + Send + 'static> EventMap<R> {
+    /// Add a handler for the event `E`
+    pub fn add<E, H, F>(&mut self, handler: H) -> usize
+    where
+        E: Send + 'static,
+        H: Handler<E, R, Fut = F>,
+        F: Future<Output = anyhow::Result<()>>,
+        F::Output: Send + 'static,
+        F: Send + 'static,
+    {
+        let next = self.id + 1;
+        let id = std::mem::replace(&mut self.id, next);
+        let inner: Arc<DynHandler<E, R>> = Arc::new(move |state, resp| handler.call(state, resp));
+        self.inner
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| Box::new(Vec::<WrappedEvent<E, R>>::new()))
+            .downcast_mut::<Vec<WrappedEvent<E, R>>>()
+            .expect("handlers must be keyed by their event")
+            .push(WrappedEvent { inner, id });
+        id
+    }
+
+    /// Remove the handler with this id for the event `E`
+    pub fn remove<E>(&mut self, id: usize) -> Option<()>
+    where
+        E: Send + 'static,
+    {
+        let handlers = self
+            .inner
+            .get_mut(&TypeId::of::<E>())?
+            .downcast_mut::<Vec<WrappedEvent<E, R>>>()?;
+        let n = handlers.iter().position(|s| s.id == id)?;
+        handlers.swap_remove(n);
+        Some(())
+    }
+
+    /// Find all of the handlers for the event `E`
+    pub fn find<E>(&self) -> Vec<WrappedEvent<E, R>>
+    where
+        E: Send + 'static,
+    {
+        self.inner
+            .get(&TypeId::of::<E>())
+            .and_then(|handlers| handlers.downcast_ref::<Vec<WrappedEvent<E, R>>>())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
@@ -5,9 +5,21 @@ use futures::prelude::*;
 mod command;
 pub use command::*;
 
+mod event;
+pub use event::*;
+
+mod notice;
+pub use notice::*;
+
 mod passive;
 pub use passive::*;
 
+mod schedule;
+pub use schedule::*;
+
+mod timer;
+pub use timer::*;
+
 type Result = anyhow::Result<()>;
 
 pub type BoxFuture = futures::future::BoxFuture<'static, Result>;
@@ -0,0 +1,411 @@
+use crate::{RespondableContext, Room, User};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use twitchchat::messages::{HostTarget, HostTargetKind, Privmsg, UserNotice};
+
+/// The tier of a subscription
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SubPlan {
+    Prime,
+    Tier1,
+    Tier2,
+    Tier3,
+}
+
+impl Default for SubPlan {
+    fn default() -> Self {
+        Self::Tier1
+    }
+}
+
+impl SubPlan {
+    fn parse(input: &str) -> Self {
+        match input {
+            "Prime" => Self::Prime,
+            "2000" => Self::Tier2,
+            "3000" => Self::Tier3,
+            _ => Self::Tier1,
+        }
+    }
+}
+
+impl std::fmt::Display for SubPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plan = match self {
+            Self::Prime => "Prime",
+            Self::Tier1 => "Tier 1",
+            Self::Tier2 => "Tier 2",
+            Self::Tier3 => "Tier 3",
+        };
+        f.write_str(plan)
+    }
+}
+
+macro_rules! respondable {
+    ($($ty:ident),* $(,)?) => {
+        $(
+            impl RespondableContext for $ty {
+                fn room(&self) -> Room<'_> {
+                    self.room.clone()
+                }
+
+                fn user(&self) -> User<'_> {
+                    self.user.clone()
+                }
+            }
+        )*
+    };
+}
+
+respondable! {
+    Sub,
+    GiftSub,
+    Raid,
+    Ritual,
+    Cheer,
+    Host,
+}
+
+/// Someone subscribed (or resubscribed) to the room
+#[derive(Debug, Clone)]
+pub struct Sub {
+    room: Room<'static>,
+    user: User<'static>,
+    /// Whether this was a resub
+    pub resub: bool,
+    /// How many months they've been subscribed for
+    pub months: u64,
+    /// Their current streak, if they chose to share it
+    pub streak: Option<u64>,
+    pub plan: SubPlan,
+    /// The message they attached to their resub
+    pub message: Option<String>,
+}
+
+/// Someone gifted subs in the room. The user is the gifter
+#[derive(Debug, Clone)]
+pub struct GiftSub {
+    room: Room<'static>,
+    user: User<'static>,
+    /// Who got the sub, this is None for a mystery gift to the community
+    pub recipient: Option<User<'static>>,
+    /// How many subs were gifted
+    pub count: u64,
+    pub plan: SubPlan,
+}
+
+/// The room was raided. The user is the raider
+#[derive(Debug, Clone)]
+pub struct Raid {
+    room: Room<'static>,
+    user: User<'static>,
+    pub viewers: u64,
+}
+
+/// A ritual happened in the room (e.g. `new_chatter`)
+#[derive(Debug, Clone)]
+pub struct Ritual {
+    room: Room<'static>,
+    user: User<'static>,
+    pub name: String,
+    pub message: Option<String>,
+}
+
+/// Someone cheered bits in the room
+#[derive(Debug, Clone)]
+pub struct Cheer {
+    room: Room<'static>,
+    user: User<'static>,
+    pub bits: u64,
+    pub message: Arc<Privmsg<'static>>,
+}
+
+/// The room started (or stopped) hosting another channel. The user is the broadcaster
+#[derive(Debug, Clone)]
+pub struct Host {
+    room: Room<'static>,
+    user: User<'static>,
+    /// The channel being hosted, this is None when hosting ended
+    pub target: Option<String>,
+    pub viewers: Option<u64>,
+}
+
+/// A parsed USERNOTICE
+#[derive(Debug, Clone)]
+pub(crate) enum Notice {
+    Sub(Sub),
+    GiftSub(GiftSub),
+    Raid(Raid),
+    Ritual(Ritual),
+}
+
+impl Notice {
+    pub(crate) fn from_user_notice(msg: &UserNotice<'static>) -> Option<Self> {
+        Self::from_tags(
+            &msg.channel,
+            msg.message.as_deref(),
+            |key| msg.tags.get(key).map(|s| s.to_string()), //
+        )
+    }
+
+    fn from_tags<F>(channel: &str, message: Option<&str>, tag: F) -> Option<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let parsed = |key: &str| tag(key).and_then(|s| s.parse::<u64>().ok());
+        let plan = || tag("msg-param-sub-plan").map(|s| SubPlan::parse(&s));
+
+        let room = Room {
+            name: channel.to_string().into(),
+            id: parsed("room-id")?,
+        };
+
+        let user = User {
+            name: tag("display-name").or_else(|| tag("login"))?.into(),
+            id: parsed("user-id")?,
+        };
+
+        let message = message.map(ToString::to_string);
+
+        let msg_id = tag("msg-id")?;
+        let notice = match msg_id.as_str() {
+            "sub" | "resub" => Self::Sub(Sub {
+                room,
+                user,
+                resub: msg_id == "resub",
+                months: parsed("msg-param-cumulative-months").unwrap_or(1),
+                streak: parsed("msg-param-streak-months")
+                    .filter(|_| tag("msg-param-should-share-streak").as_deref() == Some("1")),
+                plan: plan().unwrap_or_default(),
+                message,
+            }),
+
+            "subgift" | "anonsubgift" => Self::GiftSub(GiftSub {
+                room,
+                user,
+                recipient: Some(User {
+                    name: tag("msg-param-recipient-display-name")
+                        .or_else(|| tag("msg-param-recipient-user-name"))?
+                        .into(),
+                    id: parsed("msg-param-recipient-id")?,
+                }),
+                count: 1,
+                plan: plan().unwrap_or_default(),
+            }),
+
+            "submysterygift" | "anonsubmysterygift" => Self::GiftSub(GiftSub {
+                room,
+                user,
+                recipient: None,
+                count: parsed("msg-param-mass-gift-count").unwrap_or(1),
+                plan: plan().unwrap_or_default(),
+            }),
+
+            "raid" => Self::Raid(Raid {
+                room,
+                user,
+                viewers: parsed("msg-param-viewerCount").unwrap_or_default(),
+            }),
+
+            "ritual" => Self::Ritual(Ritual {
+                room,
+                user,
+                name: tag("msg-param-ritual-name")?,
+                message,
+            }),
+
+            _ => return None,
+        };
+
+        Some(notice)
+    }
+}
+
+impl Cheer {
+    pub(crate) fn from_privmsg(message: Arc<Privmsg<'static>>) -> Option<Self> {
+        let bits = message
+            .tags
+            .get("bits")
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|&bits| bits > 0)?;
+
+        let room = Room {
+            name: message.channel.to_string().into(),
+            id: message.room_id()?,
+        };
+
+        let user = User {
+            name: message
+                .display_name()
+                .unwrap_or_else(|| &message.name)
+                .to_string()
+                .into(),
+            id: message.user_id()?,
+        };
+
+        Some(Self {
+            room,
+            user,
+            bits,
+            message,
+        })
+    }
+}
+
+impl Host {
+    /// HOSTTARGET doesn't have tags, so the room ids have to be known ahead of time
+    pub(crate) fn from_host_target(
+        msg: &HostTarget<'static>,
+        room_ids: &HashMap<String, u64>,
+    ) -> Option<Self> {
+        let channel = format!("#{}", msg.source.trim_start_matches('#'));
+        let id = *room_ids.get(&channel)?;
+
+        let user = User {
+            name: channel[1..].to_string().into(),
+            id,
+        };
+        let room = Room {
+            name: channel.into(),
+            id,
+        };
+
+        let target = match &msg.kind {
+            HostTargetKind::Start { target } => Some(target.to_string()),
+            HostTargetKind::End => None,
+        };
+
+        Some(Self {
+            room,
+            user,
+            target,
+            viewers: msg.viewers.map(|n| n as _),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(tags: &[(&str, &str)], message: Option<&str>) -> Option<Notice> {
+        let tags = tags
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>();
+        Notice::from_tags("#museun", message, |key| tags.get(key).cloned())
+    }
+
+    const COMMON: &[(&str, &str)] = &[
+        ("room-id", "23196011"),
+        ("user-id", "1234"),
+        ("login", "someone"),
+        ("display-name", "Someone"),
+    ];
+
+    fn with(tags: &[(&'static str, &'static str)]) -> Vec<(&'static str, &'static str)> {
+        COMMON.iter().chain(tags).copied().collect()
+    }
+
+    #[test]
+    fn sub() {
+        let tags = with(&[
+            ("msg-id", "resub"),
+            ("msg-param-cumulative-months", "12"),
+            ("msg-param-streak-months", "3"),
+            ("msg-param-should-share-streak", "1"),
+            ("msg-param-sub-plan", "Prime"),
+        ]);
+
+        match parse(&tags, Some("hello")).unwrap() {
+            Notice::Sub(sub) => {
+                assert!(sub.resub);
+                assert_eq!(sub.months, 12);
+                assert_eq!(sub.streak, Some(3));
+                assert_eq!(sub.plan, SubPlan::Prime);
+                assert_eq!(sub.message.as_deref(), Some("hello"));
+                assert_eq!(sub.user().name, "Someone");
+                assert_eq!(sub.room().id, 23196011);
+            }
+            notice => panic!("expected a sub, got: {:?}", notice),
+        }
+
+        let tags = with(&[
+            ("msg-id", "sub"),
+            ("msg-param-streak-months", "3"),
+            ("msg-param-should-share-streak", "0"),
+            ("msg-param-sub-plan", "2000"),
+        ]);
+
+        match parse(&tags, None).unwrap() {
+            Notice::Sub(sub) => {
+                assert!(!sub.resub);
+                assert_eq!(sub.months, 1);
+                assert_eq!(sub.streak, None);
+                assert_eq!(sub.plan, SubPlan::Tier2);
+            }
+            notice => panic!("expected a sub, got: {:?}", notice),
+        }
+    }
+
+    #[test]
+    fn gift_sub() {
+        let tags = with(&[
+            ("msg-id", "subgift"),
+            ("msg-param-recipient-display-name", "Other"),
+            ("msg-param-recipient-id", "4321"),
+        ]);
+
+        match parse(&tags, None).unwrap() {
+            Notice::GiftSub(gift) => {
+                let recipient = gift.recipient.unwrap();
+                assert_eq!(recipient.name, "Other");
+                assert_eq!(recipient.id, 4321);
+                assert_eq!(gift.count, 1);
+            }
+            notice => panic!("expected a gift sub, got: {:?}", notice),
+        }
+
+        let tags = with(&[
+            ("msg-id", "submysterygift"),
+            ("msg-param-mass-gift-count", "5"),
+        ]);
+
+        match parse(&tags, None).unwrap() {
+            Notice::GiftSub(gift) => {
+                assert!(gift.recipient.is_none());
+                assert_eq!(gift.count, 5);
+            }
+            notice => panic!("expected a gift sub, got: {:?}", notice),
+        }
+
+        // the recipient is required for a directed gift
+        assert!(parse(&with(&[("msg-id", "subgift")]), None).is_none());
+    }
+
+    #[test]
+    fn raid_and_ritual() {
+        let tags = with(&[("msg-id", "raid"), ("msg-param-viewerCount", "42")]);
+        match parse(&tags, None).unwrap() {
+            Notice::Raid(raid) => assert_eq!(raid.viewers, 42),
+            notice => panic!("expected a raid, got: {:?}", notice),
+        }
+
+        let tags = with(&[
+            ("msg-id", "ritual"),
+            ("msg-param-ritual-name", "new_chatter"),
+        ]);
+        match parse(&tags, Some("hi")).unwrap() {
+            Notice::Ritual(ritual) => assert_eq!(ritual.name, "new_chatter"),
+            notice => panic!("expected a ritual, got: {:?}", notice),
+        }
+    }
+
+    #[test]
+    fn unknown() {
+        assert!(parse(&with(&[("msg-id", "bitsbadgetier")]), None).is_none());
+        assert!(parse(&[("msg-id", "raid")], None).is_none());
+    }
+}
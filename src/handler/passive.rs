@@ -4,11 +4,15 @@ use crate::{Handler, RespondableContext, Responder, Room, User};
 use twitchchat::messages::Privmsg;
 
 use futures::prelude::*;
+use regex::Regex;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct Passive {
     pub message: Arc<Privmsg<'static>>,
+    matches: Arc<Matches>,
+    mentioned: bool,
     room_id: u64,
     user_id: u64,
 }
@@ -23,10 +27,147 @@ impl Passive {
 
         Some(Self {
             message,
+            matches: Default::default(),
+            mentioned: false,
             room_id,
             user_id,
         })
     }
+
+    /// What the pattern for this handler matched
+    ///
+    /// This is empty for handlers that aren't registered with a pattern
+    pub fn matches(&self) -> &Matches {
+        &self.matches
+    }
+
+    /// Whether this message starts by mentioning the bot
+    pub fn mentions_us(&self) -> bool {
+        self.mentioned
+    }
+
+    pub(crate) fn with_mention(self, mention: &Regex) -> Self {
+        let mentioned = mention.is_match(&self.message.data);
+        Self { mentioned, ..self }
+    }
+
+    pub(crate) fn with_matches(&self, matches: Matches) -> Self {
+        Self {
+            matches: Arc::new(matches),
+            ..self.clone()
+        }
+    }
+}
+
+/// What should trigger a passive handler
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// Every message
+    Any,
+    /// Messages matching this regex
+    Regex(Regex),
+    /// Messages containing this keyword (or phrase), ignoring case
+    ///
+    /// Use `Pattern::keyword` to create this
+    Keyword(Regex),
+    /// Messages starting with a mention of the bot (e.g. `@shaken_bot` or `shaken_bot:`)
+    Mention,
+}
+
+impl Pattern {
+    pub fn regex(re: &str) -> anyhow::Result<Self> {
+        Regex::new(re)
+            .map(Self::Regex)
+            .map_err(|err| anyhow::anyhow!("invalid pattern '{}': {}", re, err))
+    }
+
+    pub fn keyword(keyword: &str) -> Self {
+        Self::Keyword(Self::keyword_regex(keyword))
+    }
+
+    /// Builds a case-insensitive regex that matches the keyword on word boundaries
+    pub fn keyword_regex(keyword: &str) -> Regex {
+        Regex::new(&Self::keyword_pattern(keyword)).expect("escaped keyword must be a valid regex")
+    }
+
+    /// The source of `keyword_regex`, allowing any amount of whitespace between the words
+    pub(crate) fn keyword_pattern(keyword: &str) -> String {
+        let keyword = keyword.trim();
+        let is_word = |c: Option<char>| c.filter(|c| c.is_alphanumeric() || *c == '_').is_some();
+        // a \b next to a non-word character would never match, so only use it when needed
+        let (head, tail) = (
+            if is_word(keyword.chars().next()) {
+                r"\b"
+            } else {
+                ""
+            },
+            if is_word(keyword.chars().last()) {
+                r"\b"
+            } else {
+                ""
+            },
+        );
+        let words = keyword
+            .split_whitespace()
+            .map(regex::escape)
+            .collect::<Vec<_>>();
+        format!("(?i){}{}{}", head, words.join(r"\s+"), tail)
+    }
+
+    pub(crate) fn mention_regex(name: &str) -> Regex {
+        let re = format!(r"(?i)^@?{}\b", regex::escape(name));
+        Regex::new(&re).expect("escaped name must be a valid regex")
+    }
+
+    /// Try to match the input, `mention` is from `Pattern::mention_regex`
+    pub(crate) fn find(&self, input: &str, mention: &Regex) -> Option<Matches> {
+        match self {
+            Self::Any => Some(Matches::default()),
+            Self::Regex(re) | Self::Keyword(re) => Matches::find(re, input),
+            Self::Mention => Matches::find(mention, input),
+        }
+    }
+}
+
+/// Captures from a `Pattern`
+#[derive(Debug, Clone, Default)]
+pub struct Matches {
+    captures: Vec<Option<String>>,
+    names: HashMap<String, String>,
+}
+
+impl Matches {
+    fn find(re: &Regex, input: &str) -> Option<Self> {
+        let caps = re.captures(input)?;
+        let captures = caps
+            .iter()
+            .map(|cap| cap.map(|cap| cap.as_str().to_string()))
+            .collect();
+        let names = re
+            .capture_names()
+            .flatten()
+            .filter_map(|name| Some((name.to_string(), caps.name(name)?.as_str().to_string())))
+            .collect();
+        Some(Self { captures, names })
+    }
+
+    /// Get a capture by its index. `0` is the entire match
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.captures.get(index)?.as_deref()
+    }
+
+    /// Get a named capture
+    pub fn name(&self, name: &str) -> Option<&str> {
+        self.names.get(name).map(|s| s.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.captures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.captures.is_empty()
+    }
 }
 
 impl RespondableContext for Passive {
@@ -53,12 +194,14 @@ impl RespondableContext for Passive {
 #[derive(Clone)]
 pub struct WrappedPassive<R> {
     pub inner: Arc<DynHandler<Passive, R>>,
+    pub pattern: Pattern,
     pub id: usize,
 }
 
 impl<R> std::fmt::Debug for WrappedPassive<R> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("WrappedPassive")
+            .field("pattern", &self.pattern)
             .field("id", &self.id)
             .finish()
     }
@@ -84,6 +227,17 @@ impl<R: Responder + Send + 'static> Default for PassiveList<R> {
 
 impl<R: Responder + Send + 'static> PassiveList<R> {
     pub fn add<H, F>(&mut self, handler: H) -> usize
+    where
+        H: Handler<Passive, R, Fut = F>,
+        F: Future<Output = anyhow::Result<()>>,
+        F::Output: Send + 'static,
+        F: Send + 'static,
+    {
+        self.add_pattern(Pattern::Any, handler)
+    }
+
+    /// Add a handler that is only called when the pattern matches
+    pub fn add_pattern<H, F>(&mut self, pattern: Pattern, handler: H) -> usize
     where
         H: Handler<Passive, R, Fut = F>,
         F: Future<Output = anyhow::Result<()>>,
@@ -94,6 +248,7 @@ impl<R: Responder + Send + 'static> PassiveList<R> {
         let id = std::mem::replace(&mut self.id, next);
         self.inner.push(WrappedPassive {
             inner: Arc::new(move |state, resp| handler.call(state, resp)),
+            pattern,
             id,
         });
         id
@@ -126,3 +281,69 @@ impl<'a, R> Iterator for PassiveIter<'a, R> {
         self.inner.inner.get(pos)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyword() {
+        let mention = Pattern::mention_regex("shaken_bot");
+
+        let pattern = Pattern::keyword("what keyboard");
+        assert!(pattern.find("what keyboard is that?", &mention).is_some());
+        assert!(pattern.find("hey, WHAT Keyboard?", &mention).is_some());
+        assert!(pattern.find("what  keyboard", &mention).is_some());
+        assert!(pattern.find("somewhat keyboards", &mention).is_none());
+        assert!(pattern.find("what is the keyboard", &mention).is_none());
+
+        let pattern = Pattern::keyword("c++");
+        assert!(pattern.find("do you like c++?", &mention).is_some());
+        assert!(pattern.find("c+", &mention).is_none());
+    }
+
+    #[test]
+    fn regex() {
+        let mention = Pattern::mention_regex("shaken_bot");
+
+        let pattern = Pattern::regex(r"(?P<user>\w+) is (\d+)").unwrap();
+        let matches = pattern.find("museun is 42 today", &mention).unwrap();
+        assert_eq!(matches.get(0), Some("museun is 42"));
+        assert_eq!(matches.get(1), Some("museun"));
+        assert_eq!(matches.get(2), Some("42"));
+        assert_eq!(matches.get(3), None);
+        assert_eq!(matches.name("user"), Some("museun"));
+        assert_eq!(matches.name("age"), None);
+
+        assert!(pattern.find("nothing here", &mention).is_none());
+        Pattern::regex("(unclosed").unwrap_err();
+    }
+
+    #[test]
+    fn mention() {
+        let mention = Pattern::mention_regex("shaken_bot");
+
+        for input in &[
+            "@shaken_bot hello",
+            "shaken_bot: hi",
+            "@Shaken_Bot",
+            "SHAKEN_BOT, hey",
+        ] {
+            assert!(
+                Pattern::Mention.find(input, &mention).is_some(),
+                "{}",
+                input
+            );
+        }
+
+        for input in &["hello @shaken_bot", "@shaken_bots", "shaken"] {
+            assert!(
+                Pattern::Mention.find(input, &mention).is_none(),
+                "{}",
+                input
+            );
+        }
+
+        assert!(Pattern::Any.find("anything", &mention).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,279 @@
+use std::time::Duration;
+use time::{Date, OffsetDateTime, PrimitiveDateTime};
+
+/// How long after its minute starts a cron timer wakes up
+const WAKE_MARGIN: Duration = Duration::from_millis(10);
+
+/// When a timer should fire
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    /// Fire every `Duration`, starting one `Duration` after the room is joined
+    Every(Duration),
+    /// Fire on a cron-style schedule (in UTC)
+    Cron(Cron),
+}
+
+impl Schedule {
+    pub fn every(interval: Duration) -> Self {
+        Self::Every(interval)
+    }
+
+    pub fn cron(spec: &str) -> anyhow::Result<Self> {
+        Cron::parse(spec).map(Self::Cron)
+    }
+
+    /// How long to wait from `now` until the next time this should fire
+    ///
+    /// This returns None if the schedule can never fire again
+    pub fn next_delay(&self, now: OffsetDateTime) -> Option<Duration> {
+        match self {
+            Self::Every(interval) => Some(*interval),
+            Self::Cron(cron) => {
+                let now = PrimitiveDateTime::new(now.date(), now.time());
+                let next = cron.next_after(now)?;
+                // round up and wake just after the minute starts, otherwise the
+                // timer would wake before it and fire again for the same minute
+                let millis = (next - now).whole_milliseconds().max(0) as u64 + 1;
+                Some(Duration::from_millis(millis) + WAKE_MARGIN)
+            }
+        }
+    }
+}
+
+/// A parsed cron-style schedule
+///
+/// This uses the five-field format: `minute hour day-of-month month day-of-week`
+///
+/// Each field can be `*`, a value, a range (`a-b`), a step (`*/n` or `a-b/n`)
+/// or a comma separated list of those. Days of the week are `0-6` starting on
+/// Sunday (`7` is also Sunday)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    minutes: Field,
+    hours: Field,
+    days: Field,
+    months: Field,
+    weekdays: Field,
+}
+
+impl Cron {
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let fields = spec.split_whitespace().collect::<Vec<_>>();
+        anyhow::ensure!(
+            fields.len() == 5,
+            "expected 5 fields in cron spec '{}', got {}",
+            spec,
+            fields.len()
+        );
+
+        let mut weekdays = Field::parse(fields[4], 0, 7)?;
+        // 7 is an alias for sunday
+        if weekdays.contains(7) {
+            weekdays.set(0);
+        }
+
+        Ok(Self {
+            minutes: Field::parse(fields[0], 0, 59)?,
+            hours: Field::parse(fields[1], 0, 23)?,
+            days: Field::parse(fields[2], 1, 31)?,
+            months: Field::parse(fields[3], 1, 12)?,
+            weekdays,
+        })
+    }
+
+    /// Find the next minute strictly after `after` that this schedule matches
+    pub fn next_after(&self, after: PrimitiveDateTime) -> Option<PrimitiveDateTime> {
+        let mut next = after
+            .date()
+            .try_with_hms(after.hour(), after.minute(), 0)
+            .ok()?
+            + time::Duration::minutes(1);
+
+        // if nothing matches within a few years, then nothing ever will (e.g. Feb 30)
+        let limit = next + time::Duration::days(366 * 4);
+        while next <= limit {
+            let date = next.date();
+            if !self.months.contains(date.month()) {
+                let (year, month) = match date.month() {
+                    12 => (date.year() + 1, 1),
+                    month => (date.year(), month + 1),
+                };
+                next = Date::try_from_ymd(year, month, 1).ok()?.midnight();
+                continue;
+            }
+
+            if !self.day_matches(date) {
+                next = date.next_day().midnight();
+                continue;
+            }
+
+            if !self.hours.contains(next.hour()) {
+                next = date.try_with_hms(next.hour(), 0, 0).ok()? + time::Duration::hours(1);
+                continue;
+            }
+
+            if !self.minutes.contains(next.minute()) {
+                next += time::Duration::minutes(1);
+                continue;
+            }
+
+            return Some(next);
+        }
+
+        None
+    }
+
+    fn day_matches(&self, date: Date) -> bool {
+        let day = self.days.contains(date.day());
+        let weekday = self
+            .weekdays
+            .contains(date.weekday().number_days_from_sunday());
+
+        // like cron, if both are restricted then either one can match
+        match (self.days.any, self.weekdays.any) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    bits: u64,
+    any: bool,
+}
+
+impl Field {
+    fn parse(input: &str, min: u8, max: u8) -> anyhow::Result<Self> {
+        let mut this = Self {
+            bits: 0,
+            any: input == "*",
+        };
+
+        for part in input.split(',') {
+            let (range, step) = match part.find('/') {
+                Some(pos) => {
+                    let step = part[pos + 1..]
+                        .parse::<u8>()
+                        .map_err(|err| anyhow::anyhow!("invalid step in '{}': {}", part, err))?;
+                    anyhow::ensure!(step > 0, "step cannot be zero in '{}'", part);
+                    (&part[..pos], step)
+                }
+                None => (part, 1),
+            };
+
+            let (start, end) = match range {
+                "*" => (min, max),
+                range => {
+                    let mut iter = range.splitn(2, '-').map(|s| {
+                        s.parse::<u8>()
+                            .map_err(|err| anyhow::anyhow!("invalid value in '{}': {}", part, err))
+                    });
+                    let start = iter.next().unwrap()?;
+                    let end = iter.next().transpose()?.unwrap_or(start);
+                    (start, end)
+                }
+            };
+
+            anyhow::ensure!(
+                min <= start && start <= end && end <= max,
+                "'{}' must be within {}-{}",
+                part,
+                min,
+                max
+            );
+
+            for n in (start..=end).step_by(step as _) {
+                this.set(n);
+            }
+        }
+
+        Ok(this)
+    }
+
+    fn set(&mut self, n: u8) {
+        self.bits |= 1 << n
+    }
+
+    fn contains(&self, n: u8) -> bool {
+        self.bits & (1 << n) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u8, d: u8, hh: u8, mm: u8) -> PrimitiveDateTime {
+        Date::try_from_ymd(y, m, d)
+            .unwrap()
+            .try_with_hms(hh, mm, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        Cron::parse("* * * * *").unwrap();
+        Cron::parse("*/5 0-6,18-23 1 */2 1-5").unwrap();
+        Cron::parse("0 12 * * 7").unwrap();
+
+        Cron::parse("* * * *").unwrap_err();
+        Cron::parse("60 * * * *").unwrap_err();
+        Cron::parse("* 24 * * *").unwrap_err();
+        Cron::parse("* * 0 * *").unwrap_err();
+        Cron::parse("*/0 * * * *").unwrap_err();
+        Cron::parse("5-1 * * * *").unwrap_err();
+        Cron::parse("a * * * *").unwrap_err();
+    }
+
+    #[test]
+    fn next_after() {
+        let tests = vec![
+            ("* * * * *", at(2020, 4, 6, 12, 30), at(2020, 4, 6, 12, 31)),
+            (
+                "*/15 * * * *",
+                at(2020, 4, 6, 12, 30),
+                at(2020, 4, 6, 12, 45),
+            ),
+            ("0 * * * *", at(2020, 4, 6, 12, 30), at(2020, 4, 6, 13, 0)),
+            ("30 9 * * *", at(2020, 4, 6, 12, 30), at(2020, 4, 7, 9, 30)),
+            ("0 0 1 * *", at(2020, 12, 6, 12, 30), at(2021, 1, 1, 0, 0)),
+            // 2020-04-06 was a monday
+            ("0 0 * * 0", at(2020, 4, 6, 12, 30), at(2020, 4, 12, 0, 0)),
+            ("0 0 * * 7", at(2020, 4, 6, 12, 30), at(2020, 4, 12, 0, 0)),
+            // either the day or the weekday
+            ("0 0 10 * 3", at(2020, 4, 6, 12, 30), at(2020, 4, 8, 0, 0)),
+            ("0 0 29 2 *", at(2020, 3, 1, 0, 0), at(2024, 2, 29, 0, 0)),
+        ];
+
+        for (spec, after, expected) in tests {
+            let cron = Cron::parse(spec).unwrap();
+            assert_eq!(cron.next_after(after).unwrap(), expected, "{}", spec);
+        }
+
+        assert!(Cron::parse("0 0 30 2 *")
+            .unwrap()
+            .next_after(at(2020, 1, 1, 0, 0))
+            .is_none());
+    }
+
+    #[test]
+    fn next_delay_just_before_the_minute() {
+        let schedule = Schedule::cron("* * * * *").unwrap();
+        let now = Date::try_from_ymd(2020, 4, 6)
+            .unwrap()
+            .try_with_hms_milli(12, 29, 59, 700)
+            .unwrap()
+            .assume_utc();
+
+        let delay = schedule.next_delay(now).unwrap();
+        assert!(delay >= Duration::from_millis(300), "{:?}", delay);
+        assert!(delay < Duration::from_secs(1), "{:?}", delay);
+
+        // after waking up, the next time is a whole minute away instead of firing again
+        let woke = now + delay;
+        assert_eq!(woke.minute(), 30);
+        let delay = schedule.next_delay(woke).unwrap();
+        assert!(delay > Duration::from_secs(59), "{:?}", delay);
+    }
+}
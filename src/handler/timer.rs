@@ -0,0 +1,92 @@
+use crate::handler::{DynHandler, Schedule};
+use crate::{Handler, RespondableContext, Responder, Room, User};
+
+use futures::prelude::*;
+use std::sync::Arc;
+
+/// Arguments for a handler that runs on a schedule in a room
+#[derive(Debug, Clone)]
+pub struct Timer {
+    room: Room<'static>,
+    user: User<'static>,
+}
+
+impl Timer {
+    pub(crate) fn new(room: Room<'static>, user: User<'static>) -> Self {
+        Self { room, user }
+    }
+}
+
+impl RespondableContext for Timer {
+    fn room(&self) -> Room<'_> {
+        self.room.clone()
+    }
+
+    // timers aren't triggered by anyone, so this is our user
+    fn user(&self) -> User<'_> {
+        self.user.clone()
+    }
+}
+
+#[derive(Clone)]
+pub struct WrappedTimer<R> {
+    pub inner: Arc<DynHandler<Timer, R>>,
+    pub schedule: Schedule,
+    pub id: usize,
+}
+
+impl<R> std::fmt::Debug for WrappedTimer<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WrappedTimer")
+            .field("schedule", &self.schedule)
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct TimerList<R> {
+    inner: Vec<WrappedTimer<R>>,
+    id: usize,
+    _phantom: std::marker::PhantomData<R>,
+}
+
+impl<R: Responder + Send + 'static> Default for TimerList<R> {
+    fn default() -> Self {
+        let (inner, id, _phantom) = Default::default();
+        Self {
+            inner,
+            id,
+            _phantom,
+        }
+    }
+}
+
+impl<R: Responder + Send + 'static> TimerList<R> {
+    pub fn add<H, F>(&mut self, schedule: Schedule, handler: H) -> usize
+    where
+        H: Handler<Timer, R, Fut = F>,
+        F: Future<Output = anyhow::Result<()>>,
+        F::Output: Send + 'static,
+        F: Send + 'static,
+    {
+        let next = self.id + 1;
+        let id = std::mem::replace(&mut self.id, next);
+        self.inner.push(WrappedTimer {
+            inner: Arc::new(move |state, resp| handler.call(state, resp)),
+            schedule,
+            id,
+        });
+        id
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<()> {
+        let n = self.inner.iter().position(|s| s.id == id)?;
+        self.inner.swap_remove(n);
+        Some(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &WrappedTimer<R>> + '_ {
+        self.inner.iter()
+    }
+}
@@ -70,6 +70,16 @@ pub struct User {
     pub display_name: String,
 }
 
+/// A Twitch game (or category)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Game {
+    #[serde(deserialize_with = "serde_util::from_str")]
+    /// The game id
+    pub id: u64,
+    /// The name of the game
+    pub name: String,
+}
+
 impl Client {
     const BASE_URI: &'static str = "https://api.twitch.tv/helix";
 
@@ -151,6 +161,22 @@ impl Client {
             .map(|data| data.data)
     }
 
+    /// Get a collection of games for the provided game ids
+    pub async fn get_games<I>(&self, game_ids: I) -> anyhow::Result<Vec<Game>>
+    where
+        I: IntoIterator,
+        I::Item: serde::Serialize,
+    {
+        #[derive(Deserialize)]
+        struct Data {
+            data: Vec<Game>,
+        }
+
+        self.get_response::<Data, _, _>("games", std::iter::repeat("id").zip(game_ids))
+            .await
+            .map(|data| data.data)
+    }
+
     /// Get a collection of users for a Twitch channel
     pub async fn get_users_for(&self, room: &str) -> anyhow::Result<Users> {
         #[derive(Deserialize)]
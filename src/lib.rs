@@ -11,6 +11,9 @@ pub mod args;
 mod bot;
 pub use bot::Bot;
 
+pub mod bus;
+pub use bus::EventBus;
+
 pub mod config;
 pub use config::*;
 
@@ -18,14 +21,23 @@ mod context;
 use context::Context;
 
 mod store;
-pub use store::resolver;
+pub use store::{database, export, forget_user, migrations, resolver};
+pub use store::{
+    ArchivedMessage, Balance, ChannelState, Channels, ChatArchive, ChatHistory, ChatMessage,
+    ChatQuery, Counter, CounterOverflow, Counters, DeliveryError, Forgotten, Identity,
+    KeyValueStore, LedgerEntry, OptOuts, PastName, Presence, Tracker, Wallets, WatchTime,
+    FORGOTTEN_USER,
+};
 use store::{Resolver, State};
 
 mod format;
 pub use format::Timestamp;
 
 mod handler;
-pub use handler::{Command, CommandMap, DynHandler, Handler, Passive, PassiveList};
+pub use handler::{
+    Cheer, Command, CommandMap, Cron, DynHandler, EventMap, GiftSub, Handler, Host, Matches,
+    Passive, PassiveList, Pattern, Raid, Ritual, Schedule, Sub, SubPlan, Timer, TimerList,
+};
 
 mod http;
 pub use http::twitch::Client as TwitchClient;
@@ -40,6 +52,9 @@ mod responder;
 pub use responder::{LoggingResponder, NullResponder, WriterResponder};
 use responder::{RespondableContext, Responder};
 
+mod role;
+pub use role::Role;
+
 mod room;
 use room::Room;
 
@@ -0,0 +1,122 @@
+use std::time::Duration;
+use {super::*, crate::*};
+
+#[derive(Debug, Template)]
+#[namespace("chat_log")]
+enum Response<'a> {
+    Seen {
+        name: &'a str,
+        ago: String,
+    },
+    LastMessage {
+        name: &'a str,
+        data: &'a str,
+        ago: String,
+    },
+    NotSeen {
+        name: &'a str,
+    },
+    NotLogged,
+}
+
+/// How often old messages are removed from the chat log
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub async fn initialize<R>(init: &mut ModuleInit<'_, R>)
+where
+    R: Responder + Send + 'static,
+{
+    init.command_map.add("seen", seen);
+    init.command_map.add("lastmsg", last_message);
+    init.passive_list.add(archive);
+    init.timer_list.add(Schedule::every(PRUNE_INTERVAL), prune);
+}
+
+async fn archive<R>(context: Context<Passive>, _responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    // logging is opt-in for each room
+    if !context.config.chat_log.is_logged(&context.room().name) {
+        return dont_care();
+    }
+
+    let msg = ChatMessage::from_privmsg(&context.args.message).dont_care()?;
+    let archive = {
+        let state = context.state().await;
+        if state.expect_get::<OptOuts>()?.contains(msg.user_id) {
+            return dont_care();
+        }
+        state.expect_get::<ChatArchive>()?.clone()
+    };
+    archive.append(&msg).await
+}
+
+async fn seen<R>(context: Context<Command>, responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    last_seen(context, responder, false).await
+}
+
+async fn last_message<R>(context: Context<Command>, responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    last_seen(context, responder, true).await
+}
+
+async fn last_seen<R>(context: Context<Command>, mut responder: R, with_message: bool) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let login = context.args.tail.first().dont_care()?;
+    let login = login.trim_start_matches('@');
+
+    if !context.config.chat_log.is_logged(&context.room().name) {
+        return responder.reply(&context, &Response::NotLogged).await;
+    }
+
+    let archive = context.state().await.expect_get::<ChatArchive>()?.clone();
+    let msg = match archive.last_from(context.room().id, login).await? {
+        Some(msg) => msg,
+        None => {
+            let resp = Response::NotSeen { name: login };
+            return responder.reply(&context, &resp).await;
+        }
+    };
+
+    let at = time::OffsetDateTime::from_unix_timestamp(msg.at);
+    let ago = (time::OffsetDateTime::now() - at).as_readable_time();
+    let resp = match with_message {
+        true => Response::LastMessage {
+            name: &msg.name,
+            data: &msg.data,
+            ago,
+        },
+        false => Response::Seen {
+            name: &msg.name,
+            ago,
+        },
+    };
+    responder.reply(&context, &resp).await
+}
+
+async fn prune<R>(context: Context<Timer>, _responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let days = context.config.chat_log.retention_days;
+    if days == 0 {
+        return Ok(());
+    }
+
+    let room = context.room();
+    let before = time::OffsetDateTime::now() - time::Duration::days(days as i64);
+    let archive = context.state().await.expect_get::<ChatArchive>()?.clone();
+    let n = archive.prune(room.id, before).await?;
+    if n > 0 {
+        log::info!("removed {} old messages from the chat log of {}", n, room);
+    }
+    Ok(())
+}
@@ -0,0 +1,230 @@
+use std::time::Duration;
+use {super::*, crate::*};
+
+#[derive(Debug, Template)]
+#[namespace("counters")]
+enum Response<'a> {
+    Value { name: &'a str, value: i64 },
+    Created { name: &'a str },
+    Removed { name: &'a str },
+    Reset { name: &'a str },
+    List { counters: String },
+    NoCounters,
+    ErrorTaken { name: &'a str },
+    ErrorNotFound { name: &'a str },
+    ErrorInvalidName { name: &'a str },
+    ErrorOverflow { name: &'a str },
+    ErrorUsage { usage: &'a str },
+}
+
+/// How often live rooms are checked for a new stream
+const STREAM_CHECK: Duration = Duration::from_secs(5 * 60);
+
+const USAGE: &str = "!counter add|remove|reset name [--per-stream]";
+
+pub async fn initialize<R>(init: &mut ModuleInit<'_, R>)
+where
+    R: Responder + Send + 'static,
+{
+    init.command_map.add("counter", counter);
+    init.command_map.add("counters", list);
+    init.passive_list.add(use_counter);
+    init.timer_list
+        .add(Schedule::every(STREAM_CHECK), reset_per_stream);
+}
+
+async fn counter<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    if Role::of(&context.args.message) < Role::Moderator {
+        return dont_care();
+    }
+
+    let mut args = context.args.tail.iter().map(|s| s.as_str());
+    let (action, name) = match (args.next(), args.next()) {
+        (Some(action), Some(name)) => (action, name.trim_start_matches('!').to_lowercase()),
+        _ => {
+            let resp = Response::ErrorUsage { usage: USAGE };
+            return responder.reply(&context, &resp).await;
+        }
+    };
+
+    let per_stream = match args.next() {
+        Some("--per-stream") if action == "add" => true,
+        None => false,
+        Some(..) => {
+            let resp = Response::ErrorUsage { usage: USAGE };
+            return responder.reply(&context, &resp).await;
+        }
+    };
+
+    if !Counters::is_valid_name(&name) {
+        let resp = Response::ErrorInvalidName { name: &name };
+        return responder.reply(&context, &resp).await;
+    }
+
+    let counters = context.state().await.expect_get::<Counters>()?.clone();
+    let room = context.room().id;
+
+    let resp = match action {
+        "add" if counters.create(room, &name, per_stream).await? => {
+            Response::Created { name: &name }
+        }
+        "add" => Response::ErrorTaken { name: &name },
+        "remove" | "delete" if counters.remove(room, &name).await? => {
+            Response::Removed { name: &name }
+        }
+        "reset" if counters.reset(room, &name).await?.is_some() => Response::Reset { name: &name },
+        "remove" | "delete" | "reset" => Response::ErrorNotFound { name: &name },
+        _ => Response::ErrorUsage { usage: USAGE },
+    };
+    responder.reply(&context, &resp).await
+}
+
+async fn list<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let counters = context.state().await.expect_get::<Counters>()?.clone();
+    let counters = counters
+        .all(context.room().id)
+        .await?
+        .into_iter()
+        .map(|counter| format!("!{} ({})", counter.name, counter.value))
+        .collect::<Vec<_>>();
+
+    let resp = match counters.is_empty() {
+        true => Response::NoCounters,
+        false => Response::List {
+            counters: counters.join(", "),
+        },
+    };
+    responder.say(&context, &resp).await
+}
+
+/// `!name` shows the counter, moderators can change it with `!name +1`
+async fn use_counter<R>(context: Context<Passive>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let mut parts = context.data().split_whitespace();
+    let name = parts
+        .next()
+        .filter(|head| head.starts_with('!'))
+        .map(|head| head[1..].to_lowercase())
+        .filter(|name| Counters::is_valid_name(name))
+        .dont_care()?;
+
+    let change = match parts.next() {
+        Some(change) => Some(Change::parse(change).dont_care()?),
+        None => None,
+    };
+
+    let counters = context.state().await.expect_get::<Counters>()?.clone();
+    let room = context.room().id;
+
+    let value = match change {
+        Some(..) if Role::of(&context.args.message) < Role::Moderator => {
+            return dont_care();
+        }
+        Some(Change::Add(delta)) => match counters.add(room, &name, delta).await {
+            Err(err) if err.is::<CounterOverflow>() => {
+                let resp = Response::ErrorOverflow { name: &name };
+                return responder.say(&context, &resp).await;
+            }
+            value => value?,
+        },
+        Some(Change::Set(value)) => counters.set(room, &name, value).await?,
+        None => counters.get(room, &name).await?,
+    };
+
+    // it isn't a counter
+    let value = value.dont_care()?;
+    responder
+        .say(&context, &Response::Value { name: &name, value })
+        .await
+}
+
+async fn reset_per_stream<R>(context: Context<Timer>, _responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let (counters, client) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<Counters>()?.clone(),
+            state.expect_get::<TwitchClient>()?.clone(),
+        )
+    };
+
+    let room = context.room();
+    let any_per_stream = counters
+        .all(room.id)
+        .await?
+        .iter()
+        .any(|counter| counter.per_stream);
+    if !any_per_stream {
+        return Ok(());
+    }
+
+    let stream = client
+        .get_streams_from_id(&[room.id])
+        .await?
+        .into_iter()
+        .find(|stream| stream.user_id == room.id);
+
+    if let Some(stream) = stream {
+        for name in counters
+            .reset_for_stream(room.id, stream.started_at)
+            .await?
+        {
+            log::info!("reset counter '{}' for a new stream in {}", name, room);
+        }
+    }
+    Ok(())
+}
+
+/// A change to a counter: `+`, `-`, `+N`, `-N` or `=N`
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Change {
+    Add(i64),
+    Set(i64),
+}
+
+impl Change {
+    fn parse(input: &str) -> Option<Self> {
+        match input {
+            "+" | "++" => return Some(Self::Add(1)),
+            "-" | "--" => return Some(Self::Add(-1)),
+            _ => {}
+        }
+
+        if input.starts_with('=') {
+            return input[1..].parse().ok().map(Self::Set);
+        }
+        if input.starts_with('+') || input.starts_with('-') {
+            return input.parse().ok().map(Self::Add);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_change() {
+        assert_eq!(Change::parse("+"), Some(Change::Add(1)));
+        assert_eq!(Change::parse("--"), Some(Change::Add(-1)));
+        assert_eq!(Change::parse("+5"), Some(Change::Add(5)));
+        assert_eq!(Change::parse("-2"), Some(Change::Add(-2)));
+        assert_eq!(Change::parse("=10"), Some(Change::Set(10)));
+        assert_eq!(Change::parse("=-1"), Some(Change::Set(-1)));
+
+        assert_eq!(Change::parse("5"), None);
+        assert_eq!(Change::parse("=x"), None);
+        assert_eq!(Change::parse("hello"), None);
+    }
+}
@@ -1,15 +1,18 @@
-use crate::{CommandMap, PassiveList, Responder, State};
+use crate::{CommandMap, EventMap, PassiveList, Responder, State, TimerList};
 
 type Result = anyhow::Result<()>;
 
 pub struct ModuleInit<'a, R> {
     pub secrets: &'a mut crate::secrets::Secrets,
-    //pub pool: sqlx::SqlitePool,
+    pub pool: sqlx::SqlitePool,
     pub config: crate::WatchedConfig,
+    pub bus: crate::EventBus,
 
     pub state: State,
     pub command_map: CommandMap<R>,
     pub passive_list: PassiveList<R>,
+    pub timer_list: TimerList<R>,
+    pub event_map: EventMap<R>,
 
     _responder: std::marker::PhantomData<R>,
 }
@@ -17,22 +20,26 @@ pub struct ModuleInit<'a, R> {
 impl<'a, R: Responder + Send + 'static> ModuleInit<'a, R> {
     pub async fn initialize(
         secrets: &'a mut crate::secrets::Secrets,
-        //pool: sqlx::SqlitePool,
+        pool: sqlx::SqlitePool,
         config: crate::WatchedConfig,
     ) -> anyhow::Result<ModuleInit<'a, R>> {
-        let (command_map, passive_list, state, _responder) = Default::default();
+        let (command_map, passive_list, timer_list, event_map, state, bus, _responder) =
+            Default::default();
         let mut this = ModuleInit {
             secrets,
-            //pool,
+            pool,
             config,
+            bus,
 
             command_map,
             passive_list,
+            timer_list,
+            event_map,
             state,
             _responder,
         };
 
-        this.build_state()?;
+        this.build_state().await?;
 
         shakespeare::initialize(&mut this).await;
         hello::initialize(&mut this).await;
@@ -41,30 +48,61 @@ impl<'a, R: Responder + Send + 'static> ModuleInit<'a, R> {
         crates::initialize(&mut this).await;
         version::initialize(&mut this).await;
         whatsong::initialize(&mut this).await;
+        quotes::initialize(&mut this).await;
+        chat_log::initialize(&mut this).await;
+        notices::initialize(&mut this).await;
+        counters::initialize(&mut this).await;
+        privacy::initialize(&mut this).await;
+        watch_time::initialize(&mut this).await;
+        points::initialize(&mut this).await;
 
         // this has to be at the end so it won't clobber the built-in commands
-        // user_defined::initialize(&mut this).await?;
+        user_defined::initialize(&mut this).await?;
 
         Ok(this)
     }
 
-    fn build_state(&mut self) -> anyhow::Result<()> {
+    /// Open a key-value store for this module
+    pub async fn kv(&self, name: &str) -> anyhow::Result<crate::KeyValueStore> {
+        crate::KeyValueStore::open(self.pool.clone(), name).await
+    }
+
+    async fn build_state(&mut self) -> anyhow::Result<()> {
         // place the state deps here if you need them initialize before any of
         // the modules
         let twitch_client_id = self.secrets.take(crate::secrets::TWITCH_CLIENT_ID)?;
         let client = crate::TwitchClient::new(&twitch_client_id);
         self.state.insert(client);
+        self.state
+            .insert(crate::Counters::new(self.pool.clone(), self.bus.clone()));
+        self.state.insert(crate::ChatHistory::default());
+        self.state
+            .insert(crate::ChatArchive::new(self.pool.clone()));
+        self.state
+            .insert(crate::Tracker::load(self.pool.clone()).await?);
+        self.state
+            .insert(crate::OptOuts::load(self.pool.clone()).await?);
+        self.state.insert(crate::WatchTime::new(self.pool.clone()));
+        self.state.insert(crate::Wallets::new(self.pool.clone()));
 
         Ok(())
     }
 }
 
+mod chat_log;
+mod counters;
 mod crates;
 mod hello;
+mod notices;
+mod points;
+mod privacy;
+mod quotes;
 mod shakespeare;
 mod uptime;
 mod version;
 mod viewers;
+mod watch_time;
 mod whatsong;
 
-//mod user_defined;
+mod user_defined;
+pub(crate) use user_defined::{history, import};
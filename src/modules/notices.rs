@@ -0,0 +1,142 @@
+use {super::*, crate::*};
+
+#[derive(Debug, Template)]
+#[namespace("notices")]
+enum Response<'a> {
+    Sub {
+        name: &'a str,
+        plan: String,
+    },
+    Resub {
+        name: &'a str,
+        months: u64,
+        plan: String,
+    },
+    GiftSub {
+        name: &'a str,
+        recipient: &'a str,
+    },
+    MysteryGiftSub {
+        name: &'a str,
+        count: u64,
+    },
+    Raid {
+        name: &'a str,
+        viewers: u64,
+    },
+    NewChatter {
+        name: &'a str,
+    },
+    Cheer {
+        name: &'a str,
+        bits: u64,
+    },
+    Host {
+        target: &'a str,
+    },
+}
+
+pub async fn initialize<R>(init: &mut ModuleInit<'_, R>)
+where
+    R: Responder + Send + 'static,
+{
+    init.event_map.add(sub);
+    init.event_map.add(gift_sub);
+    init.event_map.add(raid);
+    init.event_map.add(ritual);
+    init.event_map.add(cheer);
+    init.event_map.add(host);
+}
+
+async fn sub<R>(context: Context<Sub>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let user = context.args.user();
+    let plan = context.args.plan.to_string();
+
+    let resp = match context.args.resub {
+        true => Response::Resub {
+            name: &user.name,
+            months: context.args.months,
+            plan,
+        },
+        false => Response::Sub {
+            name: &user.name,
+            plan,
+        },
+    };
+    responder.say(&context, &resp).await
+}
+
+async fn gift_sub<R>(context: Context<GiftSub>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let user = context.args.user();
+    let resp = match &context.args.recipient {
+        Some(recipient) => Response::GiftSub {
+            name: &user.name,
+            recipient: &recipient.name,
+        },
+        None => Response::MysteryGiftSub {
+            name: &user.name,
+            count: context.args.count,
+        },
+    };
+    responder.say(&context, &resp).await
+}
+
+async fn raid<R>(context: Context<Raid>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let user = context.args.user();
+    let resp = Response::Raid {
+        name: &user.name,
+        viewers: context.args.viewers,
+    };
+    responder.say(&context, &resp).await
+}
+
+async fn ritual<R>(context: Context<Ritual>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    if context.args.name != "new_chatter" {
+        return dont_care();
+    }
+
+    let user = context.args.user();
+    if context
+        .state()
+        .await
+        .expect_get::<OptOuts>()?
+        .contains(user.id)
+    {
+        return dont_care();
+    }
+
+    let resp = Response::NewChatter { name: &user.name };
+    responder.say(&context, &resp).await
+}
+
+async fn cheer<R>(context: Context<Cheer>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let user = context.args.user();
+    let resp = Response::Cheer {
+        name: &user.name,
+        bits: context.args.bits,
+    };
+    responder.say(&context, &resp).await
+}
+
+async fn host<R>(context: Context<Host>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let target = context.args.target.as_deref().dont_care()?;
+    responder.say(&context, &Response::Host { target }).await
+}
@@ -0,0 +1,555 @@
+use {super::*, crate::*};
+
+use crate::bus::events::WatchTimeSampled;
+use futures::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use twitchchat::BadgeKind;
+
+#[derive(Debug, Template)]
+#[namespace("points")]
+enum Response<'a> {
+    Balance {
+        name: &'a str,
+        points: i64,
+        currency: &'a str,
+    },
+    Given {
+        from: &'a str,
+        to: &'a str,
+        amount: u64,
+        currency: &'a str,
+    },
+    Added {
+        name: &'a str,
+        amount: u64,
+        points: i64,
+        currency: &'a str,
+    },
+    Removed {
+        name: &'a str,
+        amount: u64,
+        points: i64,
+        currency: &'a str,
+    },
+    Top {
+        users: String,
+        currency: &'a str,
+    },
+    NoPoints {
+        currency: &'a str,
+    },
+    ErrorNotEnough {
+        points: i64,
+        currency: &'a str,
+    },
+    ErrorUnknownUser {
+        name: &'a str,
+    },
+    ErrorOptedOut {
+        name: &'a str,
+    },
+    ErrorUsage {
+        usage: &'a str,
+    },
+}
+
+/// How many users are on the leaderboard
+const TOP_USERS: i64 = 5;
+
+pub async fn initialize<R>(init: &mut ModuleInit<'_, R>)
+where
+    R: Responder + Send + 'static,
+{
+    let mut config = init.config.clone();
+    let mut points = config
+        .next()
+        .await
+        .expect("initial configuration")
+        .points
+        .clone();
+
+    let earnings = Earnings::default();
+    init.state.insert(earnings.clone());
+
+    init.command_map.add("points", balance);
+    init.command_map.add("give", give);
+    init.command_map.add("toppoints", top);
+    init.command_map.add("addpoints", add);
+    init.command_map.add("removepoints", remove);
+    init.passive_list.add(chatting);
+
+    // chatting reads the rates from its context, so keep these in step with the file
+    let wallets = Wallets::new(init.pool.clone());
+    let mut samples = init.bus.subscribe::<WatchTimeSampled>();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                Some(config) = config.next() => points = config.points.clone(),
+                Some(sample) = samples.next() => {
+                    watching(&wallets, &earnings, &points, &sample).await
+                }
+                else => break,
+            }
+        }
+    });
+}
+
+/// Give the users in the sample their points for watching
+async fn watching(
+    wallets: &Wallets,
+    earnings: &Earnings,
+    points: &crate::config::Points,
+    sample: &WatchTimeSampled,
+) {
+    earnings.went_live(sample.room, Duration::from_secs(sample.seconds * 2));
+
+    let base = points.per_minute * sample.seconds / 60;
+    let awards = sample
+        .users
+        .iter()
+        .map(|&user| {
+            let subscriber = earnings.is_subscriber(sample.room, user);
+            (user, points.earned(base, subscriber) as u64)
+        })
+        .filter(|&(_, amount)| amount > 0)
+        .collect::<Vec<_>>();
+
+    if let Err(err) = wallets.award_many(sample.room, &awards, "watching").await {
+        log::warn!("cannot give points for watching: {}", err);
+    }
+}
+
+async fn balance<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let currency = &context.config.points.name;
+    let (wallets, tracker) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<Wallets>()?.clone(),
+            state.expect_get::<Tracker>()?.clone(),
+        )
+    };
+
+    let user = context.user();
+    let (id, name) = match context.args.tail.first() {
+        Some(login) => match tracker.get_by_login(login) {
+            Some(identity) => (identity.id as u64, identity.display_name),
+            None => {
+                let name = login.trim_start_matches('@');
+                let resp = Response::ErrorUnknownUser { name };
+                return responder.reply(&context, &resp).await;
+            }
+        },
+        None => (user.id, user.name.to_string()),
+    };
+
+    let points = wallets.balance(context.room().id, id).await?;
+    let resp = Response::Balance {
+        name: &name,
+        points,
+        currency,
+    };
+    responder.reply(&context, &resp).await
+}
+
+async fn give<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let currency = &context.config.points.name;
+    let (login, amount) = match parse_target(&context) {
+        Some(target) => target,
+        None => {
+            let resp = Response::ErrorUsage {
+                usage: "!give <user> <amount>",
+            };
+            return responder.reply(&context, &resp).await;
+        }
+    };
+
+    let (wallets, tracker, opt_outs) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<Wallets>()?.clone(),
+            state.expect_get::<Tracker>()?.clone(),
+            state.expect_get::<OptOuts>()?.clone(),
+        )
+    };
+
+    let user = context.user();
+    let target = match tracker.get_by_login(login) {
+        Some(target) if target.id as u64 != user.id => target,
+        Some(..) => return dont_care(),
+        None => {
+            let resp = Response::ErrorUnknownUser { name: login };
+            return responder.reply(&context, &resp).await;
+        }
+    };
+    if opt_outs.contains(target.id as u64) {
+        let resp = Response::ErrorOptedOut {
+            name: &target.display_name,
+        };
+        return responder.reply(&context, &resp).await;
+    }
+
+    let room = context.room().id;
+    let resp = match wallets
+        .transfer(room, user.id, target.id as u64, amount)
+        .await?
+    {
+        Some(..) => Response::Given {
+            from: &user.name,
+            to: &target.display_name,
+            amount,
+            currency,
+        },
+        None => Response::ErrorNotEnough {
+            points: wallets.balance(room, user.id).await?,
+            currency,
+        },
+    };
+    responder.reply(&context, &resp).await
+}
+
+async fn top<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let currency = &context.config.points.name;
+    let (wallets, tracker) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<Wallets>()?.clone(),
+            state.expect_get::<Tracker>()?.clone(),
+        )
+    };
+
+    let top = wallets.top(context.room().id, TOP_USERS).await?;
+    if top.is_empty() {
+        return responder
+            .say(&context, &Response::NoPoints { currency })
+            .await;
+    }
+
+    let users = top
+        .iter()
+        .enumerate()
+        .map(|(n, balance)| {
+            let name = tracker
+                .user_name(balance.user_id as u64)
+                .unwrap_or_else(|| balance.user_id.to_string());
+            format!("{}. {} ({})", n + 1, name, balance.balance)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    responder
+        .say(&context, &Response::Top { users, currency })
+        .await
+}
+
+async fn add<R>(context: Context<Command>, responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    moderate(context, responder, true).await
+}
+
+async fn remove<R>(context: Context<Command>, responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    moderate(context, responder, false).await
+}
+
+async fn moderate<R>(context: Context<Command>, mut responder: R, add: bool) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    if Role::of(&context.args.message) < Role::Moderator {
+        return dont_care();
+    }
+
+    let currency = &context.config.points.name;
+    let (login, amount) = match parse_target(&context) {
+        Some(target) => target,
+        None => {
+            let usage = match add {
+                true => "!addpoints <user> <amount>",
+                false => "!removepoints <user> <amount>",
+            };
+            return responder
+                .reply(&context, &Response::ErrorUsage { usage })
+                .await;
+        }
+    };
+
+    let (wallets, tracker) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<Wallets>()?.clone(),
+            state.expect_get::<Tracker>()?.clone(),
+        )
+    };
+
+    let target = match tracker.get_by_login(login) {
+        Some(target) => target,
+        None => {
+            let resp = Response::ErrorUnknownUser { name: login };
+            return responder.reply(&context, &resp).await;
+        }
+    };
+
+    let (room, actor) = (context.room().id, Some(context.user().id));
+    let name = &target.display_name;
+    let resp = if add {
+        let points = wallets
+            .award(room, target.id as u64, amount, "moderator", actor)
+            .await?;
+        Response::Added {
+            name,
+            amount,
+            points,
+            currency,
+        }
+    } else {
+        let points = wallets
+            .take(room, target.id as u64, amount, "moderator", actor)
+            .await?;
+        Response::Removed {
+            name,
+            amount,
+            points,
+            currency,
+        }
+    };
+    responder.reply(&context, &resp).await
+}
+
+async fn chatting<R>(context: Context<Passive>, _responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let points = &context.config.points;
+    let (user, room) = (context.user().id, context.room().id);
+    let (wallets, earnings) = {
+        let state = context.state().await;
+        if state.expect_get::<OptOuts>()?.contains(user) {
+            return dont_care();
+        }
+        (
+            state.expect_get::<Wallets>()?.clone(),
+            state.expect_get::<Earnings>()?.clone(),
+        )
+    };
+
+    let subscriber = context
+        .args
+        .message
+        .badges()
+        .iter()
+        .any(|badge| match badge.kind {
+            BadgeKind::Subscriber => true,
+            _ => false,
+        });
+    earnings.saw(room, user, subscriber);
+
+    let cooldown = Duration::from_secs(points.message_cooldown);
+    if !earnings.is_live(room) || !earnings.can_earn_from_chat(room, user, cooldown) {
+        return dont_care();
+    }
+
+    let amount = points.earned(points.per_message, subscriber);
+    if amount > 0 {
+        wallets
+            .award(room, user, amount as u64, "chatting", None)
+            .await?;
+    }
+    Ok(())
+}
+
+/// `<user> <amount>`
+fn parse_target(context: &Context<Command>) -> Option<(&str, u64)> {
+    let mut args = context.args.tail.iter();
+    let login = args.next()?.trim_start_matches('@');
+    let amount = parse_amount(args.next()?)?;
+    Some((login, amount))
+}
+
+/// An amount of points, from 1 up to what a balance can hold
+fn parse_amount(input: &str) -> Option<u64> {
+    input
+        .parse::<i64>()
+        .ok()
+        .filter(|&n| n > 0)
+        .map(|n| n as u64)
+}
+
+/// What the bot knows about who can earn points right now
+#[derive(Debug, Default, Clone)]
+struct Earnings {
+    inner: Arc<Mutex<EarningsInner>>,
+}
+
+#[derive(Debug, Default)]
+struct EarningsInner {
+    live_until: HashMap<u64, Instant>,
+    last_earned: HashMap<(u64, u64), Instant>,
+    subscribers: HashSet<(u64, u64)>,
+}
+
+impl Earnings {
+    /// The room is live, until it hasn't been seen live for `ttl`
+    fn went_live(&self, room: u64, ttl: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.live_until.insert(room, Instant::now() + ttl);
+    }
+
+    fn is_live(&self, room: u64) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .live_until
+            .get(&room)
+            .map_or(false, |&until| Instant::now() < until)
+    }
+
+    /// Remember whether the user is subscribed to the room
+    fn saw(&self, room: u64, user: u64, subscriber: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        if subscriber {
+            inner.subscribers.insert((room, user));
+        } else {
+            inner.subscribers.remove(&(room, user));
+        }
+    }
+
+    fn is_subscriber(&self, room: u64, user: u64) -> bool {
+        self.inner
+            .lock()
+            .unwrap()
+            .subscribers
+            .contains(&(room, user))
+    }
+
+    /// Whether chatting earns points, starting the cooldown if it does
+    fn can_earn_from_chat(&self, room: u64, user: u64, cooldown: Duration) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+        match inner.last_earned.get(&(room, user)) {
+            Some(&last) if now.duration_since(last) < cooldown => false,
+            _ => {
+                inner.last_earned.insert((room, user), now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn earnings() {
+        let earnings = Earnings::default();
+        assert!(!earnings.is_live(1));
+        earnings.went_live(1, Duration::from_secs(60));
+        assert!(earnings.is_live(1));
+        earnings.went_live(2, Duration::from_secs(0));
+        assert!(!earnings.is_live(2));
+
+        earnings.saw(1, 42, true);
+        assert!(earnings.is_subscriber(1, 42));
+        assert!(!earnings.is_subscriber(2, 42));
+        earnings.saw(1, 42, false);
+        assert!(!earnings.is_subscriber(1, 42));
+
+        let cooldown = Duration::from_secs(60);
+        assert!(earnings.can_earn_from_chat(1, 42, cooldown));
+        assert!(!earnings.can_earn_from_chat(1, 42, cooldown));
+        assert!(earnings.can_earn_from_chat(1, 43, cooldown));
+        assert!(earnings.can_earn_from_chat(1, 42, Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn default_templates() {
+        let templates: toml::Value =
+            toml::from_str(include_str!("../../default_templates.toml")).unwrap();
+
+        let currency = "points";
+        let responses = vec![
+            Response::Balance {
+                name: "museun",
+                points: 10,
+                currency,
+            },
+            Response::Given {
+                from: "museun",
+                to: "shaken_bot",
+                amount: 5,
+                currency,
+            },
+            Response::Added {
+                name: "museun",
+                amount: 5,
+                points: 15,
+                currency,
+            },
+            Response::Removed {
+                name: "museun",
+                amount: 5,
+                points: 10,
+                currency,
+            },
+            Response::Top {
+                users: "1. museun (10)".into(),
+                currency,
+            },
+            Response::NoPoints { currency },
+            Response::ErrorNotEnough {
+                points: 10,
+                currency,
+            },
+            Response::ErrorUnknownUser { name: "museun" },
+            Response::ErrorOptedOut { name: "museun" },
+            Response::ErrorUsage { usage: "!give" },
+        ];
+
+        for resp in &responses {
+            let variant = resp.variant(Default::default());
+            let data = templates["points"][variant].as_str().unwrap();
+            let out = WriterResponder::apply_template(resp, data).unwrap();
+            assert!(!out.contains("${"), "{}: {}", variant, out);
+        }
+    }
+
+    #[test]
+    fn amounts() {
+        assert_eq!(parse_amount("100"), Some(100));
+        assert_eq!(parse_amount("9223372036854775807"), Some(i64::MAX as u64));
+        for input in &[
+            "0",
+            "-100",
+            "9223372036854775808",
+            "18446744073709551516",
+            "lots",
+        ] {
+            assert_eq!(parse_amount(input), None, "{}", input);
+        }
+    }
+
+    #[test]
+    fn subscriber_multiplier() {
+        let points = config::Points {
+            subscriber_multiplier: 1.5,
+            ..Default::default()
+        };
+        assert_eq!(points.earned(5, false), 5);
+        assert_eq!(points.earned(5, true), 8);
+        assert_eq!(points.earned(0, true), 0);
+    }
+}
@@ -0,0 +1,56 @@
+use {super::*, crate::*};
+
+#[derive(Debug, Template)]
+#[namespace("privacy")]
+enum Response<'a> {
+    OptedOut { name: &'a str },
+    OptedIn { name: &'a str },
+    AlreadyOptedOut { name: &'a str },
+    NotOptedOut { name: &'a str },
+}
+
+pub async fn initialize<R>(init: &mut ModuleInit<'_, R>)
+where
+    R: Responder + Send + 'static,
+{
+    init.command_map.add("optout", opt_out);
+    init.command_map.add("optin", opt_in);
+}
+
+async fn opt_out<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let user = context.user();
+    let (opt_outs, history) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<OptOuts>()?.clone(),
+            state.expect_get::<ChatHistory>()?.clone(),
+        )
+    };
+
+    let resp = if opt_outs.opt_out(user.id).await? {
+        // the recent chat isn't stored, but it shouldn't be used either
+        history.forget_user(user.id);
+        Response::OptedOut { name: &user.name }
+    } else {
+        Response::AlreadyOptedOut { name: &user.name }
+    };
+    responder.reply(&context, &resp).await
+}
+
+async fn opt_in<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let user = context.user();
+    let opt_outs = context.state().await.expect_get::<OptOuts>()?.clone();
+
+    let resp = if opt_outs.opt_in(user.id).await? {
+        Response::OptedIn { name: &user.name }
+    } else {
+        Response::NotOptedOut { name: &user.name }
+    };
+    responder.reply(&context, &resp).await
+}
@@ -0,0 +1,484 @@
+use {super::*, crate::*};
+
+use regex::Regex;
+
+#[derive(Debug, Template)]
+#[namespace("quotes")]
+enum Response<'a> {
+    Quote {
+        n: i64,
+        body: &'a str,
+        author: &'a str,
+        date: String,
+    },
+    QuotePlaying {
+        n: i64,
+        body: &'a str,
+        author: &'a str,
+        game: &'a str,
+        date: String,
+    },
+    Added {
+        n: i64,
+    },
+    Edited {
+        n: i64,
+    },
+    Deleted {
+        n: i64,
+    },
+    Found {
+        count: usize,
+        quotes: String,
+    },
+    ErrorNotFound {
+        n: i64,
+    },
+    ErrorNoQuotes,
+    ErrorNoMatches,
+    ErrorNoMessage {
+        name: &'a str,
+    },
+    ErrorUsage {
+        usage: &'a str,
+    },
+}
+
+const USAGE: &str =
+    "!quote [n] | add <text or @user> | search <words> | edit <n> <text> | delete <n>";
+
+/// How many quote numbers a search lists
+const SEARCH_LIMIT: usize = 10;
+
+pub async fn initialize<R>(init: &mut ModuleInit<'_, R>)
+where
+    R: Responder + Send + 'static,
+{
+    init.command_map.add("quote", quote);
+}
+
+async fn quote<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let pool = context
+        .state()
+        .await
+        .expect_get::<sqlx::SqlitePool>()?
+        .clone();
+    let room = context.room().id;
+
+    let tail = &context.args.tail;
+    let rest = || tail.iter().skip(1).cloned().collect::<Vec<_>>().join(" ");
+    let is_moderator = Role::of(&context.args.message) >= Role::Moderator;
+
+    let usage = Response::ErrorUsage { usage: USAGE };
+    match tail.first().map(String::as_str) {
+        None => match Quotes::random(pool, room).await? {
+            Some(quote) => say_quote(&context, &mut responder, &quote).await,
+            None => responder.reply(&context, &Response::ErrorNoQuotes).await,
+        },
+
+        Some("add") => {
+            let text = rest();
+            if text.is_empty() {
+                return responder.reply(&context, &usage).await;
+            }
+            add(&context, &mut responder, pool, text).await
+        }
+
+        Some("search") => {
+            let text = rest();
+            let words = text.split_whitespace().collect::<Vec<_>>();
+            if words.is_empty() {
+                return responder.reply(&context, &usage).await;
+            }
+
+            let found = Quotes::search(pool, room, &words).await?;
+            match found.as_slice() {
+                [] => responder.reply(&context, &Response::ErrorNoMatches).await,
+                [quote] => say_quote(&context, &mut responder, quote).await,
+                found => {
+                    let quotes = found
+                        .iter()
+                        .take(SEARCH_LIMIT)
+                        .map(|quote| format!("#{}", quote.number))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let resp = Response::Found {
+                        count: found.len(),
+                        quotes,
+                    };
+                    responder.reply(&context, &resp).await
+                }
+            }
+        }
+
+        Some("edit") if is_moderator => {
+            let (n, body) = match tail.get(1).and_then(|n| parse_number(n)) {
+                Some(n) => (n, tail[2..].join(" ")),
+                None => return responder.reply(&context, &usage).await,
+            };
+            if body.is_empty() {
+                return responder.reply(&context, &usage).await;
+            }
+
+            let resp = match Quotes::edit(pool, room, n, &body).await? {
+                true => Response::Edited { n },
+                false => Response::ErrorNotFound { n },
+            };
+            responder.reply(&context, &resp).await
+        }
+
+        Some("delete") | Some("remove") if is_moderator => {
+            let n = match tail.get(1).and_then(|n| parse_number(n)) {
+                Some(n) => n,
+                None => return responder.reply(&context, &usage).await,
+            };
+
+            let resp = match Quotes::remove(pool, room, n).await? {
+                true => Response::Deleted { n },
+                false => Response::ErrorNotFound { n },
+            };
+            responder.reply(&context, &resp).await
+        }
+
+        Some("edit") | Some("delete") | Some("remove") => dont_care(),
+
+        Some(n) => {
+            let n = match parse_number(n) {
+                Some(n) => n,
+                None => return responder.reply(&context, &usage).await,
+            };
+            match Quotes::get(pool, room, n).await? {
+                Some(quote) => say_quote(&context, &mut responder, &quote).await,
+                None => {
+                    responder
+                        .reply(&context, &Response::ErrorNotFound { n })
+                        .await
+                }
+            }
+        }
+    }
+}
+
+/// `!quote add some text` quotes the broadcaster, `!quote add @user` quotes what they last said
+async fn add<R>(
+    context: &Context<Command>,
+    responder: &mut R,
+    pool: sqlx::SqlitePool,
+    text: String,
+) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let room = context.room();
+
+    let mention = text.starts_with('@') && !text.contains(char::is_whitespace);
+    let (body, author, author_id) = if mention {
+        let name = &text[1..];
+        let history = context.state().await.expect_get::<ChatHistory>()?.clone();
+
+        // commands aren't worth quoting
+        let query = ChatQuery::default()
+            .user(name)
+            .pattern(Regex::new("^[^!]").unwrap())
+            .limit(1);
+        match history.query(room.id, &query).pop() {
+            Some(msg) => (msg.data, msg.name, Some(msg.user_id as i64)),
+            None => {
+                let resp = Response::ErrorNoMessage { name };
+                return responder.reply(context, &resp).await;
+            }
+        }
+    } else {
+        (text, room.remove_hashes().to_string(), Some(room.id as i64))
+    };
+
+    let client = context.state().await.expect_get::<TwitchClient>()?.clone();
+    let (game, title) = match stream_info(&client, room.id).await {
+        Ok(info) => info,
+        Err(err) => {
+            log::warn!("cannot get the stream for {}: {}", room, err);
+            (None, None)
+        }
+    };
+
+    let quote = Quote {
+        id: 0,
+        room: room.id as _,
+        number: 0,
+        body,
+        author,
+        author_id,
+        added_by: context.user().id as _,
+        game,
+        title,
+        created_at: time::OffsetDateTime::now().timestamp(),
+    };
+
+    let n = Quotes::add(pool, &quote).await?;
+    responder.reply(context, &Response::Added { n }).await
+}
+
+async fn say_quote<R>(context: &Context<Command>, responder: &mut R, quote: &Quote) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let date = time::OffsetDateTime::from_unix_timestamp(quote.created_at).format("%F");
+    let resp = match &quote.game {
+        Some(game) => Response::QuotePlaying {
+            n: quote.number,
+            body: &quote.body,
+            author: &quote.author,
+            game,
+            date,
+        },
+        None => Response::Quote {
+            n: quote.number,
+            body: &quote.body,
+            author: &quote.author,
+            date,
+        },
+    };
+    responder.say(context, &resp).await
+}
+
+/// The game and title of the stream, if it's live
+async fn stream_info(
+    client: &TwitchClient,
+    room: u64,
+) -> anyhow::Result<(Option<String>, Option<String>)> {
+    let stream = match client
+        .get_streams_from_id(&[room])
+        .await?
+        .into_iter()
+        .find(|stream| stream.user_id == room)
+    {
+        Some(stream) => stream,
+        None => return Ok((None, None)),
+    };
+
+    let game = match stream.game_id {
+        0 => None,
+        id => client
+            .get_games(&[id])
+            .await?
+            .into_iter()
+            .next()
+            .map(|game| game.name),
+    };
+    Ok((game, Some(stream.title)))
+}
+
+/// Quotes are numbered `#1`, `#2`, .. but `1` is fine too
+fn parse_number(input: &str) -> Option<i64> {
+    input.trim_start_matches('#').parse().ok()
+}
+
+/// A quote in a room
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+struct Quote {
+    /// Assigned by the database, `0` until the quote has been added
+    id: i64,
+    room: i64,
+    /// The number of the quote in its room, `0` until the quote has been added
+    number: i64,
+    body: String,
+    author: String,
+    author_id: Option<i64>,
+    added_by: i64,
+    game: Option<String>,
+    title: Option<String>,
+    created_at: i64,
+}
+
+const QUOTE_COLUMNS: &str =
+    "id, room, number, body, author, author_id, added_by, game, title, created_at";
+
+struct Quotes;
+
+impl Quotes {
+    /// Add the quote, returning its number
+    async fn add(pool: sqlx::SqlitePool, quote: &Quote) -> anyhow::Result<i64> {
+        use sqlx::prelude::*;
+
+        let mut tx = pool.begin().await?;
+        let number = sqlx::query("SELECT COALESCE(MAX(number), 0) + 1 FROM quotes WHERE room = ?")
+            .bind(quote.room)
+            .fetch_one(&mut tx)
+            .await?
+            .get::<i64, _>(0);
+
+        sqlx::query(
+            "INSERT INTO quotes (room, number, body, author, author_id, added_by, game, title, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(quote.room)
+        .bind(number)
+        .bind(&quote.body)
+        .bind(&quote.author)
+        .bind(quote.author_id)
+        .bind(quote.added_by)
+        .bind(quote.game.as_deref())
+        .bind(quote.title.as_deref())
+        .bind(quote.created_at)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(number)
+    }
+
+    async fn get(pool: sqlx::SqlitePool, room: u64, number: i64) -> anyhow::Result<Option<Quote>> {
+        sqlx::query_as::<_, Quote>(&format!(
+            "SELECT {} FROM quotes WHERE room = ? AND number = ?",
+            QUOTE_COLUMNS
+        ))
+        .bind(room as i64)
+        .bind(number)
+        .fetch_optional(&mut &pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    async fn random(pool: sqlx::SqlitePool, room: u64) -> anyhow::Result<Option<Quote>> {
+        sqlx::query_as::<_, Quote>(&format!(
+            "SELECT {} FROM quotes WHERE room = ? ORDER BY RANDOM() LIMIT 1",
+            QUOTE_COLUMNS
+        ))
+        .bind(room as i64)
+        .fetch_optional(&mut &pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// The quotes containing all of the words (ignoring case), oldest first
+    async fn search(
+        pool: sqlx::SqlitePool,
+        room: u64,
+        words: &[&str],
+    ) -> anyhow::Result<Vec<Quote>> {
+        let mut sql = format!("SELECT {} FROM quotes WHERE room = ?", QUOTE_COLUMNS);
+        for _ in words {
+            sql.push_str(" AND INSTR(LOWER(body), LOWER(?)) > 0");
+        }
+        sql.push_str(" ORDER BY number");
+
+        let mut query = sqlx::query_as::<_, Quote>(&sql).bind(room as i64);
+        for word in words {
+            query = query.bind(*word);
+        }
+        query.fetch_all(&mut &pool).await.map_err(Into::into)
+    }
+
+    async fn edit(
+        pool: sqlx::SqlitePool,
+        room: u64,
+        number: i64,
+        body: &str,
+    ) -> anyhow::Result<bool> {
+        let n = sqlx::query("UPDATE quotes SET body = ? WHERE room = ? AND number = ?")
+            .bind(body)
+            .bind(room as i64)
+            .bind(number)
+            .execute(&mut &pool)
+            .await?;
+        Ok(n == 1)
+    }
+
+    async fn remove(pool: sqlx::SqlitePool, room: u64, number: i64) -> anyhow::Result<bool> {
+        let n = sqlx::query("DELETE FROM quotes WHERE room = ? AND number = ?")
+            .bind(room as i64)
+            .bind(number)
+            .execute(&mut &pool)
+            .await?;
+        Ok(n == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_quote(room: i64, body: &str) -> Quote {
+        Quote {
+            id: 0,
+            room,
+            number: 0,
+            body: body.into(),
+            author: "museun".into(),
+            author_id: Some(1234),
+            added_by: 4321,
+            game: Some("Science & Technology".into()),
+            title: None,
+            created_at: 1_585_744_496,
+        }
+    }
+
+    #[tokio::test]
+    async fn quotes() {
+        let pool = crate::database::in_memory().await.unwrap();
+
+        for (room, body) in &[
+            (1, "it works on my machine"),
+            (1, "the borrow checker is my friend"),
+            (2, "hello world"),
+            (1, "it compiles, ship it"),
+        ] {
+            Quotes::add(pool.clone(), &make_quote(*room, body))
+                .await
+                .unwrap();
+        }
+
+        // numbered per room
+        let quote = Quotes::get(pool.clone(), 1, 3).await.unwrap().unwrap();
+        assert_eq!(quote.body, "it compiles, ship it");
+        assert_eq!(quote.game.as_deref(), Some("Science & Technology"));
+        let quote = Quotes::get(pool.clone(), 2, 1).await.unwrap().unwrap();
+        assert_eq!(quote.body, "hello world");
+        assert!(Quotes::get(pool.clone(), 2, 2).await.unwrap().is_none());
+
+        let found = |words: &'static [&'static str]| {
+            let pool = pool.clone();
+            async move {
+                Quotes::search(pool, 1, words)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|quote| quote.number)
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(found(&["IT"]).await, vec![1, 3]);
+        assert_eq!(found(&["it", "ship"]).await, vec![3]);
+        assert!(found(&["hello"]).await.is_empty());
+
+        assert!(Quotes::edit(pool.clone(), 1, 1, "it works on your machine")
+            .await
+            .unwrap());
+        assert!(!Quotes::edit(pool.clone(), 1, 42, "nope").await.unwrap());
+        assert_eq!(found(&["your"]).await, vec![1]);
+
+        assert!(Quotes::remove(pool.clone(), 1, 3).await.unwrap());
+        assert!(!Quotes::remove(pool.clone(), 1, 3).await.unwrap());
+        assert_eq!(found(&["it"]).await, vec![1]);
+
+        assert_eq!(
+            Quotes::random(pool.clone(), 2)
+                .await
+                .unwrap()
+                .unwrap()
+                .number,
+            1
+        );
+        assert!(Quotes::random(pool.clone(), 3).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn number() {
+        assert_eq!(parse_number("#12"), Some(12));
+        assert_eq!(parse_number("12"), Some(12));
+        assert_eq!(parse_number("twelve"), None);
+    }
+}
@@ -36,6 +36,7 @@ where
     init.state.insert(client);
 
     init.command_map.add("speak", command);
+    init.passive_list.add_pattern(Pattern::Mention, mention);
     init.passive_list.add(passive);
 }
 
@@ -61,10 +62,12 @@ where
     responder.say(&context, &resp).await
 }
 
-async fn passive<R>(mut context: Context<Passive>, mut responder: R) -> Result
+async fn mention<R>(mut context: Context<Passive>, mut responder: R) -> Result
 where
     R: Responder + Send + 'static,
 {
+    ignore_opted_out(&context).await?;
+
     context.args.room().check_list(
         context
             .get_current_config()
@@ -74,26 +77,59 @@ where
             .iter(),
     )?;
 
-    let user = context.get_our_user().await;
-    // TODO make this a regex or do some case folding
-    let force = context.data().starts_with(&format!("@{}", user.name));
-
     let data = {
         let cache = &mut *context.state_mut().await;
         let client = cache.expect_get_mut::<Shakespeare>()?;
+        client.trigger().await.dont_care()?
+    };
 
-        if force {
-            client.trigger().await.dont_care()?
-        } else {
-            let mut rng = rand::rngs::SmallRng::from_entropy();
-            client.passive(&mut rng).await.dont_care()?
-        }
+    let resp = Response::Shakespeare { data: &data };
+    responder.say(&context, &resp).await
+}
+
+async fn passive<R>(mut context: Context<Passive>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    // mentions are always handled by the `mention` passive
+    if context.args.mentions_us() {
+        return dont_care();
+    }
+    ignore_opted_out(&context).await?;
+
+    context.args.room().check_list(
+        context
+            .get_current_config()
+            .await?
+            .shakespeare
+            .whitelist
+            .iter(),
+    )?;
+
+    let data = {
+        let cache = &mut *context.state_mut().await;
+        let client = cache.expect_get_mut::<Shakespeare>()?;
+        let mut rng = rand::rngs::SmallRng::from_entropy();
+        client.passive(&mut rng).await.dont_care()?
     };
 
     let resp = Response::Shakespeare { data: &data };
     responder.say(&context, &resp).await
 }
 
+/// The chat of users that opted out isn't used to generate anything
+async fn ignore_opted_out(context: &Context<Passive>) -> anyhow::Result<()> {
+    if context
+        .state()
+        .await
+        .expect_get::<OptOuts>()?
+        .contains(context.user().id)
+    {
+        return dont_care();
+    }
+    Ok(())
+}
+
 pub struct Shakespeare {
     client: client::Client,
 
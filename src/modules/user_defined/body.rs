@@ -0,0 +1,362 @@
+use rand::prelude::*;
+use std::collections::HashMap;
+
+/// A parsed user-defined command body
+///
+/// Variables are written as `${name}` or `${name args}`, and `$$` is a literal `$`:
+///
+/// | variable              | is replaced with                                  |
+/// | ---                   | ---                                               |
+/// | `${user}`             | the name of the user who ran the command          |
+/// | `${target}`           | the first argument (without the `@`), or the user |
+/// | `${1}`, `${2}`, ..    | that argument, or nothing                         |
+/// | `${args}`             | all of the arguments                              |
+/// | `${uses}`             | how many times the command has been used          |
+/// | `${random 10}`        | a random number from 1 to 10                      |
+/// | `${random 5 10}`      | a random number from 5 to 10                      |
+/// | `${choice a\|b\|c}`   | one of the choices, at random                     |
+/// | `${uptime}`           | how long the stream has been live                 |
+/// | `${song}`             | the current song                                  |
+/// | `${counter deaths}`   | the value of that counter                         |
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Body {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Var(Var),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Var {
+    User,
+    Target,
+    Arg(usize),
+    Args,
+    Uses,
+    Random(i64, i64),
+    Choice(Vec<String>),
+    Uptime,
+    Song,
+    Counter(String),
+}
+
+/// Why a body couldn't be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Error {
+    Unterminated { pos: usize },
+    Empty { pos: usize },
+    Unknown { name: String },
+    Invalid { name: String, reason: &'static str },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unterminated { pos } => write!(f, "the '${{' at {} is never closed", pos),
+            Self::Empty { pos } => write!(f, "the variable at {} is empty", pos),
+            Self::Unknown { name } => write!(f, "unknown variable '{}' (use $$ for a '$')", name),
+            Self::Invalid { name, reason } => write!(f, "'{}' {}", name, reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Body {
+    pub(super) fn parse(input: &str) -> Result<Self, Error> {
+        let mut parts = vec![];
+        let mut text = String::new();
+
+        let mut rest = input;
+        while let Some(index) = rest.find('$') {
+            text.push_str(&rest[..index]);
+            let tail = &rest[index + 1..];
+
+            if tail.starts_with('$') {
+                text.push('$');
+                rest = &tail[1..];
+                continue;
+            }
+
+            if !tail.starts_with('{') {
+                // a lone '$' is just text
+                text.push('$');
+                rest = tail;
+                continue;
+            }
+
+            let pos = input.len() - rest.len() + index;
+            let end = tail.find('}').ok_or(Error::Unterminated { pos })?;
+            let var = Var::parse(&tail[1..end], pos)?;
+
+            if !text.is_empty() {
+                parts.push(Part::Text(std::mem::take(&mut text)));
+            }
+            parts.push(Part::Var(var));
+            rest = &tail[end + 1..];
+        }
+
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Whether the stream's uptime needs to be looked up
+    pub(super) fn needs_uptime(&self) -> bool {
+        self.vars().any(|var| *var == Var::Uptime)
+    }
+
+    /// Whether the current song needs to be looked up
+    pub(super) fn needs_song(&self) -> bool {
+        self.vars().any(|var| *var == Var::Song)
+    }
+
+    /// The counters that need to be looked up
+    pub(super) fn counters(&self) -> impl Iterator<Item = &str> {
+        self.vars().filter_map(|var| match var {
+            Var::Counter(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    pub(super) fn render(&self, env: &Env, rng: &mut impl Rng) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            let var = match part {
+                Part::Text(text) => {
+                    out.push_str(text);
+                    continue;
+                }
+                Part::Var(var) => var,
+            };
+
+            match var {
+                Var::User => out.push_str(&env.user),
+                Var::Target => match env.args.first() {
+                    Some(target) => out.push_str(target.trim_start_matches('@')),
+                    None => out.push_str(&env.user),
+                },
+                Var::Arg(n) => {
+                    if let Some(arg) = env.args.get(n - 1) {
+                        out.push_str(arg)
+                    }
+                }
+                Var::Args => out.push_str(&env.args.join(" ")),
+                Var::Uses => out.push_str(&env.uses.to_string()),
+                Var::Random(low, high) => {
+                    let n = rng.sample(rand::distributions::Uniform::new_inclusive(low, high));
+                    out.push_str(&n.to_string())
+                }
+                Var::Choice(choices) => {
+                    if let Some(choice) = choices.choose(rng) {
+                        out.push_str(choice)
+                    }
+                }
+                Var::Uptime => out.push_str(env.uptime.as_deref().unwrap_or("offline")),
+                Var::Song => out.push_str(env.song.as_deref().unwrap_or("nothing")),
+                Var::Counter(name) => out.push_str(
+                    &env.counters
+                        .get(name)
+                        .copied()
+                        .unwrap_or_default()
+                        .to_string(),
+                ),
+            }
+        }
+        out
+    }
+
+    fn vars(&self) -> impl Iterator<Item = &Var> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Var(var) => Some(var),
+            _ => None,
+        })
+    }
+}
+
+impl Var {
+    fn parse(input: &str, pos: usize) -> Result<Self, Error> {
+        let input = input.trim();
+        let (name, args) = match input.find(char::is_whitespace) {
+            Some(index) => (&input[..index], input[index..].trim()),
+            None => (input, ""),
+        };
+
+        let invalid = |reason| Error::Invalid {
+            name: name.to_string(),
+            reason,
+        };
+
+        let no_args = |var| {
+            if args.is_empty() {
+                Ok(var)
+            } else {
+                Err(invalid("doesn't take any arguments"))
+            }
+        };
+
+        match name {
+            "" => Err(Error::Empty { pos }),
+            "user" => no_args(Self::User),
+            "target" => no_args(Self::Target),
+            "args" => no_args(Self::Args),
+            "uses" => no_args(Self::Uses),
+            "uptime" => no_args(Self::Uptime),
+            "song" => no_args(Self::Song),
+
+            "random" => {
+                let nums = args
+                    .split_whitespace()
+                    .map(str::parse::<i64>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| invalid("takes whole numbers"))?;
+                let (low, high) = match nums[..] {
+                    [high] => (1, high),
+                    [low, high] => (low, high),
+                    _ => return Err(invalid("takes a maximum, or a minimum and a maximum")),
+                };
+                if low > high {
+                    return Err(invalid("needs the minimum to be less than the maximum"));
+                }
+                Ok(Self::Random(low, high))
+            }
+
+            "choice" => {
+                let choices = args
+                    .split('|')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>();
+                if choices.is_empty() {
+                    return Err(invalid("needs choices separated by '|'"));
+                }
+                Ok(Self::Choice(choices))
+            }
+
+            "counter" => {
+                if !crate::Counters::is_valid_name(args) {
+                    return Err(invalid("needs the name of a counter"));
+                }
+                Ok(Self::Counter(args.to_lowercase()))
+            }
+
+            n => match n.parse::<usize>() {
+                Ok(0) => Err(invalid("arguments start at 1")),
+                Ok(n) => no_args(Self::Arg(n)),
+                Err(..) => Err(Error::Unknown {
+                    name: n.to_string(),
+                }),
+            },
+        }
+    }
+}
+
+/// The values that a body can use
+#[derive(Debug, Default)]
+pub(super) struct Env {
+    pub user: String,
+    pub args: Vec<String>,
+    pub uses: i32,
+    pub uptime: Option<String>,
+    pub song: Option<String>,
+    pub counters: HashMap<String, i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> Env {
+        Env {
+            user: "museun".into(),
+            args: vec!["@shaken_bot".into(), "hello".into()],
+            uses: 42,
+            uptime: Some("1 hour".into()),
+            song: None,
+            counters: vec![("deaths".to_string(), 3)].into_iter().collect(),
+        }
+    }
+
+    fn render(input: &str) -> String {
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(0);
+        Body::parse(input).unwrap().render(&env(), &mut rng)
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(render("hello world"), "hello world");
+        assert_eq!(render("hi ${user}"), "hi museun");
+        assert_eq!(render("${user} hugs ${target}"), "museun hugs shaken_bot");
+        assert_eq!(render("${2} ${3}!"), "hello !");
+        assert_eq!(render("you said: ${args}"), "you said: @shaken_bot hello");
+        assert_eq!(render("used ${uses} times"), "used 42 times");
+        assert_eq!(render("live for ${uptime}"), "live for 1 hour");
+        assert_eq!(render("playing ${song}"), "playing nothing");
+        assert_eq!(render("died ${counter deaths} times"), "died 3 times");
+        assert_eq!(render("${counter unknown}"), "0");
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(render("costs $5"), "costs $5");
+        assert_eq!(render("costs $$5"), "costs $5");
+        assert_eq!(render("literally $${user}"), "literally ${user}");
+        assert_eq!(render("$$$${user}"), "$${user}");
+        assert_eq!(render("ends with $"), "ends with $");
+    }
+
+    #[test]
+    fn random() {
+        for _ in 0..100 {
+            let n: i64 = render("${random 5 10}").parse().unwrap();
+            assert!(n >= 5 && n <= 10);
+            let n: i64 = render("${random 3}").parse().unwrap();
+            assert!(n >= 1 && n <= 3);
+            assert!(["a", "b c"].contains(&&*render("${choice a | b c}")));
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Body::parse("hello ${user").unwrap_err(),
+            Error::Unterminated { pos: 6 }
+        );
+        assert_eq!(Body::parse("${}").unwrap_err(), Error::Empty { pos: 0 });
+        assert!(matches!(
+            Body::parse("${foo}").unwrap_err(),
+            Error::Unknown { .. }
+        ));
+        for input in &[
+            "${user foo}",
+            "${random}",
+            "${random a b}",
+            "${random 10 5}",
+            "${choice}",
+            "${counter}",
+            "${counter boss-deaths}",
+            "${0}",
+        ] {
+            assert!(
+                matches!(Body::parse(input).unwrap_err(), Error::Invalid { .. }),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn lookups() {
+        let body = Body::parse("${uptime} ${counter a} ${counter b}").unwrap();
+        assert!(body.needs_uptime());
+        assert!(!body.needs_song());
+        assert_eq!(body.counters().collect::<Vec<_>>(), vec!["a", "b"]);
+    }
+}
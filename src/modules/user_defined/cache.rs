@@ -0,0 +1,253 @@
+use super::{Registry, UserDefinedCommand};
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// All of the user-defined commands, so chat doesn't have to wait on the database
+///
+/// This has to be refreshed after the commands are changed in the database.
+/// Uses are counted here and written to the database with `flush`.
+#[derive(Clone)]
+pub(super) struct Cache {
+    inner: Arc<Mutex<Inner>>,
+    /// Held by `refresh` and `flush`, so a refresh can't read the uses that a
+    /// flush has written before the flush has counted them here
+    sync: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self {
+            inner: Default::default(),
+            sync: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Every command with a name, from any room
+    commands: HashMap<String, Vec<UserDefinedCommand>>,
+    /// The rooms each command id is shared with
+    shares: HashMap<i64, HashSet<u64>>,
+    /// Uses that haven't been written to the database yet
+    uses: HashMap<i64, i32>,
+}
+
+impl Cache {
+    pub(super) async fn load(pool: sqlx::SqlitePool) -> anyhow::Result<Self> {
+        let this = Self::default();
+        this.refresh(pool).await?;
+        Ok(this)
+    }
+
+    /// Reload the commands from the database, keeping any unwritten uses
+    pub(super) async fn refresh(&self, pool: sqlx::SqlitePool) -> anyhow::Result<()> {
+        let _sync = self.sync.lock().await;
+
+        let mut commands = HashMap::<_, Vec<_>>::new();
+        for udc in Registry::all_commands(pool.clone()).await? {
+            commands.entry(udc.name.clone()).or_default().push(udc);
+        }
+
+        let mut shares = HashMap::<_, HashSet<_>>::new();
+        for (id, room) in Registry::all_shares(pool).await? {
+            shares.entry(id).or_default().insert(room);
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.commands = commands;
+        inner.shares = shares;
+        Ok(())
+    }
+
+    /// Find the command that `name` refers to in this room
+    ///
+    /// This has the same precedence as `Registry::lookup`
+    pub(super) fn lookup(&self, name: &str, room: u64) -> Option<UserDefinedCommand> {
+        let inner = self.inner.lock().unwrap();
+        let shared = |udc: &UserDefinedCommand| {
+            inner
+                .shares
+                .get(&udc.id)
+                .map(|rooms| rooms.contains(&room))
+                .unwrap_or_default()
+        };
+
+        let mut udc = inner
+            .commands
+            .get(name)?
+            .iter()
+            .filter_map(|udc| {
+                let precedence = if udc.room == room as i64 {
+                    0
+                } else if shared(udc) {
+                    1
+                } else if udc.global {
+                    2
+                } else {
+                    return None;
+                };
+                Some((precedence, udc))
+            })
+            .min_by_key(|&(precedence, _)| precedence)
+            .map(|(_, udc)| udc.clone())?;
+
+        udc.uses += inner.uses.get(&udc.id).copied().unwrap_or_default();
+        Some(udc)
+    }
+
+    /// Count a use of the command, returning its total uses
+    pub(super) fn add_use(&self, udc: &UserDefinedCommand) -> i32 {
+        let mut inner = self.inner.lock().unwrap();
+        let pending = inner.uses.entry(udc.id).or_default();
+        *pending += 1;
+
+        // the lookup already included the other pending uses
+        udc.uses + 1
+    }
+
+    /// Write the pending uses to the database
+    pub(super) async fn flush(&self, pool: sqlx::SqlitePool) -> anyhow::Result<()> {
+        let _sync = self.sync.lock().await;
+
+        let uses = std::mem::take(&mut self.inner.lock().unwrap().uses);
+        if uses.is_empty() {
+            return Ok(());
+        }
+
+        if let Err(err) = Registry::add_uses(pool, uses.iter().map(|(&k, &v)| (k, v))).await {
+            // put them back so they'll be tried again
+            let mut inner = self.inner.lock().unwrap();
+            for (id, n) in uses {
+                *inner.uses.entry(id).or_default() += n;
+            }
+            return Err(err);
+        }
+
+        // the database has these uses now
+        let mut inner = self.inner.lock().unwrap();
+        for udc in inner.commands.values_mut().flatten() {
+            if let Some(n) = uses.get(&udc.id) {
+                udc.uses += n;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_udc(name: &str, room: i64) -> UserDefinedCommand {
+        UserDefinedCommand {
+            id: 0,
+            name: name.into(),
+            body: format!("from {}", room),
+            room,
+            uses: 0,
+            owner: 1234,
+            disabled: false,
+            created_at: time::OffsetDateTime::now(),
+            role: Default::default(),
+            cooldown: 0,
+            global: false,
+            variants: vec![],
+            mode: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn lookup() {
+        let pool = crate::database::in_memory().await.unwrap();
+        Registry::add_many(
+            pool.clone(),
+            [
+                make_udc("!local", 1),
+                make_udc("!shared", 1),
+                make_udc("!local", 2),
+            ]
+            .iter(),
+        )
+        .await
+        .unwrap();
+
+        let cache = Cache::load(pool.clone()).await.unwrap();
+        assert_eq!(cache.lookup("!local", 1).unwrap().room, 1);
+        assert_eq!(cache.lookup("!local", 2).unwrap().room, 2);
+        assert!(cache.lookup("!local", 3).is_none());
+        assert!(cache.lookup("!shared", 2).is_none());
+
+        let shared = Registry::lookup_local(pool.clone(), "!shared", 1)
+            .await
+            .unwrap()
+            .unwrap();
+        Registry::share(pool.clone(), &shared, &[2]).await.unwrap();
+
+        let local = Registry::lookup_local(pool.clone(), "!local", 1)
+            .await
+            .unwrap()
+            .unwrap();
+        Registry::set_global(pool.clone(), &local, true)
+            .await
+            .unwrap();
+
+        // nothing changes until it's refreshed
+        assert!(cache.lookup("!shared", 2).is_none());
+        cache.refresh(pool.clone()).await.unwrap();
+
+        assert_eq!(cache.lookup("!shared", 2).unwrap().room, 1);
+        assert!(cache.lookup("!shared", 3).is_none());
+        assert_eq!(cache.lookup("!local", 2).unwrap().room, 2);
+        assert_eq!(cache.lookup("!local", 3).unwrap().room, 1);
+    }
+
+    #[tokio::test]
+    async fn uses() {
+        let pool = crate::database::in_memory().await.unwrap();
+        Registry::add_many(pool.clone(), [make_udc("!hello", 1)].iter())
+            .await
+            .unwrap();
+
+        let cache = Cache::load(pool.clone()).await.unwrap();
+        for n in 1..=3 {
+            let udc = cache.lookup("!hello", 1).unwrap();
+            assert_eq!(cache.add_use(&udc), n);
+        }
+
+        let stored = || async {
+            Registry::lookup_local(pool.clone(), "!hello", 1)
+                .await
+                .unwrap()
+                .unwrap()
+                .uses
+        };
+
+        assert_eq!(stored().await, 0);
+        cache.flush(pool.clone()).await.unwrap();
+        assert_eq!(stored().await, 3);
+
+        // flushed uses aren't counted twice
+        assert_eq!(cache.lookup("!hello", 1).unwrap().uses, 3);
+        cache.refresh(pool.clone()).await.unwrap();
+        assert_eq!(cache.lookup("!hello", 1).unwrap().uses, 3);
+
+        let udc = cache.lookup("!hello", 1).unwrap();
+        assert_eq!(cache.add_use(&udc), 4);
+        cache.flush(pool.clone()).await.unwrap();
+        assert_eq!(stored().await, 4);
+
+        // a command changing while the uses are being written
+        for n in 5..=10 {
+            let udc = cache.lookup("!hello", 1).unwrap();
+            cache.add_use(&udc);
+            let (flushed, refreshed) =
+                futures::join!(cache.flush(pool.clone()), cache.refresh(pool.clone()));
+            flushed.unwrap();
+            refreshed.unwrap();
+            assert_eq!(cache.lookup("!hello", 1).unwrap().uses, n);
+        }
+        assert_eq!(stored().await, 10);
+    }
+}
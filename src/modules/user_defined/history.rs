@@ -0,0 +1,252 @@
+use super::UserDefinedCommand;
+
+/// What was done to a command
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum Action {
+    Add,
+    Edit,
+    Rename,
+    Delete,
+}
+
+impl Action {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Edit => "edit",
+            Self::Rename => "rename",
+            Self::Delete => "delete",
+        }
+    }
+
+    pub(crate) fn past_tense(self) -> &'static str {
+        match self {
+            Self::Add => "added",
+            Self::Edit => "edited",
+            Self::Rename => "renamed",
+            Self::Delete => "deleted",
+        }
+    }
+}
+
+impl std::str::FromStr for Action {
+    type Err = anyhow::Error;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "add" => Ok(Self::Add),
+            "edit" => Ok(Self::Edit),
+            "rename" => Ok(Self::Rename),
+            "delete" => Ok(Self::Delete),
+            action => anyhow::bail!("unknown action: '{}'", action),
+        }
+    }
+}
+
+/// A change made to a command
+#[derive(Debug, Clone)]
+pub(crate) struct Revision {
+    pub id: i64,
+    pub room: i64,
+    /// The name of the command after the change
+    pub name: String,
+    pub action: Action,
+    /// The id of the user who made the change
+    pub actor: i64,
+    pub at: time::OffsetDateTime,
+    /// The command before the change, `None` when it was added
+    pub(super) previous: Option<UserDefinedCommand>,
+}
+
+impl Revision {
+    /// The body before the change
+    pub(crate) fn previous_body(&self) -> Option<&str> {
+        self.previous.as_ref().map(|udc| udc.body.as_str())
+    }
+
+    /// The name before the change
+    pub(crate) fn previous_name(&self) -> Option<&str> {
+        self.previous.as_ref().map(|udc| udc.name.as_str())
+    }
+}
+
+pub(crate) struct History;
+
+impl History {
+    /// Record that `actor` did `action` to the command now called `name`
+    pub(super) async fn record(
+        pool: sqlx::SqlitePool,
+        action: Action,
+        actor: u64,
+        room: i64,
+        name: &str,
+        previous: Option<&UserDefinedCommand>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(include_str!("sql/add_revision.sql"))
+            .bind(room)
+            .bind(name)
+            .bind(action.as_str())
+            .bind(actor as i64)
+            .bind(time::OffsetDateTime::now().timestamp())
+            .bind(previous.map(|udc| udc.name.clone()))
+            .bind(previous.map(|udc| udc.body.clone()))
+            .bind(previous.map(|udc| udc.uses))
+            .bind(previous.map(|udc| udc.owner))
+            .bind(previous.map(|udc| udc.disabled))
+            .bind(previous.map(|udc| udc.created_at.timestamp()))
+            .bind(previous.map(|udc| udc.role.as_str()))
+            .bind(previous.map(|udc| udc.cooldown as i64))
+            .bind(previous.map(|udc| udc.variants_json()))
+            .bind(previous.map(|udc| udc.mode.as_str()))
+            .execute(&mut &pool)
+            .await?;
+        Ok(())
+    }
+
+    /// The most recent revisions, newest first, optionally filtered to a room and a command
+    pub(crate) async fn recent(
+        pool: sqlx::SqlitePool,
+        room: Option<i64>,
+        name: Option<&str>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<Revision>> {
+        sqlx::query_as::<_, RevisionRow>(include_str!("sql/revisions.sql"))
+            .bind(room)
+            .bind(room)
+            .bind(name)
+            .bind(name)
+            .bind(limit)
+            .fetch_all(&mut &pool)
+            .await?
+            .into_iter()
+            .map(Revision::try_from_row)
+            .collect()
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct RevisionRow {
+    id: i64,
+    room: i64,
+    name: String,
+    action: String,
+    actor: i64,
+    at: i64,
+    previous_name: Option<String>,
+    previous_body: Option<String>,
+    previous_uses: Option<i32>,
+    previous_owner: Option<i64>,
+    previous_disabled: Option<bool>,
+    previous_created_at: Option<i64>,
+    previous_role: Option<String>,
+    previous_cooldown: Option<i64>,
+    previous_variants: Option<String>,
+    previous_mode: Option<String>,
+}
+
+impl Revision {
+    fn try_from_row(row: RevisionRow) -> anyhow::Result<Self> {
+        let previous = match (row.previous_name, row.previous_body) {
+            (Some(name), Some(body)) => Some(UserDefinedCommand {
+                id: 0,
+                name,
+                body,
+                room: row.room,
+                uses: row.previous_uses.unwrap_or_default(),
+                owner: row.previous_owner.unwrap_or(row.actor),
+                disabled: row.previous_disabled.unwrap_or_default(),
+                created_at: time::OffsetDateTime::from_unix_timestamp(
+                    row.previous_created_at.unwrap_or(row.at),
+                ),
+                role: row
+                    .previous_role
+                    .as_deref()
+                    .unwrap_or_default()
+                    .parse()
+                    .unwrap_or_default(),
+                cooldown: row.previous_cooldown.unwrap_or_default() as _,
+                global: false,
+                variants: row
+                    .previous_variants
+                    .as_deref()
+                    .map(serde_json::from_str)
+                    .transpose()?
+                    .unwrap_or_default(),
+                mode: row
+                    .previous_mode
+                    .as_deref()
+                    .unwrap_or_default()
+                    .parse()
+                    .unwrap_or_default(),
+            }),
+            _ => None,
+        };
+
+        Ok(Self {
+            id: row.id,
+            room: row.room,
+            name: row.name,
+            action: row.action.parse()?,
+            actor: row.actor,
+            at: time::OffsetDateTime::from_unix_timestamp(row.at),
+            previous,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_udc(name: &str, body: &str) -> UserDefinedCommand {
+        UserDefinedCommand {
+            id: 0,
+            name: name.into(),
+            body: body.into(),
+            room: 1234,
+            uses: 3,
+            owner: 4321,
+            disabled: false,
+            created_at: time::OffsetDateTime::from_unix_timestamp(1_585_744_496),
+            role: crate::Role::Subscriber,
+            cooldown: 30,
+            global: false,
+            variants: vec!["hello".into()],
+            mode: super::super::Mode::Sequence,
+        }
+    }
+
+    #[tokio::test]
+    async fn record() {
+        let pool = crate::database::in_memory().await.unwrap();
+
+        let old = make_udc("!hello", "world");
+        History::record(pool.clone(), Action::Add, 1, 1234, "!hello", None)
+            .await
+            .unwrap();
+        History::record(pool.clone(), Action::Edit, 2, 1234, "!hello", Some(&old))
+            .await
+            .unwrap();
+        History::record(pool.clone(), Action::Add, 1, 42, "!other", None)
+            .await
+            .unwrap();
+
+        let revisions = History::recent(pool.clone(), Some(1234), Some("!hello"), 10)
+            .await
+            .unwrap();
+        assert_eq!(revisions.len(), 2);
+
+        assert_eq!(revisions[0].action, Action::Edit);
+        assert_eq!(revisions[0].actor, 2);
+        assert_eq!(revisions[0].previous, Some(old));
+
+        assert_eq!(revisions[1].action, Action::Add);
+        assert_eq!(revisions[1].previous, None);
+
+        let all = History::recent(pool.clone(), None, None, 10).await.unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].name, "!other");
+
+        let limited = History::recent(pool, None, None, 1).await.unwrap();
+        assert_eq!(limited.len(), 1);
+    }
+}
@@ -0,0 +1,450 @@
+use super::{body::Body, command_name, Registry, UserDefinedCommand};
+use crate::Role;
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// The chat bot the commands were exported from
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Source {
+    Nightbot,
+    StreamElements,
+    Streamlabs,
+}
+
+impl std::str::FromStr for Source {
+    type Err = anyhow::Error;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "nightbot" => Ok(Self::Nightbot),
+            "streamelements" | "se" => Ok(Self::StreamElements),
+            "streamlabs" | "slcb" => Ok(Self::Streamlabs),
+            source => anyhow::bail!(
+                "unknown source: '{}'. use 'nightbot', 'streamelements' or 'streamlabs'",
+                source
+            ),
+        }
+    }
+}
+
+/// The format of the export file
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            format => anyhow::bail!("unknown format: '{}'. use 'csv' or 'json'", format),
+        }
+    }
+}
+
+/// A command read from an export
+#[derive(Debug, Clone, PartialEq)]
+pub struct Imported {
+    pub name: String,
+    pub body: String,
+    pub uses: i32,
+    pub enabled: bool,
+    /// Variables that we don't have an equivalent for, they are kept as-is
+    pub unsupported: Vec<String>,
+}
+
+/// What happened (or would happen) to the imported commands
+#[derive(Debug, Default)]
+pub struct Report {
+    pub added: Vec<Imported>,
+    /// These collide with a built-in command
+    pub builtin: Vec<Imported>,
+    /// These already exist in the room (or were repeated in the export)
+    pub existing: Vec<Imported>,
+}
+
+/// Parse an export, translating the variables in each body to ours
+pub fn parse(source: Source, format: Format, data: &str) -> anyhow::Result<Vec<Imported>> {
+    let rows = match format {
+        Format::Csv => parse_csv(data)?,
+        Format::Json => parse_json(data)?,
+    };
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let (body, unsupported) = translate(source, &row.body);
+            Imported {
+                name: command_name(row.name.trim()),
+                body,
+                uses: row.uses,
+                enabled: row.enabled,
+                unsupported,
+            }
+        })
+        .collect())
+}
+
+/// Add the commands to the room in a single transaction
+///
+/// Commands that collide with built-in or existing commands are skipped. If
+/// `dry_run` is true, nothing is written.
+pub async fn import(
+    pool: sqlx::SqlitePool,
+    room: u64,
+    owner: u64,
+    commands: Vec<Imported>,
+    dry_run: bool,
+) -> anyhow::Result<Report> {
+    let builtin = Registry::all_builtin(pool.clone()).await?;
+    let mut existing = Registry::all_commands_for(pool.clone(), room)
+        .await?
+        .into_iter()
+        .map(|udc| udc.name)
+        .collect::<HashSet<_>>();
+
+    let mut report = Report::default();
+    for command in commands {
+        if builtin.contains(&command.name) {
+            report.builtin.push(command)
+        } else if !existing.insert(command.name.clone()) {
+            report.existing.push(command)
+        } else {
+            report.added.push(command)
+        }
+    }
+
+    if dry_run {
+        return Ok(report);
+    }
+
+    let created_at = time::OffsetDateTime::now();
+    let commands = report
+        .added
+        .iter()
+        .map(|command| UserDefinedCommand {
+            id: 0,
+            name: command.name.clone(),
+            body: command.body.clone(),
+            room: room as _,
+            uses: command.uses,
+            owner: owner as _,
+            disabled: !command.enabled,
+            created_at,
+            role: Role::default(),
+            cooldown: 0,
+            global: false,
+            variants: vec![],
+            mode: Default::default(),
+        })
+        .collect::<Vec<_>>();
+
+    Registry::add_many(pool, commands.iter()).await?;
+    Ok(report)
+}
+
+#[derive(Debug)]
+struct Row {
+    name: String,
+    body: String,
+    uses: i32,
+    enabled: bool,
+}
+
+const NAME_FIELDS: &[&str] = &["name", "command"];
+const BODY_FIELDS: &[&str] = &["message", "reply", "response"];
+const USES_FIELDS: &[&str] = &["count", "uses"];
+const ENABLED_FIELDS: &[&str] = &["enabled"];
+
+fn parse_json(data: &str) -> anyhow::Result<Vec<Row>> {
+    let value: Value = serde_json::from_str(data)?;
+
+    // nightbot wraps them in `commands`, streamlabs uses `commands.custom`
+    let list = [
+        value.as_array(),
+        value.get("commands").and_then(Value::as_array),
+        value
+            .get("commands")
+            .and_then(|v| v.get("custom"))
+            .and_then(Value::as_array),
+    ]
+    .iter()
+    .find_map(|list| *list)
+    .ok_or_else(|| anyhow::anyhow!("cannot find the list of commands"))?;
+
+    let get = |item: &Value, keys: &[&str]| keys.iter().find_map(|key| item.get(key).cloned());
+
+    list.iter()
+        .map(|item| {
+            let name = get(item, NAME_FIELDS)
+                .and_then(|v| v.as_str().map(ToString::to_string))
+                .ok_or_else(|| anyhow::anyhow!("command is missing a name: {}", item))?;
+            let body = get(item, BODY_FIELDS)
+                .and_then(|v| v.as_str().map(ToString::to_string))
+                .ok_or_else(|| anyhow::anyhow!("'{}' is missing a body", name))?;
+            let uses = get(item, USES_FIELDS)
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default();
+            let enabled = get(item, ENABLED_FIELDS)
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true);
+
+            Ok(Row {
+                name,
+                body,
+                uses: uses as _,
+                enabled,
+            })
+        })
+        .collect()
+}
+
+fn parse_csv(data: &str) -> anyhow::Result<Vec<Row>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(data.as_bytes());
+
+    let headers = reader.headers()?.clone();
+    let column = |keys: &[&str]| {
+        headers
+            .iter()
+            .position(|h| keys.iter().any(|k| h.trim().eq_ignore_ascii_case(k)))
+    };
+
+    let name = column(NAME_FIELDS)
+        .ok_or_else(|| anyhow::anyhow!("cannot find the command name column"))?;
+    let body =
+        column(BODY_FIELDS).ok_or_else(|| anyhow::anyhow!("cannot find the response column"))?;
+    let (uses, enabled) = (column(USES_FIELDS), column(ENABLED_FIELDS));
+
+    reader
+        .records()
+        .map(|record| {
+            let record = record?;
+            let field = |index: Option<usize>| index.and_then(|i| record.get(i)).map(str::trim);
+            Ok(Row {
+                name: field(Some(name)).unwrap_or_default().to_string(),
+                body: field(Some(body)).unwrap_or_default().to_string(),
+                uses: field(uses).and_then(|s| s.parse().ok()).unwrap_or_default(),
+                enabled: field(enabled)
+                    .map(|s| !s.eq_ignore_ascii_case("false") && s != "0")
+                    .unwrap_or(true),
+            })
+        })
+        .filter(|row: &anyhow::Result<Row>| {
+            row.as_ref().map(|row| !row.name.is_empty()).unwrap_or(true)
+        })
+        .collect()
+}
+
+/// Translate the variables in a body to ours, returning the unsupported ones
+///
+/// This writes the `${name}` and `$$` syntax of [`Body`](../body/struct.Body.html).
+/// Unsupported variables, and any that `Body` wouldn't accept, are kept as literal text
+fn translate(source: Source, body: &str) -> (String, Vec<String>) {
+    // the trailing `\$` matches any other '$', so it can be escaped
+    static NIGHTBOT: Lazy<Regex> = Lazy::new(|| Regex::new(r"\$\(([^()]*)\)|\$").unwrap());
+    static STREAM_ELEMENTS: Lazy<Regex> = Lazy::new(|| Regex::new(r"\$\{([^}]*)\}|\$").unwrap());
+    static STREAMLABS: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"\$([A-Za-z]\w*)(?:\(([^)]*)\))?|\$").unwrap());
+
+    let (re, map): (&Regex, fn(&Captures<'_>) -> Option<String>) = match source {
+        Source::Nightbot => (&*NIGHTBOT, nightbot),
+        Source::StreamElements => (&*STREAM_ELEMENTS, stream_elements),
+        Source::Streamlabs => (&*STREAMLABS, streamlabs),
+    };
+
+    let mut unsupported = vec![];
+    let body = re.replace_all(body, |caps: &Captures<'_>| {
+        if caps.get(1).is_none() {
+            return "$$".to_string();
+        }
+        match map(caps)
+            .map(|var| format!("${{{}}}", var))
+            .filter(|var| Body::parse(var).is_ok())
+        {
+            Some(var) => var,
+            None => {
+                unsupported.push(caps[0].to_string());
+                caps[0].replace('$', "$$")
+            }
+        }
+    });
+
+    (body.into_owned(), unsupported)
+}
+
+fn nightbot(caps: &Captures<'_>) -> Option<String> {
+    let var = match caps[1].trim().to_ascii_lowercase().as_str() {
+        "user" => "user".into(),
+        "touser" => "target".into(),
+        "query" => "args".into(),
+        "count" => "uses".into(),
+        n if is_index(n) => n.into(),
+        _ => return None,
+    };
+    Some(var)
+}
+
+fn stream_elements(caps: &Captures<'_>) -> Option<String> {
+    let inner = caps[1].trim();
+    let var = match inner.to_ascii_lowercase().as_str() {
+        "user" | "user.name" | "sender" | "sender.name" => "user".into(),
+        "touser" => "target".into(),
+        "1:" => "args".into(),
+        "uptime" => "uptime".into(),
+        n if is_index(n) => n.into(),
+        s if s.starts_with("getcount ") => format!("counter {}", inner[9..].trim()),
+        s if s.starts_with("random.pick ") => {
+            let choices = inner[12..]
+                .split('\'')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>();
+            format!("choice {}", choices.join("|"))
+        }
+        s if s.starts_with("random.") => match s[7..].split('-').collect::<Vec<_>>()[..] {
+            [low, high] if is_number(low) && is_number(high) => format!("random {} {}", low, high),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(var)
+}
+
+fn streamlabs(caps: &Captures<'_>) -> Option<String> {
+    let var = match caps[1].to_ascii_lowercase().as_str() {
+        "user" | "username" => "user".into(),
+        "touser" | "target" => "target".into(),
+        "msg" => "args".into(),
+        "count" => "uses".into(),
+        "uptime" => "uptime".into(),
+        "randnum" => {
+            let mut range = caps.get(2)?.as_str().split(',').map(str::trim);
+            match (range.next(), range.next()) {
+                (Some(low), Some(high)) if is_number(low) && is_number(high) => {
+                    format!("random {} {}", low, high)
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    Some(var)
+}
+
+fn is_index(s: &str) -> bool {
+    s.len() == 1 && s.chars().all(|c| ('1'..='9').contains(&c))
+}
+
+fn is_number(s: &str) -> bool {
+    !s.is_empty()
+        && s.trim_start_matches('-')
+            .chars()
+            .all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translate_nightbot() {
+        let (body, unsupported) = translate(
+            Source::Nightbot,
+            "hello $(touser), from $(user). $(1) and $(query). costs $5 $(urlfetch foo)",
+        );
+        assert_eq!(
+            body,
+            "hello ${target}, from ${user}. ${1} and ${args}. costs $$5 $$(urlfetch foo)"
+        );
+        assert_eq!(unsupported, vec!["$(urlfetch foo)"]);
+    }
+
+    #[test]
+    fn translate_stream_elements() {
+        let (body, unsupported) = translate(
+            Source::StreamElements,
+            "${sender} hugs ${touser} ${random.pick 'a' 'b c'} ${random.1-10} ${getcount deaths} ${pointsname}",
+        );
+        assert_eq!(
+            body,
+            "${user} hugs ${target} ${choice a|b c} ${random 1 10} ${counter deaths} $${pointsname}"
+        );
+        assert_eq!(unsupported, vec!["${pointsname}"]);
+    }
+
+    #[test]
+    fn translate_streamlabs() {
+        let (body, unsupported) = translate(
+            Source::Streamlabs,
+            "$username rolled $randnum(1,6) for $target, $5 $currency",
+        );
+        assert_eq!(
+            body,
+            "${user} rolled ${random 1 6} for ${target}, $$5 $$currency"
+        );
+        assert_eq!(unsupported, vec!["$currency"]);
+    }
+
+    #[test]
+    fn translate_invalid() {
+        // these look supported, but the variables they'd become aren't valid
+        let (body, unsupported) = translate(
+            Source::StreamElements,
+            "${random.10-1} ${getcount boss-deaths} ${random.pick ''} ${user}",
+        );
+        assert_eq!(
+            body,
+            "$${random.10-1} $${getcount boss-deaths} $${random.pick ''} ${user}"
+        );
+        assert_eq!(unsupported.len(), 3);
+        assert!(Body::parse(&body).is_ok());
+
+        let (body, unsupported) = translate(Source::Streamlabs, "$randnum(6,1) $user");
+        assert_eq!(body, "$$randnum(6,1) ${user}");
+        assert_eq!(unsupported, vec!["$randnum(6,1)"]);
+    }
+
+    #[test]
+    fn parse_json() {
+        let nightbot = r#"{"_total": 1, "commands": [
+            {"name": "!discord", "message": "join us $(user)", "count": 4, "coolDown": 5}
+        ]}"#;
+        let commands = parse(Source::Nightbot, Format::Json, nightbot).unwrap();
+        assert_eq!(
+            commands,
+            vec![Imported {
+                name: "!discord".into(),
+                body: "join us ${user}".into(),
+                uses: 4,
+                enabled: true,
+                unsupported: vec![],
+            }]
+        );
+
+        let stream_elements = r#"[
+            {"command": "discord", "reply": "join us ${user}", "enabled": false}
+        ]"#;
+        let commands = parse(Source::StreamElements, Format::Json, stream_elements).unwrap();
+        assert_eq!(commands[0].name, "!discord");
+        assert!(!commands[0].enabled);
+    }
+
+    #[test]
+    fn parse_csv() {
+        let streamlabs = "Command,Permission,Info,Group,Response,Cooldown,Count,Enabled\n\
+                          !discord,Everyone,,GLOBAL,join us $username,5,12,True\n\
+                          !off,Everyone,,GLOBAL,nope,5,0,False\n";
+        let commands = parse(Source::Streamlabs, Format::Csv, streamlabs).unwrap();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].body, "join us ${user}");
+        assert_eq!(commands[0].uses, 12);
+        assert!(!commands[1].enabled);
+    }
+}
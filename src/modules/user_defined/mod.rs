@@ -1,13 +1,32 @@
 #![cfg_attr(debug_assertions, allow(dead_code, unused_variables, unused_mut))]
 
+mod body;
+mod cache;
+pub(crate) mod history;
+pub(crate) mod import;
 mod registry;
 mod response;
-use {registry::Registry, response::Response};
+mod triggers;
+use {
+    body::Body,
+    cache::Cache,
+    history::{Action, History},
+    registry::Registry,
+    response::Response,
+    triggers::{Pattern, Trigger, TriggerCache, Triggers},
+};
 
 use {super::*, crate::*};
 
+use futures::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
 #[derive(Debug, Clone, Eq, Ord)]
 struct UserDefinedCommand {
+    /// Assigned by the database, `0` until the command has been added
+    id: i64,
     name: String,
     body: String,
     room: i64,
@@ -15,6 +34,82 @@ struct UserDefinedCommand {
     owner: i64,
     disabled: bool,
     created_at: time::OffsetDateTime, // maybe time::PrimitiveDateTime (we're going to assume UTC)
+    /// The minimum role needed to use the command
+    role: Role,
+    /// Seconds between uses, per room
+    cooldown: u64,
+    /// Whether every room can use the command
+    global: bool,
+    /// The other bodies of the command, after `body`
+    variants: Vec<String>,
+    /// How the bodies are used
+    mode: Mode,
+}
+
+impl UserDefinedCommand {
+    /// All of the bodies, starting with `body`
+    fn bodies(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.body.as_str()).chain(self.variants.iter().map(String::as_str))
+    }
+
+    /// The bodies that should be sent for this use of the command
+    fn choose(&self, uses: i32, rng: &mut impl rand::Rng) -> Vec<&str> {
+        use rand::seq::IteratorRandom as _;
+        match self.mode {
+            Mode::Random => self.bodies().choose(rng).into_iter().collect(),
+            Mode::RoundRobin => {
+                let n = (uses.max(1) - 1) as usize % (self.variants.len() + 1);
+                self.bodies().nth(n).into_iter().collect()
+            }
+            Mode::Sequence => self.bodies().collect(),
+        }
+    }
+
+    fn variants_json(&self) -> String {
+        serde_json::to_string(&self.variants).expect("strings are valid json")
+    }
+}
+
+/// How the bodies of a command are used
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Mode {
+    /// Pick one at random
+    Random,
+    /// Take turns
+    RoundRobin,
+    /// Send all of them, in order
+    Sequence,
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Self::Random
+    }
+}
+
+impl Mode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Random => "random",
+            Self::RoundRobin => "roundrobin",
+            Self::Sequence => "sequence",
+        }
+    }
+}
+
+impl std::str::FromStr for Mode {
+    type Err = anyhow::Error;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "random" => Ok(Self::Random),
+            "roundrobin" | "round-robin" | "round_robin" => Ok(Self::RoundRobin),
+            "sequence" | "all" => Ok(Self::Sequence),
+            mode => anyhow::bail!(
+                "unknown mode '{}'. use random, roundrobin or sequence",
+                mode
+            ),
+        }
+    }
 }
 
 impl PartialEq for UserDefinedCommand {
@@ -25,6 +120,11 @@ impl PartialEq for UserDefinedCommand {
             && self.uses.eq(&other.uses)
             && self.owner.eq(&other.owner)
             && self.disabled.eq(&other.disabled)
+            && self.role.eq(&other.role)
+            && self.cooldown.eq(&other.cooldown)
+            && self.global.eq(&other.global)
+            && self.variants.eq(&other.variants)
+            && self.mode.eq(&other.mode)
     }
 }
 
@@ -45,8 +145,20 @@ where
     init.command_map.add("info", info);
     init.command_map.add("delete", delete);
     init.command_map.add("rename", rename);
+    init.command_map.add("addvariant", add_variant);
+    init.command_map.add("delvariant", delete_variant);
+    init.command_map.add("history", history);
+    init.command_map.add("revert", revert);
+    init.command_map.add("share", share);
+    init.command_map.add("unshare", unshare);
+    init.command_map.add("global", global);
+    init.command_map.add("addtrigger", add_trigger);
+    init.command_map.add("edittrigger", edit_trigger);
+    init.command_map.add("deltrigger", delete_trigger);
 
     init.passive_list.add(user_defined);
+    init.passive_list.add(user_triggers);
+    init.state.insert(Cooldowns::default());
 
     Registry::initialize_table(init.pool.clone()).await?;
     Registry::reserve_many(
@@ -78,13 +190,53 @@ where
     )
     .await?;
 
+    let triggers = TriggerCache::load(init.pool.clone()).await?;
+    init.state.insert(triggers);
+
+    let cache = Cache::load(init.pool.clone()).await?;
+    init.state.insert(cache.clone());
+
+    // uses are counted in the cache, and written every so often and when the bot stops
+    let pool = init.pool.clone();
+    let mut shutdown = init.bus.subscribe::<crate::bus::events::Shutdown>();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            // the bot waits until the event is dropped, so hold on to it until they're written
+            let stopping = tokio::select! {
+                _ = interval.tick() => None,
+                event = shutdown.next() => Some(event),
+            };
+            if let Err(err) = cache.flush(pool.clone()).await {
+                log::warn!("cannot save the uses of user commands: {}", err);
+            }
+            if stopping.is_some() {
+                break;
+            }
+        }
+    });
+
     Ok(())
 }
 
-fn parse_command(cmd: &Command) -> (Option<&String>, Option<String>) {
-    let mut iter = cmd.tail.iter(); // is this right?
+/// How often the uses of commands are written to the database
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Keep the cache in sync after changing the commands in the database
+async fn refresh_cache(context: &Context<Command>, pool: sqlx::SqlitePool) -> anyhow::Result<()> {
+    let cache = context.state().await.expect_get::<Cache>()?.clone();
+    cache.refresh(pool).await
+}
+
+/// Commands are stored with their `!` prefix, but it's optional when managing them
+fn command_name(name: &str) -> String {
+    format!("!{}", name.trim_start_matches('!'))
+}
+
+fn parse_command(cmd: &Command) -> (Option<String>, Option<String>) {
+    let mut iter = cmd.tail.iter();
     let (head, tail) = (
-        iter.next(),
+        iter.next().map(|s| command_name(s)),
         iter.fold(String::new(), |mut a, c| {
             if !a.is_empty() {
                 a.push_str(" ");
@@ -106,7 +258,7 @@ where
 {
     match parse_command(&context.args) {
         // command and body
-        (Some(head), Some(tail)) => Ok(Some((head.clone(), tail))),
+        (Some(head), Some(tail)) => Ok(Some((head, tail))),
         // empty body
         (Some(head), None) => {
             let resp = Response::ErrorMissingTail { head: &head };
@@ -122,36 +274,112 @@ where
     }
 }
 
-async fn authorized(
+/// Replies with the error if the body isn't valid
+async fn assert_valid_body<R>(
     context: &Context<Command>,
-    udc: &UserDefinedCommand,
-    pool: sqlx::SqlitePool,
-) -> anyhow::Result<bool> {
-    let (room, user) = (context.room(), context.user());
+    responder: &mut R,
+    body: &str,
+) -> anyhow::Result<Option<()>>
+where
+    R: Responder + Send + 'static,
+{
+    match Body::parse(body) {
+        Ok(..) => Ok(Some(())),
+        Err(err) => {
+            let resp = Response::ErrorInvalidBody {
+                error: &err.to_string(),
+            };
+            responder.reply(context, &resp).await?;
+            Ok(None)
+        }
+    }
+}
+
+async fn authorized(context: &Context<Command>, udc: &UserDefinedCommand) -> anyhow::Result<bool> {
+    if context.user().id == udc.owner as u64 {
+        return Ok(true);
+    }
+
+    // moderators can only change their own room's commands
+    let local = udc.room == context.room().id as i64;
+    if local && Role::of(&context.args.message) >= Role::Moderator {
+        return Ok(true);
+    }
 
-    let state = context.state().await;
-    let twitch = state.expect_get::<TwitchClient>()?;
+    is_bot_owner(context).await
+}
 
-    let owned = twitch
-        .get_users(&context.config.owners)
+async fn is_bot_owner(context: &Context<Command>) -> anyhow::Result<bool> {
+    let (tracker, twitch) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<Tracker>()?.clone(),
+            state.expect_get::<TwitchClient>()?.clone(),
+        )
+    };
+
+    // owners can be configured by id or by any name they've had
+    let mut unknown = vec![];
+    for owner in &context.config.owners {
+        match tracker.resolve(owner).await? {
+            Some(id) if id == context.user().id => return Ok(true),
+            Some(..) => {}
+            None => unknown.push(owner.as_str()),
+        }
+    }
+
+    if unknown.is_empty() {
+        return Ok(false);
+    }
+
+    Ok(twitch
+        .get_users(&unknown)
         .await?
         .iter()
         .map(|user| user.id)
-        .any(|d| d == udc.owner as u64);
-
-    let authed = {
-        let msg = &context.args.message;
-        msg.is_moderator()
-            || msg.badges().iter().map(|s| &s.kind).fold(false, |ok, b| {
-                use twitchchat::BadgeKind::*;
-                ok & match b {
-                    Broadcaster | Moderator => true,
-                    _ => false,
-                }
-            })
-    };
+        .any(|id| id == context.user().id))
+}
+
+/// The display names of these users, only asking twitch about the ones that haven't been seen
+async fn display_names<I>(
+    tracker: &Tracker,
+    twitch: &TwitchClient,
+    ids: I,
+) -> anyhow::Result<HashMap<i64, String>>
+where
+    I: IntoIterator<Item = i64>,
+{
+    let mut names = HashMap::new();
+    let mut unknown = vec![];
+    for id in ids {
+        match tracker.user_name(id as u64) {
+            Some(name) => {
+                names.insert(id, name);
+            }
+            // twitch won't know who they were either
+            None if id == FORGOTTEN_USER => {}
+            None => unknown.push(id as u64),
+        }
+    }
+
+    if unknown.is_empty() {
+        return Ok(names);
+    }
+
+    let users = twitch.get_users_from_id(&unknown).await?;
+    for user in users {
+        tracker
+            .observe(user.id, &user.login, &user.display_name)
+            .await?;
+        names.insert(user.id as i64, user.display_name);
+    }
+    Ok(names)
+}
 
-    Ok(authed || owned)
+/// Counters answer to `!name` too, so commands can't share their names
+async fn is_counter(counters: &Counters, room: u64, name: &str) -> anyhow::Result<bool> {
+    let name = name.trim_start_matches('!').to_lowercase();
+    Ok(counters.get(room, &name).await?.is_some())
 }
 
 async fn add<R>(context: Context<Command>, mut responder: R) -> Result
@@ -159,11 +387,20 @@ where
     R: Responder + Send + 'static,
 {
     let (head, tail) = assert_both(&context, &mut responder).await?.dont_care()?;
+    assert_valid_body(&context, &mut responder, &tail)
+        .await?
+        .dont_care()?;
 
-    let state = context.state().await;
-    let pool = state.expect_get::<sqlx::SqlitePool>()?.clone();
+    let (pool, counters) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<sqlx::SqlitePool>()?.clone(),
+            state.expect_get::<Counters>()?.clone(),
+        )
+    };
 
     let udc = UserDefinedCommand {
+        id: 0,
         name: head,
         body: tail,
         room: context.room().id as _,
@@ -171,14 +408,31 @@ where
         uses: 0,
         disabled: false,
         created_at: time::OffsetDateTime::now(),
+        role: Role::default(),
+        cooldown: 0,
+        global: false,
+        variants: vec![],
+        mode: Mode::default(),
     };
 
-    let resp = match Registry::add_user_command(pool, &udc).await? {
+    // the counter would answer to it as well
+    if is_counter(&counters, context.room().id, &udc.name).await? {
+        let resp = Response::ErrorAlreadyExists { command: &udc.name };
+        return responder.say(&context, &resp).await;
+    }
+
+    let resp = match Registry::add_user_command(pool.clone(), &udc).await? {
         registry::AddResult::Builtin => Response::ErrorReservedName { command: &udc.name },
         registry::AddResult::Exists => Response::ErrorAlreadyExists { command: &udc.name },
-        registry::AddResult::Okay => Response::Added { command: &udc.name },
+        registry::AddResult::Okay => {
+            let actor = context.user().id;
+            History::record(pool.clone(), Action::Add, actor, udc.room, &udc.name, None).await?;
+            Response::Added { command: &udc.name }
+        }
     };
 
+    refresh_cache(&context, pool).await?;
+
     responder.say(&context, &resp).await
 }
 
@@ -187,10 +441,27 @@ where
     R: Responder + Send + 'static,
 {
     let (head, tail) = assert_both(&context, &mut responder).await?.dont_care()?;
+    let edit = match Edit::parse(&tail) {
+        Ok(edit) => edit,
+        Err(err) => {
+            let resp = Response::ErrorInvalidFlag {
+                error: &err.to_string(),
+            };
+            return responder.reply(&context, &resp).await;
+        }
+    };
+    if let Some(body) = &edit.body {
+        assert_valid_body(&context, &mut responder, body)
+            .await?
+            .dont_care()?;
+    }
     let room = context.room();
 
-    let state = context.state().await;
-    let pool = state.expect_get::<sqlx::SqlitePool>()?.clone();
+    let pool = context
+        .state()
+        .await
+        .expect_get::<sqlx::SqlitePool>()?
+        .clone();
 
     let mut udc = match Registry::lookup(pool.clone(), &head, room.id).await? {
         Some(udc) => udc,
@@ -201,7 +472,7 @@ where
         }
     };
 
-    if !authorized(&context, &udc, pool.clone()).await? {
+    if !authorized(&context, &udc).await? {
         return responder
             .reply(
                 &context,
@@ -210,9 +481,34 @@ where
             .await;
     }
 
-    udc.body = tail;
+    let previous = udc.clone();
+    if let Some(body) = edit.body {
+        udc.body = body;
+    }
+    if let Some(role) = edit.role {
+        udc.role = role;
+    }
+    if let Some(cooldown) = edit.cooldown {
+        udc.cooldown = cooldown;
+    }
+    if let Some(mode) = edit.mode {
+        udc.mode = mode;
+    }
     let _ = Registry::update(pool.clone(), &udc).await?; // bool
 
+    let actor = context.user().id;
+    History::record(
+        pool.clone(),
+        Action::Edit,
+        actor,
+        udc.room,
+        &udc.name,
+        Some(&previous),
+    )
+    .await?;
+
+    refresh_cache(&context, pool).await?;
+
     responder
         .reply(&context, &Response::Edited { command: &udc.name })
         .await
@@ -226,10 +522,17 @@ where
     let head = head.dont_care()?;
     let room = context.room();
 
-    let state = context.state().await;
-    let pool = state.expect_get::<sqlx::SqlitePool>()?.clone();
+    let (pool, cache, tracker, twitch) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<sqlx::SqlitePool>()?.clone(),
+            state.expect_get::<Cache>()?.clone(),
+            state.expect_get::<Tracker>()?.clone(),
+            state.expect_get::<TwitchClient>()?.clone(),
+        )
+    };
 
-    let cmd = match Registry::lookup(pool, &head, room.id).await? {
+    let cmd = match cache.lookup(&head, room.id) {
         Some(cmd) => cmd,
         None => {
             return responder
@@ -238,28 +541,52 @@ where
         }
     };
 
-    let owner = match state
-        .expect_get::<crate::TwitchClient>()?
-        .get_users_from_id(&[cmd.owner])
-        .await?
-        .pop()
-    {
-        Some(user) => user.display_name,
-        None => "<unknown>".into(),
-    };
+    let names = display_names(&tracker, &twitch, vec![cmd.owner, cmd.room]).await?;
+    let name_of = |id: i64| names.get(&id).map(String::as_str).unwrap_or("<unknown>");
+    let owner = name_of(cmd.owner);
+    let from = name_of(cmd.room);
 
-    let resp = &[
-        Response::CommandDescription {
+    let role = cmd.role.to_string();
+    let mut resp = vec![];
+    if cmd.variants.is_empty() {
+        resp.push(Response::CommandDescription {
             command: &cmd.name,
             body: &cmd.body,
-        },
-        Response::CommandCreatedAt {
-            owner: &owner,
-            uses: cmd.uses as _,
-        },
-    ];
+        });
+    } else {
+        resp.push(Response::CommandMode {
+            mode: cmd.mode.as_str(),
+            count: cmd.variants.len() as u64 + 1,
+        });
+        for (n, body) in cmd.bodies().enumerate() {
+            resp.push(Response::CommandVariant {
+                n: n as u64 + 1,
+                body,
+            });
+        }
+    }
+    resp.push(Response::CommandCreatedAt {
+        owner,
+        uses: cmd.uses as _,
+    });
+
+    if cmd.room != room.id as i64 {
+        let shared = Registry::shares(pool, &cmd).await?.contains(&room.id);
+        resp.push(if shared {
+            Response::CommandShared { room: from }
+        } else {
+            Response::CommandGlobal { room: from }
+        });
+    }
 
-    for resp in resp {
+    if cmd.role != Role::Everyone || cmd.cooldown > 0 {
+        resp.push(Response::CommandRestrictions {
+            role: &role,
+            cooldown: cmd.cooldown,
+        });
+    }
+
+    for resp in &resp {
         responder.reply(&context, resp).await?;
     }
     Ok(())
@@ -271,10 +598,13 @@ where
 {
     let (head, _) = parse_command(&context.args);
     let head = head.dont_care()?;
-    let (room, user) = (context.room(), context.user());
+    let room = context.room();
 
-    let state = context.state().await;
-    let pool = state.expect_get::<sqlx::SqlitePool>()?.clone();
+    let pool = context
+        .state()
+        .await
+        .expect_get::<sqlx::SqlitePool>()?
+        .clone();
 
     let udc = match Registry::lookup(pool.clone(), &head, room.id).await? {
         Some(udc) => udc,
@@ -285,7 +615,7 @@ where
         }
     };
 
-    if !authorized(&context, &udc, pool.clone()).await? {
+    if !authorized(&context, &udc).await? {
         return responder
             .reply(
                 &context,
@@ -295,11 +625,25 @@ where
     }
 
     // TODO should this say that its a builtin command?
-    let resp = match Registry::remove(pool, &udc).await? {
+    let resp = match Registry::remove(pool.clone(), &udc).await? {
         registry::RemoveResult::Missing => Response::ErrorCommandNotFound { command: &head },
-        registry::RemoveResult::Okay => Response::Deleted,
+        registry::RemoveResult::Okay => {
+            let actor = context.user().id;
+            History::record(
+                pool.clone(),
+                Action::Delete,
+                actor,
+                udc.room,
+                &udc.name,
+                Some(&udc),
+            )
+            .await?;
+            Response::Deleted
+        }
     };
 
+    refresh_cache(&context, pool).await?;
+
     responder.reply(&context, &resp).await
 }
 
@@ -308,12 +652,293 @@ where
     R: Responder + Send + 'static,
 {
     let (head, tail) = assert_both(&context, &mut responder).await?.dont_care()?;
-    let (room, user) = (context.room(), context.user());
+    let room = context.room();
 
-    let state = context.state().await;
-    let pool = state.expect_get::<sqlx::SqlitePool>()?.clone();
+    let (pool, counters) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<sqlx::SqlitePool>()?.clone(),
+            state.expect_get::<Counters>()?.clone(),
+        )
+    };
 
-    let mut udc = match Registry::lookup(pool.clone(), &head, room.id).await? {
+    let udc = match Registry::lookup(pool.clone(), &head, room.id).await? {
+        Some(udc) => udc,
+        None => {
+            return responder
+                .reply(&context, &Response::ErrorCommandNotFound { command: &head })
+                .await
+        }
+    };
+
+    if !authorized(&context, &udc).await? {
+        return responder
+            .reply(
+                &context,
+                &Response::ErrorInsufficientPrivlege { command: &head },
+            )
+            .await;
+    }
+
+    let to = command_name(tail.split_whitespace().next().unwrap_or_default());
+    if is_counter(&counters, room.id, &to).await? {
+        let resp = Response::ErrorAlreadyExists { command: &to };
+        return responder.reply(&context, &resp).await;
+    }
+
+    let resp = match Registry::rename(pool.clone(), &udc, &to).await? {
+        registry::AddResult::Builtin => Response::ErrorReservedName { command: &to },
+        registry::AddResult::Exists => Response::ErrorAlreadyExists { command: &to },
+        registry::AddResult::Okay => {
+            let actor = context.user().id;
+            History::record(
+                pool.clone(),
+                Action::Rename,
+                actor,
+                udc.room,
+                &to,
+                Some(&udc),
+            )
+            .await?;
+            Response::Renamed {
+                from: &udc.name,
+                to: &to,
+            }
+        }
+    };
+
+    refresh_cache(&context, pool).await?;
+
+    responder.reply(&context, &resp).await
+}
+
+/// Add another body to a command
+async fn add_variant<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let (head, tail) = assert_both(&context, &mut responder).await?.dont_care()?;
+    assert_valid_body(&context, &mut responder, &tail)
+        .await?
+        .dont_care()?;
+
+    change_variants(context, responder, &head, |udc| {
+        udc.variants.push(tail);
+        Ok(udc.variants.len() + 1)
+    })
+    .await
+}
+
+/// Remove one of the bodies of a command, by its number in `!info`
+async fn delete_variant<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let (head, tail) = assert_both(&context, &mut responder).await?.dont_care()?;
+    let n = match tail.parse::<usize>() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            let resp = Response::ErrorInvalidFlag {
+                error: "the variant must be a number, starting at 1",
+            };
+            return responder.reply(&context, &resp).await;
+        }
+    };
+
+    change_variants(context, responder, &head, |udc| {
+        anyhow::ensure!(n <= udc.variants.len() + 1, "there isn't a variant #{}", n);
+        anyhow::ensure!(
+            !udc.variants.is_empty(),
+            "a command needs at least one body"
+        );
+        match n {
+            1 => udc.body = udc.variants.remove(0),
+            n => {
+                udc.variants.remove(n - 2);
+            }
+        }
+        Ok(n)
+    })
+    .await
+}
+
+async fn change_variants<R, F>(
+    context: Context<Command>,
+    mut responder: R,
+    head: &str,
+    change: F,
+) -> Result
+where
+    R: Responder + Send + 'static,
+    F: FnOnce(&mut UserDefinedCommand) -> anyhow::Result<usize>,
+{
+    let room = context.room();
+
+    let pool = context
+        .state()
+        .await
+        .expect_get::<sqlx::SqlitePool>()?
+        .clone();
+
+    let mut udc = match Registry::lookup(pool.clone(), head, room.id).await? {
+        Some(udc) => udc,
+        None => {
+            return responder
+                .reply(&context, &Response::ErrorCommandNotFound { command: head })
+                .await
+        }
+    };
+
+    if !authorized(&context, &udc).await? {
+        return responder
+            .reply(
+                &context,
+                &Response::ErrorInsufficientPrivlege { command: head },
+            )
+            .await;
+    }
+
+    let previous = udc.clone();
+    let n = match change(&mut udc) {
+        Ok(n) => n,
+        Err(err) => {
+            let resp = Response::ErrorInvalidFlag {
+                error: &err.to_string(),
+            };
+            return responder.reply(&context, &resp).await;
+        }
+    };
+    let _ = Registry::update(pool.clone(), &udc).await?; // bool
+
+    let actor = context.user().id;
+    History::record(
+        pool.clone(),
+        Action::Edit,
+        actor,
+        udc.room,
+        &udc.name,
+        Some(&previous),
+    )
+    .await?;
+
+    refresh_cache(&context, pool).await?;
+
+    let resp = if udc.variants.len() < previous.variants.len() {
+        Response::VariantDeleted {
+            command: &udc.name,
+            n: n as _,
+        }
+    } else {
+        Response::VariantAdded {
+            command: &udc.name,
+            n: n as _,
+        }
+    };
+    responder.reply(&context, &resp).await
+}
+
+/// Share one of this room's commands with other rooms
+async fn share<R>(context: Context<Command>, responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    change_shares(context, responder, true).await
+}
+
+/// Stop sharing one of this room's commands with other rooms
+async fn unshare<R>(context: Context<Command>, responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    change_shares(context, responder, false).await
+}
+
+async fn change_shares<R>(context: Context<Command>, mut responder: R, share: bool) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let (head, tail) = assert_both(&context, &mut responder).await?.dont_care()?;
+    let room = context.room();
+
+    let (pool, twitch) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<sqlx::SqlitePool>()?.clone(),
+            state.expect_get::<TwitchClient>()?.clone(),
+        )
+    };
+
+    let udc = match Registry::lookup_local(pool.clone(), &head, room.id).await? {
+        Some(udc) => udc,
+        None => {
+            return responder
+                .reply(&context, &Response::ErrorCommandNotFound { command: &head })
+                .await
+        }
+    };
+
+    // this changes what other rooms see, so only the bot owners can do it
+    if !is_bot_owner(&context).await? {
+        return responder
+            .reply(
+                &context,
+                &Response::ErrorInsufficientPrivlege { command: &head },
+            )
+            .await;
+    }
+
+    let names = tail
+        .split_whitespace()
+        .map(|s| s.trim_start_matches('#').to_lowercase())
+        .collect::<Vec<_>>();
+    let rooms = twitch
+        .get_users(&names)
+        .await?
+        .into_iter()
+        .map(|user| user.id)
+        .filter(|&id| id != room.id)
+        .collect::<Vec<_>>();
+
+    if rooms.is_empty() {
+        return responder
+            .reply(&context, &Response::ErrorUnknownRooms { rooms: &tail })
+            .await;
+    }
+
+    let resp = if share {
+        Registry::share(pool.clone(), &udc, &rooms).await?;
+        Response::Shared {
+            command: &udc.name,
+            count: rooms.len() as _,
+        }
+    } else {
+        Registry::unshare(pool.clone(), &udc, &rooms).await?;
+        Response::Unshared {
+            command: &udc.name,
+            count: rooms.len() as _,
+        }
+    };
+    refresh_cache(&context, pool).await?;
+
+    responder.reply(&context, &resp).await
+}
+
+/// Toggle whether one of this room's commands can be used in every room
+async fn global<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let (head, _) = parse_command(&context.args);
+    let head = head.dont_care()?;
+    let room = context.room();
+
+    let pool = context
+        .state()
+        .await
+        .expect_get::<sqlx::SqlitePool>()?
+        .clone();
+
+    let udc = match Registry::lookup_local(pool.clone(), &head, room.id).await? {
         Some(udc) => udc,
         None => {
             return responder
@@ -322,7 +947,7 @@ where
         }
     };
 
-    if !authorized(&context, &udc, pool.clone()).await? {
+    if !is_bot_owner(&context).await? {
         return responder
             .reply(
                 &context,
@@ -331,15 +956,472 @@ where
             .await;
     }
 
-    let old = std::mem::replace(&mut udc.name, tail.clone());
-    let resp = match Registry::update(pool, &udc).await? {
-        true => Response::Renamed {
-            from: &old,
-            to: &tail,
+    let _ = Registry::set_global(pool.clone(), &udc, !udc.global).await?; // bool
+    let resp = if udc.global {
+        Response::NotGlobal { command: &udc.name }
+    } else {
+        Response::Global { command: &udc.name }
+    };
+    refresh_cache(&context, pool).await?;
+
+    responder.reply(&context, &resp).await
+}
+
+/// The cooldown of a new trigger, in seconds
+const DEFAULT_TRIGGER_COOLDOWN: u64 = 30;
+
+/// The changes requested by `!addtrigger` and `!edittrigger`
+///
+/// Written as `name [--cooldown N] [pattern =>] [body]`, where the pattern is
+/// either some words or a `/regex/`
+#[derive(Debug, Default, PartialEq)]
+struct TriggerEdit {
+    name: String,
+    cooldown: Option<u64>,
+    pattern: Option<Pattern>,
+    body: Option<String>,
+}
+
+impl TriggerEdit {
+    fn parse(input: &str) -> anyhow::Result<Self> {
+        let (name, rest) = split_word(input.trim());
+        anyhow::ensure!(!name.is_empty(), "you must provide a name for the trigger");
+
+        let edit = Edit::parse(rest)?;
+        anyhow::ensure!(edit.role.is_none(), "triggers don't have a role");
+        anyhow::ensure!(edit.mode.is_none(), "triggers don't have a mode");
+
+        let (pattern, body) = match edit.body.as_deref().map(|s| s.splitn(2, "=>")) {
+            Some(mut iter) => match (iter.next(), iter.next()) {
+                (Some(pattern), Some(body)) => (Some(Pattern::parse(pattern)?), body.trim()),
+                (Some(body), None) => (None, body.trim()),
+                _ => (None, ""),
+            },
+            None => (None, ""),
+        };
+
+        Ok(Self {
+            name: name.to_lowercase(),
+            cooldown: edit.cooldown,
+            pattern,
+            body: Some(body.to_string()).filter(|s| !s.is_empty()),
+        })
+    }
+}
+
+/// Replies with the error if the trigger can't be parsed
+async fn parse_trigger<R>(
+    context: &Context<Command>,
+    responder: &mut R,
+) -> anyhow::Result<Option<TriggerEdit>>
+where
+    R: Responder + Send + 'static,
+{
+    let input = context.args.tail.join(" ");
+    let error = match TriggerEdit::parse(&input) {
+        Ok(edit) => match &edit.body {
+            Some(body) => match Body::parse(body) {
+                Ok(..) => return Ok(Some(edit)),
+                Err(err) => err.to_string(),
+            },
+            None => return Ok(Some(edit)),
         },
-        false => Response::ErrorCommandNotFound { command: &old },
+        Err(err) => err.to_string(),
+    };
+
+    let resp = Response::ErrorInvalidTrigger { error: &error };
+    responder.reply(context, &resp).await?;
+    Ok(None)
+}
+
+/// Moderators manage the triggers in their room
+async fn can_manage_triggers(context: &Context<Command>) -> anyhow::Result<bool> {
+    if Role::of(&context.args.message) >= Role::Moderator {
+        return Ok(true);
+    }
+    is_bot_owner(context).await
+}
+
+/// Keep the trigger cache in sync after changing the triggers in the database
+async fn refresh_triggers(
+    context: &Context<Command>,
+    pool: sqlx::SqlitePool,
+) -> anyhow::Result<()> {
+    let triggers = context.state().await.expect_get::<TriggerCache>()?.clone();
+    triggers.refresh(pool).await
+}
+
+async fn add_trigger<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let edit = parse_trigger(&context, &mut responder).await?.dont_care()?;
+    if !can_manage_triggers(&context).await? {
+        let resp = Response::ErrorInsufficientPrivlege {
+            command: &edit.name,
+        };
+        return responder.reply(&context, &resp).await;
+    }
+
+    let (pattern, body) = match (edit.pattern, edit.body) {
+        (Some(pattern), Some(body)) => (pattern, body),
+        _ => {
+            let resp = Response::ErrorInvalidTrigger {
+                error: "use: !addtrigger name [--cooldown N] pattern => body",
+            };
+            return responder.reply(&context, &resp).await;
+        }
+    };
+
+    let pool = context
+        .state()
+        .await
+        .expect_get::<sqlx::SqlitePool>()?
+        .clone();
+
+    let trigger = Trigger {
+        id: 0,
+        name: edit.name,
+        pattern,
+        body,
+        room: context.room().id as _,
+        owner: context.user().id as _,
+        cooldown: edit.cooldown.unwrap_or(DEFAULT_TRIGGER_COOLDOWN),
+        disabled: false,
+        created_at: time::OffsetDateTime::now().timestamp(),
+    };
+
+    let pattern = trigger.pattern.to_string();
+    let resp = if Triggers::add(pool.clone(), &trigger).await? {
+        Response::TriggerAdded {
+            name: &trigger.name,
+            pattern: &pattern,
+        }
+    } else {
+        Response::ErrorTriggerExists {
+            name: &trigger.name,
+        }
+    };
+
+    refresh_triggers(&context, pool).await?;
+
+    responder.reply(&context, &resp).await
+}
+
+async fn edit_trigger<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let edit = parse_trigger(&context, &mut responder).await?.dont_care()?;
+    if !can_manage_triggers(&context).await? {
+        let resp = Response::ErrorInsufficientPrivlege {
+            command: &edit.name,
+        };
+        return responder.reply(&context, &resp).await;
+    }
+
+    let pool = context
+        .state()
+        .await
+        .expect_get::<sqlx::SqlitePool>()?
+        .clone();
+
+    let mut trigger = match Triggers::lookup(pool.clone(), &edit.name, context.room().id).await? {
+        Some(trigger) => trigger,
+        None => {
+            let resp = Response::ErrorTriggerNotFound { name: &edit.name };
+            return responder.reply(&context, &resp).await;
+        }
+    };
+
+    if let Some(cooldown) = edit.cooldown {
+        trigger.cooldown = cooldown;
+    }
+    if let Some(pattern) = edit.pattern {
+        trigger.pattern = pattern;
+    }
+    if let Some(body) = edit.body {
+        trigger.body = body;
+    }
+    let _ = Triggers::update(pool.clone(), &trigger).await?; // bool
+
+    refresh_triggers(&context, pool).await?;
+
+    responder
+        .reply(
+            &context,
+            &Response::TriggerEdited {
+                name: &trigger.name,
+            },
+        )
+        .await
+}
+
+async fn delete_trigger<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let name = context.args.tail.first().dont_care()?.to_lowercase();
+    if !can_manage_triggers(&context).await? {
+        let resp = Response::ErrorInsufficientPrivlege { command: &name };
+        return responder.reply(&context, &resp).await;
+    }
+
+    let pool = context
+        .state()
+        .await
+        .expect_get::<sqlx::SqlitePool>()?
+        .clone();
+
+    let resp = match Triggers::lookup(pool.clone(), &name, context.room().id).await? {
+        Some(trigger) if Triggers::remove(pool.clone(), &trigger).await? => {
+            Response::TriggerDeleted { name: &name }
+        }
+        _ => Response::ErrorTriggerNotFound { name: &name },
     };
 
+    refresh_triggers(&context, pool).await?;
+
+    responder.reply(&context, &resp).await
+}
+
+async fn user_triggers<R>(context: Context<Passive>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    // commands aren't conversation
+    if context.data().starts_with('!') {
+        return dont_care();
+    }
+
+    let (triggers, cooldowns) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<TriggerCache>()?.clone(),
+            state.expect_get::<Cooldowns>()?.clone(),
+        )
+    };
+
+    // only respond once to a message
+    let room = context.room().id;
+    let trigger = triggers
+        .matches(room, context.data())
+        .into_iter()
+        .find(|trigger| cooldowns.try_use(room, &trigger.name, trigger.cooldown))
+        .dont_care()?;
+
+    let data = match Body::parse(&trigger.body) {
+        Ok(body) => render(&context, &body, 0).await,
+        Err(err) => {
+            log::warn!("invalid body for trigger '{}': {}", trigger.name, err);
+            trigger.body.clone()
+        }
+    };
+
+    responder
+        .say(&context, &Response::Say { data: &data })
+        .await
+}
+
+/// How many revisions `!history` shows
+const HISTORY_LIMIT: i64 = 3;
+
+async fn history<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let (head, _) = parse_command(&context.args);
+    let head = head.dont_care()?;
+    let room = context.room();
+
+    let (pool, tracker, twitch) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<sqlx::SqlitePool>()?.clone(),
+            state.expect_get::<Tracker>()?.clone(),
+            state.expect_get::<TwitchClient>()?.clone(),
+        )
+    };
+
+    let revisions = History::recent(pool, Some(room.id as _), Some(&head), HISTORY_LIMIT).await?;
+    if revisions.is_empty() {
+        return responder
+            .reply(&context, &Response::ErrorNoHistory { command: &head })
+            .await;
+    }
+
+    let actors = display_names(&tracker, &twitch, revisions.iter().map(|rev| rev.actor)).await?;
+
+    let now = time::OffsetDateTime::now();
+    for (n, rev) in revisions.iter().enumerate() {
+        let by = actors
+            .get(&rev.actor)
+            .map(String::as_str)
+            .unwrap_or("<unknown>");
+
+        let n = n as u64 + 1;
+        let ago = &(now - rev.at).as_readable_time();
+        let resp = match rev.action {
+            Action::Add => Response::HistoryAdded { n, by, ago },
+            Action::Edit => Response::HistoryEdited { n, by, ago },
+            Action::Rename => Response::HistoryRenamed { n, by, ago },
+            Action::Delete => Response::HistoryDeleted { n, by, ago },
+        };
+        responder.reply(&context, &resp).await?;
+    }
+    Ok(())
+}
+
+/// Undo a revision of a command, the most recent one by default
+async fn revert<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let (head, tail) = parse_command(&context.args);
+    let head = head.dont_care()?;
+    let n = match tail.as_deref().map(str::parse::<usize>) {
+        None => 1,
+        Some(Ok(n)) if n > 0 => n,
+        Some(..) => {
+            let resp = Response::ErrorInvalidFlag {
+                error: "the revision must be a number, starting at 1",
+            };
+            return responder.reply(&context, &resp).await;
+        }
+    };
+    let room = context.room();
+
+    let pool = context
+        .state()
+        .await
+        .expect_get::<sqlx::SqlitePool>()?
+        .clone();
+
+    let rev = match History::recent(pool.clone(), Some(room.id as _), Some(&head), n as _)
+        .await?
+        .into_iter()
+        .nth(n - 1)
+    {
+        Some(rev) => rev,
+        None => {
+            let resp = Response::ErrorNoRevision {
+                command: &head,
+                n: n as _,
+            };
+            return responder.reply(&context, &resp).await;
+        }
+    };
+
+    let current = Registry::lookup_local(pool.clone(), &head, room.id).await?;
+    let target = match (&current, &rev.previous) {
+        (Some(udc), _) | (None, Some(udc)) => udc,
+        (None, None) => {
+            return responder
+                .reply(&context, &Response::ErrorCommandNotFound { command: &head })
+                .await
+        }
+    };
+
+    if !authorized(&context, target).await? {
+        return responder
+            .reply(
+                &context,
+                &Response::ErrorInsufficientPrivlege { command: &head },
+            )
+            .await;
+    }
+
+    let actor = context.user().id;
+    let reverted = match (rev.action, current, rev.previous) {
+        // undo an add by deleting it
+        (Action::Add, Some(udc), _) => {
+            let ok = Registry::remove(pool.clone(), &udc).await? == registry::RemoveResult::Okay;
+            if ok {
+                History::record(
+                    pool.clone(),
+                    Action::Delete,
+                    actor,
+                    udc.room,
+                    &udc.name,
+                    Some(&udc),
+                )
+                .await?;
+            }
+            ok
+        }
+
+        // undo an edit by putting back what it was
+        (Action::Edit, Some(udc), Some(previous)) => {
+            let edited = UserDefinedCommand {
+                body: previous.body,
+                role: previous.role,
+                cooldown: previous.cooldown,
+                variants: previous.variants,
+                mode: previous.mode,
+                ..udc.clone()
+            };
+            let ok = Registry::update(pool.clone(), &edited).await?;
+            if ok {
+                History::record(
+                    pool.clone(),
+                    Action::Edit,
+                    actor,
+                    udc.room,
+                    &udc.name,
+                    Some(&udc),
+                )
+                .await?;
+            }
+            ok
+        }
+
+        // undo a rename by renaming it back
+        (Action::Rename, Some(udc), Some(previous)) => {
+            let ok = Registry::rename(pool.clone(), &udc, &previous.name).await?
+                == registry::AddResult::Okay;
+            if ok {
+                let name = &previous.name;
+                History::record(
+                    pool.clone(),
+                    Action::Rename,
+                    actor,
+                    udc.room,
+                    name,
+                    Some(&udc),
+                )
+                .await?;
+            }
+            ok
+        }
+
+        // undo a delete by adding it back
+        (Action::Delete, None, Some(previous)) => {
+            let ok = Registry::add_user_command(pool.clone(), &previous).await?
+                == registry::AddResult::Okay;
+            if ok {
+                let name = &previous.name;
+                History::record(pool.clone(), Action::Add, actor, previous.room, name, None)
+                    .await?;
+            }
+            ok
+        }
+
+        _ => false,
+    };
+
+    let resp = if reverted {
+        Response::Reverted {
+            command: &head,
+            n: n as _,
+        }
+    } else {
+        Response::ErrorCannotRevert {
+            command: &head,
+            n: n as _,
+        }
+    };
+    refresh_cache(&context, pool).await?;
+
     responder.reply(&context, &resp).await
 }
 
@@ -357,19 +1439,302 @@ where
         return dont_care();
     }
 
-    let state = context.state().await;
-    let pool = state.expect_get::<sqlx::SqlitePool>()?.clone();
-
-    let mut udc = Registry::lookup(pool.clone(), &head, context.room().id)
-        .await?
+    let cache = context.state().await.expect_get::<Cache>()?.clone();
+    let udc = cache
+        .lookup(&head, context.room().id)
+        .filter(|udc| !udc.disabled)
         .dont_care()?;
 
-    responder
-        .say(&context, &Response::Say { data: &udc.body })
-        .await?;
+    let role = Role::of(&context.args.message);
+    if role < udc.role {
+        return dont_care();
+    }
+
+    // moderators aren't held to the cooldown
+    if role < Role::Moderator {
+        let cooldowns = context.state().await.expect_get::<Cooldowns>()?.clone();
+        if !cooldowns.try_use(context.room().id, &udc.name, udc.cooldown) {
+            return dont_care();
+        }
+    }
 
-    udc.uses += 1;
-    let _ = Registry::update(pool, &udc).await?;
+    let uses = cache.add_use(&udc);
 
+    // the rng can't be held across an await, so choose them up front
+    let bodies = udc
+        .choose(uses, &mut rand::thread_rng())
+        .into_iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    for body in bodies {
+        let data = match Body::parse(&body) {
+            Ok(body) => render(&context, &body, uses).await,
+            Err(err) => {
+                // this was added before bodies were checked
+                log::warn!("invalid body for '{}': {}", udc.name, err);
+                body
+            }
+        };
+
+        responder
+            .say(&context, &Response::Say { data: &data })
+            .await?;
+    }
     Ok(())
 }
+
+/// When each user-defined command and trigger was last used
+#[derive(Default, Clone)]
+struct Cooldowns {
+    last: Arc<Mutex<HashMap<(u64, String), Instant>>>,
+}
+
+impl Cooldowns {
+    /// Marks it as used, unless its cooldown hasn't elapsed yet
+    fn try_use(&self, room: u64, name: &str, cooldown: u64) -> bool {
+        if cooldown == 0 {
+            return true;
+        }
+
+        let cooldown = Duration::from_secs(cooldown);
+        let mut last = self.last.lock().unwrap();
+        let key = (room, name.to_string());
+        match last.get(&key) {
+            Some(at) if at.elapsed() < cooldown => false,
+            _ => {
+                last.insert(key, Instant::now());
+                true
+            }
+        }
+    }
+}
+
+/// The changes requested by `!edit`
+///
+/// Flags come before the body: `!edit !cmd --role mod --cooldown 30 new body`
+#[derive(Debug, Default, PartialEq)]
+struct Edit {
+    role: Option<Role>,
+    cooldown: Option<u64>,
+    mode: Option<Mode>,
+    body: Option<String>,
+}
+
+impl Edit {
+    fn parse(input: &str) -> anyhow::Result<Self> {
+        let mut edit = Self::default();
+        let mut rest = input.trim_start();
+
+        while rest.starts_with("--") {
+            let (flag, tail) = split_word(rest);
+            let (value, tail) = split_word(tail);
+            anyhow::ensure!(!value.is_empty(), "'{}' needs a value", flag);
+
+            match flag {
+                "--role" => edit.role = Some(value.parse()?),
+                "--cooldown" => {
+                    let cooldown = value
+                        .parse()
+                        .map_err(|_| anyhow::anyhow!("'--cooldown' takes a number of seconds"))?;
+                    edit.cooldown = Some(cooldown)
+                }
+                "--mode" => edit.mode = Some(value.parse()?),
+                flag => anyhow::bail!("unknown flag '{}'. use --role, --cooldown or --mode", flag),
+            }
+            rest = tail;
+        }
+
+        edit.body = Some(rest.to_string()).filter(|s| !s.is_empty());
+        anyhow::ensure!(
+            edit.role.is_some()
+                || edit.cooldown.is_some()
+                || edit.mode.is_some()
+                || edit.body.is_some(),
+            "nothing to change"
+        );
+        Ok(edit)
+    }
+}
+
+fn split_word(input: &str) -> (&str, &str) {
+    match input.find(char::is_whitespace) {
+        Some(index) => (&input[..index], input[index..].trim_start()),
+        None => (input, ""),
+    }
+}
+
+/// Look up what the body needs, then render it
+async fn render(context: &Context<Passive>, body: &Body, uses: i32) -> String {
+    let (twitch, whatsong, counters) = {
+        let state = context.state().await;
+        (
+            state.get::<TwitchClient>().cloned(),
+            state.get::<super::whatsong::Client>().cloned(),
+            state.get::<Counters>().cloned(),
+        )
+    };
+    let room = context.room();
+
+    let mut env = body::Env {
+        user: context.user().name.to_string(),
+        args: context
+            .data()
+            .split_whitespace()
+            .skip(1)
+            .map(ToString::to_string)
+            .collect(),
+        uses,
+        ..Default::default()
+    };
+
+    if body.needs_uptime() {
+        if let Some(client) = twitch {
+            match client.get_streams_from_id(&[room.id]).await {
+                Ok(streams) => {
+                    env.uptime = streams
+                        .into_iter()
+                        .find(|stream| stream.user_id == room.id)
+                        .map(|stream| {
+                            (time::OffsetDateTime::now() - stream.started_at).as_readable_time()
+                        })
+                }
+                Err(err) => log::warn!("cannot get the uptime for {}: {}", room, err),
+            }
+        }
+    }
+
+    if body.needs_song() {
+        if let Some(client) = whatsong {
+            match client.current().await {
+                Ok(song) => env.song = song.map(|(_, song)| song.title),
+                Err(err) => log::warn!("cannot get the current song: {}", err),
+            }
+        }
+    }
+
+    if let Some(counters) = counters {
+        for name in body.counters() {
+            match counters.get(room.id, name).await {
+                Ok(Some(value)) => {
+                    env.counters.insert(name.to_string(), value);
+                }
+                Ok(None) => {}
+                Err(err) => log::warn!("cannot get counter '{}': {}", name, err),
+            }
+        }
+    }
+
+    body.render(&env, &mut rand::thread_rng())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_edit() {
+        assert_eq!(
+            Edit::parse("new body").unwrap(),
+            Edit {
+                body: Some("new body".into()),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            Edit::parse("--role mod --cooldown 30").unwrap(),
+            Edit {
+                role: Some(Role::Moderator),
+                cooldown: Some(30),
+                mode: None,
+                body: None,
+            }
+        );
+        assert_eq!(
+            Edit::parse("--cooldown 5 hello --world").unwrap(),
+            Edit {
+                cooldown: Some(5),
+                body: Some("hello --world".into()),
+                ..Default::default()
+            }
+        );
+
+        for input in &["--role", "--role admin", "--cooldown soon", "--foo bar", ""] {
+            assert!(Edit::parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn choose_variants() {
+        let mut udc = UserDefinedCommand {
+            id: 0,
+            name: "!hello".into(),
+            body: "a".into(),
+            room: 1234,
+            uses: 0,
+            owner: 1234,
+            disabled: false,
+            created_at: time::OffsetDateTime::now(),
+            role: Role::default(),
+            cooldown: 0,
+            global: false,
+            variants: vec!["b".into(), "c".into()],
+            mode: Mode::RoundRobin,
+        };
+
+        let mut rng = rand::rngs::mock::StepRng::new(0, 1);
+        let turns = (1..=4)
+            .flat_map(|uses| udc.choose(uses, &mut rng))
+            .collect::<Vec<_>>();
+        assert_eq!(turns, vec!["a", "b", "c", "a"]);
+
+        udc.mode = Mode::Sequence;
+        assert_eq!(udc.choose(1, &mut rng), vec!["a", "b", "c"]);
+
+        udc.mode = Mode::Random;
+        for uses in 1..10 {
+            let chosen = udc.choose(uses, &mut rand::thread_rng());
+            assert_eq!(chosen.len(), 1);
+            assert!(["a", "b", "c"].contains(&chosen[0]));
+        }
+    }
+
+    #[test]
+    fn parse_trigger_edit() {
+        assert_eq!(
+            TriggerEdit::parse("Keyboard --cooldown 5 what keyboard => a ${random 10} key one")
+                .unwrap(),
+            TriggerEdit {
+                name: "keyboard".into(),
+                cooldown: Some(5),
+                pattern: Some(Pattern::Keyword("what keyboard".into())),
+                body: Some("a ${random 10} key one".into()),
+            }
+        );
+        assert_eq!(
+            TriggerEdit::parse("keyboard /key(board)?s?/ =>").unwrap(),
+            TriggerEdit {
+                name: "keyboard".into(),
+                pattern: Some(Pattern::Regex("key(board)?s?".into())),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            TriggerEdit::parse("keyboard just a new body").unwrap(),
+            TriggerEdit {
+                name: "keyboard".into(),
+                body: Some("just a new body".into()),
+                ..Default::default()
+            }
+        );
+
+        for input in &[
+            "",
+            "keyboard",
+            "keyboard --role mod hi",
+            "keyboard /(/ => hi",
+        ] {
+            assert!(TriggerEdit::parse(input).is_err(), "{}", input);
+        }
+    }
+}
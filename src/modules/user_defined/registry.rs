@@ -5,20 +5,22 @@ pub(super) struct Registry;
 
 impl Registry {
     pub(super) async fn initialize_table(pool: sqlx::SqlitePool) -> anyhow::Result<()> {
-        // TODO foreign key unique for name fields in both tables
-        sqlx::query_file!("./src/modules/user_defined/sql/create_registry.sql")
-            .execute(&mut &pool)
-            .await?;
+        use sqlx::prelude::*;
+
+        // the builtin commands are reserved again each start
+        let mut conn = pool.acquire().await?;
+        conn.execute(include_str!("sql/reset_builtin.sql")).await?;
         Ok(())
     }
 
     pub(super) async fn all_builtin(pool: sqlx::SqlitePool) -> anyhow::Result<HashSet<String>> {
+        #[derive(sqlx::FromRow)]
         struct Builtin {
             name: String,
         }
 
         // TODO use fetch and allocate into the hashmap instead of vec->map
-        sqlx::query_file_as!(Builtin, "./src/modules/user_defined/sql/all_builtin.sql")
+        sqlx::query_as::<_, Builtin>(include_str!("sql/all_builtin.sql"))
             .fetch_all(&mut &pool)
             .await
             .map(|vec| vec.into_iter().map(|k| k.name).collect())
@@ -27,7 +29,8 @@ impl Registry {
 
     pub(super) async fn reserve(pool: sqlx::SqlitePool, name: &str) -> anyhow::Result<()> {
         anyhow::ensure!(
-            sqlx::query_file!("./src/modules/user_defined/sql/reserve.sql", &name)
+            sqlx::query(include_str!("sql/reserve.sql"))
+                .bind(name)
                 .execute(&mut &pool)
                 .await?
                 == 1,
@@ -42,12 +45,10 @@ impl Registry {
         I: Iterator<Item = S>,
         S: AsRef<str>,
     {
-        use sqlx::prelude::*;
-
-        let mut conn = pool.acquire().await?;
-        let mut tx = conn.begin().await?;
+        let mut tx = pool.begin().await?;
         for name in names {
-            sqlx::query_file!("./src/modules/user_defined/sql/reserve.sql", name.as_ref())
+            sqlx::query(include_str!("sql/reserve.sql"))
+                .bind(name.as_ref())
                 .execute(&mut tx)
                 .await?;
         }
@@ -63,19 +64,21 @@ impl Registry {
             return Ok(AddResult::Builtin);
         }
 
-        if sqlx::query_file!(
-            "./src/modules/user_defined/sql/add_user_command.sql",
-            &udc.name,
-            &udc.body,
-            &udc.room,
-            &udc.uses,
-            &udc.owner,
-            &udc.disabled,
-            &udc.created_at.format("%F %T %z"),
-        )
-        .execute(&mut &pool)
-        .await
-        .is_err()
+        if sqlx::query(include_str!("sql/add_user_command.sql"))
+            .bind(&udc.name)
+            .bind(&udc.body)
+            .bind(udc.room)
+            .bind(udc.uses)
+            .bind(udc.owner)
+            .bind(udc.disabled)
+            .bind(udc.created_at.timestamp())
+            .bind(udc.role.as_str())
+            .bind(udc.cooldown as i64)
+            .bind(udc.variants_json())
+            .bind(udc.mode.as_str())
+            .execute(&mut &pool)
+            .await
+            .is_err()
         {
             // this means the constraint failed
             return Ok(AddResult::Exists);
@@ -84,19 +87,43 @@ impl Registry {
         Ok(AddResult::Okay)
     }
 
+    /// Add all of these commands, or none of them if any fail
+    pub(super) async fn add_many<'a, I>(pool: sqlx::SqlitePool, many: I) -> anyhow::Result<()>
+    where
+        I: Iterator<Item = &'a UserDefinedCommand> + 'a,
+    {
+        let mut tx = pool.begin().await?;
+        for udc in many {
+            sqlx::query(include_str!("sql/add_user_command.sql"))
+                .bind(&udc.name)
+                .bind(&udc.body)
+                .bind(udc.room)
+                .bind(udc.uses)
+                .bind(udc.owner)
+                .bind(udc.disabled)
+                .bind(udc.created_at.timestamp())
+                .bind(udc.role.as_str())
+                .bind(udc.cooldown as i64)
+                .bind(udc.variants_json())
+                .bind(udc.mode.as_str())
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
     pub(super) async fn remove(
         pool: sqlx::SqlitePool,
         udc: &UserDefinedCommand,
     ) -> anyhow::Result<RemoveResult> {
         let UserDefinedCommand { name, room, .. } = udc;
 
-        let res = match sqlx::query_file!(
-            "./src/modules/user_defined/sql/remove.sql",
-            name,
-            (*room as i64)
-        )
-        .execute(&mut &pool)
-        .await?
+        let res = match sqlx::query(include_str!("sql/remove.sql"))
+            .bind(name)
+            .bind(*room)
+            .execute(&mut &pool)
+            .await?
         {
             0 => RemoveResult::Missing,
             _ => RemoveResult::Okay,
@@ -109,16 +136,17 @@ impl Registry {
         pool: sqlx::SqlitePool,
         udc: &UserDefinedCommand,
     ) -> anyhow::Result<bool> {
-        let n = sqlx::query_file!(
-            "./src/modules/user_defined/sql/update.sql",
-            &udc.name,
-            &udc.body,
-            &udc.uses,
-            &udc.disabled,
-            &udc.name,
-        )
-        .execute(&mut &pool)
-        .await?;
+        let n = sqlx::query(include_str!("sql/update.sql"))
+            .bind(&udc.body)
+            .bind(udc.disabled)
+            .bind(udc.role.as_str())
+            .bind(udc.cooldown as i64)
+            .bind(udc.variants_json())
+            .bind(udc.mode.as_str())
+            .bind(&udc.name)
+            .bind(udc.room)
+            .execute(&mut &pool)
+            .await?;
         Ok(n == 1)
     }
 
@@ -126,41 +154,177 @@ impl Registry {
     where
         I: Iterator<Item = &'a UserDefinedCommand> + 'a,
     {
-        use sqlx::prelude::*;
+        let mut tx = pool.begin().await?;
+        for udc in many {
+            sqlx::query(include_str!("sql/update.sql"))
+                .bind(&udc.body)
+                .bind(udc.disabled)
+                .bind(udc.role.as_str())
+                .bind(udc.cooldown as i64)
+                .bind(udc.variants_json())
+                .bind(udc.mode.as_str())
+                .bind(&udc.name)
+                .bind(udc.room)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
 
-        let mut conn = pool.acquire().await?;
-        let mut tx = conn.begin().await?;
+    pub(super) async fn rename(
+        pool: sqlx::SqlitePool,
+        udc: &UserDefinedCommand,
+        name: &str,
+    ) -> anyhow::Result<AddResult> {
+        if Self::all_builtin(pool.clone()).await?.contains(name) {
+            return Ok(AddResult::Builtin);
+        }
 
-        for udc in many {
-            sqlx::query_file!(
-                "./src/modules/user_defined/sql/update.sql",
-                &udc.name,
-                &udc.body,
-                &udc.uses,
-                &udc.disabled,
-                &udc.name,
-            )
-            .execute(&mut tx)
-            .await?;
+        match sqlx::query(include_str!("sql/rename.sql"))
+            .bind(name)
+            .bind(&udc.name)
+            .bind(udc.room)
+            .execute(&mut &pool)
+            .await
+        {
+            Ok(1) => Ok(AddResult::Okay),
+            Ok(..) => anyhow::bail!("command '{}' wasn't found", udc.name),
+            // this means the constraint failed
+            Err(..) => Ok(AddResult::Exists),
+        }
+    }
+
+    /// Add to the uses of these commands, by their ids
+    pub(super) async fn add_uses<I>(pool: sqlx::SqlitePool, uses: I) -> anyhow::Result<()>
+    where
+        I: Iterator<Item = (i64, i32)>,
+    {
+        let mut tx = pool.begin().await?;
+        for (id, n) in uses {
+            sqlx::query(include_str!("sql/add_uses.sql"))
+                .bind(n)
+                .bind(id)
+                .execute(&mut tx)
+                .await?;
         }
         tx.commit().await?;
         Ok(())
     }
 
+    /// Every room that every command is shared with, as `(command id, room)`
+    pub(super) async fn all_shares(pool: sqlx::SqlitePool) -> anyhow::Result<Vec<(i64, u64)>> {
+        use sqlx::prelude::*;
+
+        Ok(sqlx::query(include_str!("sql/all_shares.sql"))
+            .fetch_all(&mut &pool)
+            .await?
+            .into_iter()
+            .map(|row| (row.get::<i64, _>(0), row.get::<i64, _>(1) as u64))
+            .collect())
+    }
+
+    /// Find the command that `name` refers to in this room
+    ///
+    /// The room's own command is used first, then one shared with the room,
+    /// then a global one.
     pub(super) async fn lookup(
         pool: sqlx::SqlitePool,
         name: &str,
         room: u64,
     ) -> anyhow::Result<Option<UserDefinedCommand>> {
-        Ok(sqlx::query_file_as!(
-            UserDefinedCommandRow,
-            "./src/modules/user_defined/sql/lookup.sql",
-            name,
-            (room as i64),
+        Ok(
+            sqlx::query_as::<_, UserDefinedCommandRow>(include_str!("sql/lookup.sql"))
+                .bind(room as i64)
+                .bind(name)
+                .bind(room as i64)
+                .bind(room as i64)
+                .fetch_optional(&mut &pool)
+                .await?
+                .map(Into::into),
         )
-        .fetch_optional(&mut &pool)
-        .await?
-        .map(Into::into))
+    }
+
+    /// Find the command that belongs to this room, ignoring shared and global ones
+    pub(super) async fn lookup_local(
+        pool: sqlx::SqlitePool,
+        name: &str,
+        room: u64,
+    ) -> anyhow::Result<Option<UserDefinedCommand>> {
+        Ok(
+            sqlx::query_as::<_, UserDefinedCommandRow>(include_str!("sql/lookup_local.sql"))
+                .bind(name)
+                .bind(room as i64)
+                .fetch_optional(&mut &pool)
+                .await?
+                .map(Into::into),
+        )
+    }
+
+    pub(super) async fn set_global(
+        pool: sqlx::SqlitePool,
+        udc: &UserDefinedCommand,
+        global: bool,
+    ) -> anyhow::Result<bool> {
+        let n = sqlx::query(include_str!("sql/set_global.sql"))
+            .bind(global)
+            .bind(&udc.name)
+            .bind(udc.room)
+            .execute(&mut &pool)
+            .await?;
+        Ok(n == 1)
+    }
+
+    /// Share the command with these rooms
+    pub(super) async fn share(
+        pool: sqlx::SqlitePool,
+        udc: &UserDefinedCommand,
+        rooms: &[u64],
+    ) -> anyhow::Result<()> {
+        let mut tx = pool.begin().await?;
+        for &room in rooms {
+            sqlx::query(include_str!("sql/share.sql"))
+                .bind(udc.id)
+                .bind(room as i64)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Stop sharing the command with these rooms
+    pub(super) async fn unshare(
+        pool: sqlx::SqlitePool,
+        udc: &UserDefinedCommand,
+        rooms: &[u64],
+    ) -> anyhow::Result<()> {
+        let mut tx = pool.begin().await?;
+        for &room in rooms {
+            sqlx::query(include_str!("sql/unshare.sql"))
+                .bind(udc.id)
+                .bind(room as i64)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// The rooms the command is shared with
+    pub(super) async fn shares(
+        pool: sqlx::SqlitePool,
+        udc: &UserDefinedCommand,
+    ) -> anyhow::Result<Vec<u64>> {
+        use sqlx::prelude::*;
+
+        Ok(sqlx::query(include_str!("sql/shares.sql"))
+            .bind(udc.id)
+            .fetch_all(&mut &pool)
+            .await?
+            .into_iter()
+            .map(|row| row.get::<i64, _>(0) as u64)
+            .collect())
     }
 
     pub(super) async fn all_commands_for(
@@ -168,31 +332,29 @@ impl Registry {
         room: u64,
     ) -> anyhow::Result<Vec<UserDefinedCommand>> {
         // to fetch and allocate into the vec instead of vec->vec
-        Ok(sqlx::query_file_as!(
-            UserDefinedCommandRow,
-            "./src/modules/user_defined/sql/all_commands_for.sql",
-            (room as i64)
+        Ok(
+            sqlx::query_as::<_, UserDefinedCommandRow>(include_str!("sql/all_commands_for.sql"))
+                .bind(room as i64)
+                .fetch_all(&mut &pool)
+                .await?
+                .into_iter()
+                .map(Into::into)
+                .collect(),
         )
-        .fetch_all(&mut &pool)
-        .await?
-        .into_iter()
-        .map(Into::into)
-        .collect())
     }
 
     pub(super) async fn all_commands(
         pool: sqlx::SqlitePool,
     ) -> anyhow::Result<Vec<UserDefinedCommand>> {
         // to fetch and allocate into the vec instead of vec->vec
-        Ok(sqlx::query_file_as!(
-            UserDefinedCommandRow,
-            "./src/modules/user_defined/sql/all_commands.sql"
+        Ok(
+            sqlx::query_as::<_, UserDefinedCommandRow>(include_str!("sql/all_commands.sql"))
+                .fetch_all(&mut &pool)
+                .await?
+                .into_iter()
+                .map(Into::into)
+                .collect(),
         )
-        .fetch_all(&mut &pool)
-        .await?
-        .into_iter()
-        .map(Into::into)
-        .collect())
     }
 }
 
@@ -209,31 +371,44 @@ pub enum RemoveResult {
     Okay,
 }
 
-#[derive(Debug)]
+#[derive(Debug, sqlx::FromRow)]
 struct UserDefinedCommandRow {
+    id: i64,
     name: String,
     body: String,
-    room: i32,
+    room: i64,
     uses: i32,
-    owner: String,
+    owner: i64,
     disabled: bool,
-    created_at: Vec<u8>,
+    created_at: i64,
+    role: String,
+    cooldown: i64,
+    global: bool,
+    variants: String,
+    mode: String,
 }
 
 impl From<UserDefinedCommandRow> for UserDefinedCommand {
     fn from(udc: UserDefinedCommandRow) -> Self {
+        let variants = serde_json::from_str(&udc.variants).unwrap_or_else(|err| {
+            log::warn!("invalid variants for '{}': {}", udc.name, err);
+            vec![]
+        });
+
         UserDefinedCommand {
+            id: udc.id,
             disabled: udc.disabled,
-            created_at: time::OffsetDateTime::parse(
-                std::str::from_utf8(&udc.created_at).unwrap(),
-                "%F %T %z",
-            )
-            .unwrap(),
+            created_at: time::OffsetDateTime::from_unix_timestamp(udc.created_at),
             name: udc.name,
             body: udc.body,
-            room: udc.room as _, // this cast should be different
+            room: udc.room,
             uses: udc.uses,
-            owner: udc.owner.parse().unwrap(),
+            owner: udc.owner,
+            role: udc.role.parse().unwrap_or_default(),
+            cooldown: udc.cooldown as _,
+            global: udc.global,
+            variants,
+            mode: udc.mode.parse().unwrap_or_default(),
         }
     }
 }
@@ -243,22 +418,14 @@ mod tests {
     use super::*;
 
     async fn get_db() -> sqlx::SqlitePool {
-        use rand::prelude::*;
-        // env = sqlite://sqlx_reference_database.db
-        //
-        //?mode=memory&cache=shared
-        let db_name = thread_rng()
-            .sample_iter(&rand::distributions::Alphanumeric)
-            .take(7)
-            .collect::<String>();
-        let db_name = format!("file:{}?mode=memory&cache=shared", db_name);
-        let pool = sqlx::SqlitePool::new(&db_name).await.unwrap();
+        let pool = crate::database::in_memory().await.unwrap();
         Registry::initialize_table(pool.clone()).await.unwrap();
         pool
     }
 
     fn make_udc(name: &str) -> UserDefinedCommand {
         UserDefinedCommand {
+            id: 0,
             name: name.into(),
             body: "test body".into(),
             room: 1234,
@@ -266,6 +433,11 @@ mod tests {
             owner: 1234,
             disabled: false,
             created_at: time::OffsetDateTime::now_local(),
+            role: Default::default(),
+            cooldown: 0,
+            global: false,
+            variants: vec![],
+            mode: Default::default(),
         }
     }
 
@@ -425,6 +597,44 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn rename() {
+        let pool = get_db().await;
+        Registry::reserve(pool.clone(), "taken").await.unwrap();
+
+        let udc = make_udc("testing");
+        Registry::add_user_command(pool.clone(), &udc)
+            .await
+            .unwrap();
+        Registry::add_user_command(pool.clone(), &make_udc("other"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Registry::rename(pool.clone(), &udc, "taken").await.unwrap(),
+            AddResult::Builtin
+        );
+        assert_eq!(
+            Registry::rename(pool.clone(), &udc, "other").await.unwrap(),
+            AddResult::Exists
+        );
+        assert_eq!(
+            Registry::rename(pool.clone(), &udc, "renamed")
+                .await
+                .unwrap(),
+            AddResult::Okay
+        );
+
+        assert!(Registry::lookup(pool.clone(), "testing", 1234)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(Registry::lookup(pool.clone(), "renamed", 1234)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
     #[tokio::test]
     async fn lookup() {
         let pool = get_db().await;
@@ -477,6 +687,69 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn lookup_shared() {
+        let pool = get_db().await;
+
+        let udc = make_udc("testing");
+        Registry::add_user_command(pool.clone(), &udc)
+            .await
+            .unwrap();
+        let udc = Registry::lookup_local(pool.clone(), "testing", 1234)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let lookup = |room| {
+            let pool = pool.clone();
+            async move {
+                Registry::lookup(pool, "testing", room)
+                    .await
+                    .unwrap()
+                    .map(|udc| (udc.room, udc.body))
+            }
+        };
+
+        assert_eq!(lookup(42).await, None);
+
+        Registry::share(pool.clone(), &udc, &[42, 43])
+            .await
+            .unwrap();
+        assert_eq!(lookup(42).await, Some((1234, "test body".into())));
+        assert_eq!(
+            Registry::shares(pool.clone(), &udc).await.unwrap(),
+            vec![42, 43]
+        );
+
+        Registry::unshare(pool.clone(), &udc, &[42]).await.unwrap();
+        assert_eq!(lookup(42).await, None);
+        assert_eq!(lookup(43).await, Some((1234, "test body".into())));
+
+        assert!(Registry::set_global(pool.clone(), &udc, true)
+            .await
+            .unwrap());
+        assert_eq!(lookup(42).await, Some((1234, "test body".into())));
+
+        // the room's own command is used over a global one
+        let local = UserDefinedCommand {
+            room: 42,
+            body: "local".into(),
+            ..make_udc("testing")
+        };
+        Registry::add_user_command(pool.clone(), &local)
+            .await
+            .unwrap();
+        assert_eq!(lookup(42).await, Some((42, "local".into())));
+        assert_eq!(lookup(1234).await, Some((1234, "test body".into())));
+
+        // shares are removed with the command
+        Registry::remove(pool.clone(), &udc).await.unwrap();
+        assert!(Registry::shares(pool.clone(), &udc)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
     #[tokio::test]
     async fn all_commands() {
         let pool = get_db().await;
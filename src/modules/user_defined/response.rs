@@ -10,14 +10,44 @@ pub enum Response<'a> {
 
     ErrorMissingHead,
     ErrorMissingTail { head: &'a str },
+    ErrorInvalidBody { error: &'a str },
+    ErrorInvalidFlag { error: &'a str },
+    ErrorNoHistory { command: &'a str },
+    ErrorNoRevision { command: &'a str, n: u64 },
+    ErrorCannotRevert { command: &'a str, n: u64 },
+    ErrorUnknownRooms { rooms: &'a str },
+    ErrorInvalidTrigger { error: &'a str },
+    ErrorTriggerExists { name: &'a str },
+    ErrorTriggerNotFound { name: &'a str },
 
     Added { command: &'a str },
     Edited { command: &'a str },
     Renamed { from: &'a str, to: &'a str },
+    VariantAdded { command: &'a str, n: u64 },
+    VariantDeleted { command: &'a str, n: u64 },
     Deleted,
+    Reverted { command: &'a str, n: u64 },
+    Shared { command: &'a str, count: u64 },
+    Unshared { command: &'a str, count: u64 },
+    Global { command: &'a str },
+    NotGlobal { command: &'a str },
+
+    TriggerAdded { name: &'a str, pattern: &'a str },
+    TriggerEdited { name: &'a str },
+    TriggerDeleted { name: &'a str },
 
     Say { data: &'a str },
 
     CommandDescription { command: &'a str, body: &'a str },
+    CommandMode { mode: &'a str, count: u64 },
+    CommandVariant { n: u64, body: &'a str },
     CommandCreatedAt { owner: &'a str, uses: u64 },
+    CommandRestrictions { role: &'a str, cooldown: u64 },
+    CommandShared { room: &'a str },
+    CommandGlobal { room: &'a str },
+
+    HistoryAdded { n: u64, by: &'a str, ago: &'a str },
+    HistoryEdited { n: u64, by: &'a str, ago: &'a str },
+    HistoryRenamed { n: u64, by: &'a str, ago: &'a str },
+    HistoryDeleted { n: u64, by: &'a str, ago: &'a str },
 }
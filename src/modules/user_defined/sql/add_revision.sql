@@ -0,0 +1,7 @@
+INSERT INTO command_history (
+    room, name, action, actor, at,
+    previous_name, previous_body, previous_uses, previous_owner,
+    previous_disabled, previous_created_at, previous_role, previous_cooldown,
+    previous_variants, previous_mode
+)
+VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
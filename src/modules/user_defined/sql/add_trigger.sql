@@ -0,0 +1,4 @@
+INSERT INTO 
+	user_triggers (name, kind, pattern, body, room, owner, cooldown, disabled, created_at)
+VALUES 
+	(?, ?, ?, ?, ?, ?, ?, ?, ?)
@@ -5,5 +5,9 @@ INSERT INTO user_commands (
     uses,
     owner,
     disabled,
-    created_at
-) VALUES (?, ?, ?, ?, ?, ?, ?)
+    created_at,
+    role,
+    cooldown,
+    variants,
+    mode
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
\ No newline at end of file
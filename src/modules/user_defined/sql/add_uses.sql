@@ -0,0 +1,6 @@
+UPDATE 
+	user_commands 
+SET
+	uses = uses + ?
+WHERE 
+	id = ?
@@ -1,10 +1,16 @@
 SELECT 
+	id,
 	name,
 	body,
 	room,
 	uses,
 	owner,
 	disabled,
-	created_at
+	created_at,
+	role,
+	cooldown,
+	global,
+	variants,
+	mode
 FROM 
 	user_commands
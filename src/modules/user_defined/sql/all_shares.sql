@@ -0,0 +1,5 @@
+SELECT 
+	command_id,
+	room
+FROM 
+	command_shares
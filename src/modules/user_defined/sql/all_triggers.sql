@@ -0,0 +1,15 @@
+SELECT 
+	id,
+	name,
+	kind,
+	pattern,
+	body,
+	room,
+	owner,
+	cooldown,
+	disabled,
+	created_at
+FROM 
+	user_triggers
+ORDER BY
+	id
@@ -1,14 +1,34 @@
 SELECT 
+	user_commands.id AS id,
 	name,
 	body,
-	room,
+	user_commands.room AS room,
 	uses,
 	owner,
 	disabled,
-	created_at
+	created_at,
+	role,
+	cooldown,
+	global,
+	variants,
+	mode
 FROM 
 	user_commands 
+LEFT JOIN 
+	command_shares 
+ON 
+	command_shares.command_id = user_commands.id
+AND
+	command_shares.room = ?
 WHERE 
 	name = ?
 AND
-	room = ?
+	(user_commands.room = ? OR command_shares.room IS NOT NULL OR global)
+-- the room's own command, then one shared with it, then a global one
+ORDER BY
+	CASE
+		WHEN user_commands.room = ? THEN 0
+		WHEN command_shares.room IS NOT NULL THEN 1
+		ELSE 2
+	END
+LIMIT 1
@@ -0,0 +1,20 @@
+SELECT 
+	id,
+	name,
+	body,
+	room,
+	uses,
+	owner,
+	disabled,
+	created_at,
+	role,
+	cooldown,
+	global,
+	variants,
+	mode
+FROM 
+	user_commands 
+WHERE 
+	name = ?
+AND
+	room = ?
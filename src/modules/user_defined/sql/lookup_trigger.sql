@@ -0,0 +1,17 @@
+SELECT 
+	id,
+	name,
+	kind,
+	pattern,
+	body,
+	room,
+	owner,
+	cooldown,
+	disabled,
+	created_at
+FROM 
+	user_triggers
+WHERE 
+	name = ?
+AND
+	room = ?
@@ -0,0 +1,6 @@
+DELETE FROM 
+    user_triggers 
+WHERE 
+    name = ? 
+AND 
+    room = ?
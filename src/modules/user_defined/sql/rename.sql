@@ -0,0 +1,8 @@
+UPDATE 
+	user_commands 
+SET
+	name = ?
+WHERE 
+	name = ?
+AND
+	room = ?
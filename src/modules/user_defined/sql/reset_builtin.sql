@@ -0,0 +1,2 @@
+-- the table is created by the migrations
+DELETE FROM builtin_commands
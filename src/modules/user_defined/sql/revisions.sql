@@ -0,0 +1,4 @@
+SELECT * FROM command_history
+WHERE (? IS NULL OR room = ?) AND (? IS NULL OR name = ?)
+ORDER BY id DESC
+LIMIT ?
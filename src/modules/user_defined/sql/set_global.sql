@@ -0,0 +1,8 @@
+UPDATE 
+	user_commands 
+SET
+	global = ?
+WHERE 
+	name = ?
+AND
+	room = ?
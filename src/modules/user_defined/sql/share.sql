@@ -0,0 +1,4 @@
+INSERT OR IGNORE INTO 
+	command_shares (command_id, room)
+VALUES 
+	(?, ?)
@@ -0,0 +1,8 @@
+SELECT 
+	room
+FROM 
+	command_shares 
+WHERE 
+	command_id = ?
+ORDER BY
+	room
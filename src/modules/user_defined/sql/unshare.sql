@@ -0,0 +1,6 @@
+DELETE FROM 
+	command_shares 
+WHERE 
+	command_id = ?
+AND
+	room = ?
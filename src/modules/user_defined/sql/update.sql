@@ -1,9 +1,13 @@
 UPDATE 
 	user_commands 
 SET
-	name = ?,
 	body = ?,
-	uses = ?,
-	disabled = ?
+	disabled = ?,
+	role = ?,
+	cooldown = ?,
+	variants = ?,
+	mode = ?
 WHERE 
 	name = ?
+AND
+	room = ?
@@ -0,0 +1,12 @@
+UPDATE 
+	user_triggers 
+SET
+	kind = ?,
+	pattern = ?,
+	body = ?,
+	cooldown = ?,
+	disabled = ?
+WHERE 
+	name = ?
+AND
+	room = ?
@@ -0,0 +1,344 @@
+use regex::{RegexBuilder, RegexSet, RegexSetBuilder};
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::sync::{Arc, Mutex};
+
+/// Triggers are matched against every message, so keep their patterns small
+const SIZE_LIMIT: usize = 1 << 16;
+
+/// A phrase that the bot responds to in a room
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Trigger {
+    /// Assigned by the database, `0` until the trigger has been added
+    pub id: i64,
+    pub name: String,
+    pub pattern: Pattern,
+    pub body: String,
+    pub room: i64,
+    pub owner: i64,
+    /// Seconds between responses, per room
+    pub cooldown: u64,
+    pub disabled: bool,
+    pub created_at: i64,
+}
+
+/// What a trigger matches
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Pattern {
+    /// These words, anywhere in the message
+    Keyword(String),
+    /// A regular expression, written as `/pattern/`
+    Regex(String),
+}
+
+impl Pattern {
+    /// Parse a pattern, `/like this/` for a regex or `like this` for a keyword
+    pub(super) fn parse(input: &str) -> anyhow::Result<Self> {
+        let input = input.trim();
+        let pattern = if input.len() > 2 && input.starts_with('/') && input.ends_with('/') {
+            Self::Regex(input[1..input.len() - 1].to_string())
+        } else {
+            let words = input.split_whitespace().collect::<Vec<_>>();
+            anyhow::ensure!(!words.is_empty(), "the pattern cannot be empty");
+            Self::Keyword(words.join(" ").to_lowercase())
+        };
+
+        // make sure it'll compile
+        RegexBuilder::new(&pattern.to_regex())
+            .size_limit(SIZE_LIMIT)
+            .build()?;
+        Ok(pattern)
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Keyword(..) => "keyword",
+            Self::Regex(..) => "regex",
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Keyword(s) | Self::Regex(s) => s,
+        }
+    }
+
+    /// Matching is case-insensitive, a regex can turn that off with `(?-i)`
+    fn to_regex(&self) -> String {
+        match self {
+            Self::Keyword(words) => crate::Pattern::keyword_pattern(words),
+            Self::Regex(regex) => format!("(?i){}", regex),
+        }
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Keyword(words) => f.write_str(words),
+            Self::Regex(regex) => write!(f, "/{}/", regex),
+        }
+    }
+}
+
+pub(super) struct Triggers;
+
+impl Triggers {
+    /// Returns `false` if the room already has a trigger with this name
+    pub(super) async fn add(pool: sqlx::SqlitePool, trigger: &Trigger) -> anyhow::Result<bool> {
+        let res = sqlx::query(include_str!("sql/add_trigger.sql"))
+            .bind(&trigger.name)
+            .bind(trigger.pattern.kind())
+            .bind(trigger.pattern.as_str())
+            .bind(&trigger.body)
+            .bind(trigger.room)
+            .bind(trigger.owner)
+            .bind(trigger.cooldown as i64)
+            .bind(trigger.disabled)
+            .bind(trigger.created_at)
+            .execute(&mut &pool)
+            .await;
+        // an error means the constraint failed
+        Ok(res.is_ok())
+    }
+
+    pub(super) async fn update(pool: sqlx::SqlitePool, trigger: &Trigger) -> anyhow::Result<bool> {
+        let n = sqlx::query(include_str!("sql/update_trigger.sql"))
+            .bind(trigger.pattern.kind())
+            .bind(trigger.pattern.as_str())
+            .bind(&trigger.body)
+            .bind(trigger.cooldown as i64)
+            .bind(trigger.disabled)
+            .bind(&trigger.name)
+            .bind(trigger.room)
+            .execute(&mut &pool)
+            .await?;
+        Ok(n == 1)
+    }
+
+    pub(super) async fn remove(pool: sqlx::SqlitePool, trigger: &Trigger) -> anyhow::Result<bool> {
+        let n = sqlx::query(include_str!("sql/remove_trigger.sql"))
+            .bind(&trigger.name)
+            .bind(trigger.room)
+            .execute(&mut &pool)
+            .await?;
+        Ok(n == 1)
+    }
+
+    pub(super) async fn lookup(
+        pool: sqlx::SqlitePool,
+        name: &str,
+        room: u64,
+    ) -> anyhow::Result<Option<Trigger>> {
+        sqlx::query_as::<_, TriggerRow>(include_str!("sql/lookup_trigger.sql"))
+            .bind(name)
+            .bind(room as i64)
+            .fetch_optional(&mut &pool)
+            .await?
+            .map(TryInto::try_into)
+            .transpose()
+    }
+
+    pub(super) async fn all(pool: sqlx::SqlitePool) -> anyhow::Result<Vec<Trigger>> {
+        sqlx::query_as::<_, TriggerRow>(include_str!("sql/all_triggers.sql"))
+            .fetch_all(&mut &pool)
+            .await?
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect()
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct TriggerRow {
+    id: i64,
+    name: String,
+    kind: String,
+    pattern: String,
+    body: String,
+    room: i64,
+    owner: i64,
+    cooldown: i64,
+    disabled: bool,
+    created_at: i64,
+}
+
+impl TryFrom<TriggerRow> for Trigger {
+    type Error = anyhow::Error;
+    fn try_from(row: TriggerRow) -> Result<Self, Self::Error> {
+        let pattern = match row.kind.as_str() {
+            "keyword" => Pattern::Keyword(row.pattern),
+            "regex" => Pattern::Regex(row.pattern),
+            kind => anyhow::bail!("unknown kind of trigger: '{}'", kind),
+        };
+
+        Ok(Self {
+            id: row.id,
+            name: row.name,
+            pattern,
+            body: row.body,
+            room: row.room,
+            owner: row.owner,
+            cooldown: row.cooldown as _,
+            disabled: row.disabled,
+            created_at: row.created_at,
+        })
+    }
+}
+
+/// The enabled triggers of each room, compiled into one set per room
+///
+/// This has to be refreshed after the triggers are changed in the database.
+#[derive(Default, Clone)]
+pub(super) struct TriggerCache {
+    rooms: Arc<Mutex<HashMap<i64, Arc<RoomTriggers>>>>,
+}
+
+struct RoomTriggers {
+    set: RegexSet,
+    triggers: Vec<Trigger>,
+}
+
+impl TriggerCache {
+    pub(super) async fn load(pool: sqlx::SqlitePool) -> anyhow::Result<Self> {
+        let this = Self::default();
+        this.refresh(pool).await?;
+        Ok(this)
+    }
+
+    /// Reload the triggers from the database
+    pub(super) async fn refresh(&self, pool: sqlx::SqlitePool) -> anyhow::Result<()> {
+        let mut triggers = HashMap::<_, Vec<_>>::new();
+        for trigger in Triggers::all(pool).await? {
+            if !trigger.disabled {
+                triggers.entry(trigger.room).or_default().push(trigger);
+            }
+        }
+
+        let mut rooms = HashMap::new();
+        for (room, triggers) in triggers {
+            let set = RegexSetBuilder::new(triggers.iter().map(|t| t.pattern.to_regex()))
+                .size_limit(SIZE_LIMIT * triggers.len())
+                .build()?;
+            rooms.insert(room, Arc::new(RoomTriggers { set, triggers }));
+        }
+
+        *self.rooms.lock().unwrap() = rooms;
+        Ok(())
+    }
+
+    /// The triggers in this room that match the message, oldest first
+    pub(super) fn matches(&self, room: u64, message: &str) -> Vec<Trigger> {
+        let room = match self.rooms.lock().unwrap().get(&(room as i64)) {
+            Some(room) => Arc::clone(room),
+            None => return vec![],
+        };
+
+        room.set
+            .matches(message)
+            .into_iter()
+            .map(|index| room.triggers[index].clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &Pattern, message: &str) -> bool {
+        regex::Regex::new(&pattern.to_regex())
+            .unwrap()
+            .is_match(message)
+    }
+
+    fn make_trigger(name: &str, pattern: &str, room: i64) -> Trigger {
+        Trigger {
+            id: 0,
+            name: name.into(),
+            pattern: Pattern::parse(pattern).unwrap(),
+            body: format!("{} in {}", name, room),
+            room,
+            owner: 1234,
+            cooldown: 30,
+            disabled: false,
+            created_at: 1_585_744_496,
+        }
+    }
+
+    #[test]
+    fn pattern() {
+        assert_eq!(
+            Pattern::parse("  What   Keyboard ").unwrap(),
+            Pattern::Keyword("what keyboard".into())
+        );
+        assert_eq!(
+            Pattern::parse("/key(board)?s?/").unwrap(),
+            Pattern::Regex("key(board)?s?".into())
+        );
+        assert!(Pattern::parse("").is_err());
+        assert!(Pattern::parse("/(/").is_err());
+
+        let keyword = Pattern::parse("what keyboard").unwrap();
+        assert!(is_match(&keyword, "hey, WHAT keyboard is that?"));
+        assert!(is_match(&keyword, "what\tkeyboard"));
+        assert!(!is_match(&keyword, "somewhat keyboards"));
+
+        // keywords are escaped
+        let keyword = Pattern::parse("c++").unwrap();
+        assert!(is_match(&keyword, "do you use c++"));
+        assert!(!is_match(&keyword, "do you use c"));
+    }
+
+    #[tokio::test]
+    async fn cache() {
+        let pool = crate::database::in_memory().await.unwrap();
+        for trigger in &[
+            make_trigger("keyboard", "what keyboard", 1),
+            make_trigger("mouse", "/what mouse( is that)?/", 1),
+            make_trigger("keyboard", "keyboard", 2),
+        ] {
+            assert!(Triggers::add(pool.clone(), trigger).await.unwrap());
+        }
+        assert!(
+            !Triggers::add(pool.clone(), &make_trigger("mouse", "mouse", 1))
+                .await
+                .unwrap()
+        );
+
+        let cache = TriggerCache::load(pool.clone()).await.unwrap();
+        let names = |room, msg| {
+            cache
+                .matches(room, msg)
+                .into_iter()
+                .map(|t| t.body)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(1, "what keyboard is that"), vec!["keyboard in 1"]);
+        assert_eq!(names(2, "what keyboard is that"), vec!["keyboard in 2"]);
+        assert_eq!(
+            names(1, "what keyboard and what mouse is that"),
+            vec!["keyboard in 1", "mouse in 1"]
+        );
+        assert!(names(1, "hello").is_empty());
+        assert!(names(3, "what keyboard").is_empty());
+
+        let mut trigger = Triggers::lookup(pool.clone(), "keyboard", 1)
+            .await
+            .unwrap()
+            .unwrap();
+        trigger.disabled = true;
+        assert!(Triggers::update(pool.clone(), &trigger).await.unwrap());
+
+        let trigger = Triggers::lookup(pool.clone(), "keyboard", 2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(Triggers::remove(pool.clone(), &trigger).await.unwrap());
+
+        cache.refresh(pool).await.unwrap();
+        assert!(names(1, "what keyboard is that").is_empty());
+        assert!(names(2, "what keyboard is that").is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+use std::time::Duration;
+use {super::*, crate::*};
+
+#[derive(Debug, Template)]
+#[namespace("watch_time")]
+enum Response<'a> {
+    WatchTime {
+        name: &'a str,
+        time: String,
+        since: String,
+    },
+    NotWatched {
+        name: &'a str,
+    },
+    Top {
+        watchers: String,
+    },
+    NoWatchers,
+}
+
+/// How often the chatters of a live room are sampled
+///
+/// Everyone in the room gets this much watch time each time
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How many users are on the leaderboard
+const TOP_WATCHERS: i64 = 5;
+
+pub async fn initialize<R>(init: &mut ModuleInit<'_, R>)
+where
+    R: Responder + Send + 'static,
+{
+    init.command_map.add("watchtime", watch_time);
+    init.command_map.add("topwatchers", top_watchers);
+    init.timer_list
+        .add(Schedule::every(SAMPLE_INTERVAL), sample);
+}
+
+async fn watch_time<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let (watch_time, tracker) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<WatchTime>()?.clone(),
+            state.expect_get::<Tracker>()?.clone(),
+        )
+    };
+
+    let user = context.user();
+    let (id, name) = match context.args.tail.first() {
+        Some(login) => match tracker.get_by_login(login) {
+            Some(identity) => (identity.id as u64, identity.display_name),
+            None => {
+                let name = login.trim_start_matches('@');
+                return responder
+                    .reply(&context, &Response::NotWatched { name })
+                    .await;
+            }
+        },
+        None => (user.id, user.name.to_string()),
+    };
+
+    let presence = match watch_time.get(context.room().id, id).await? {
+        Some(presence) => presence,
+        None => {
+            return responder
+                .reply(&context, &Response::NotWatched { name: &name })
+                .await
+        }
+    };
+
+    let since = time::OffsetDateTime::from_unix_timestamp(presence.first_seen);
+    let resp = Response::WatchTime {
+        name: &name,
+        time: Duration::from_secs(presence.seconds as u64).as_readable_time(),
+        since: since.format("%F"),
+    };
+    responder.reply(&context, &resp).await
+}
+
+async fn top_watchers<R>(context: Context<Command>, mut responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let (watch_time, tracker) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<WatchTime>()?.clone(),
+            state.expect_get::<Tracker>()?.clone(),
+        )
+    };
+
+    let top = watch_time.top(context.room().id, TOP_WATCHERS).await?;
+    if top.is_empty() {
+        return responder.say(&context, &Response::NoWatchers).await;
+    }
+
+    let watchers = top
+        .iter()
+        .enumerate()
+        .map(|(n, presence)| {
+            let name = tracker
+                .user_name(presence.user_id as u64)
+                .unwrap_or_else(|| presence.user_id.to_string());
+            let time = Duration::from_secs(presence.seconds as u64).as_readable_time();
+            format!("{}. {} ({})", n + 1, name, time)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    responder.say(&context, &Response::Top { watchers }).await
+}
+
+async fn sample<R>(context: Context<Timer>, _responder: R) -> Result
+where
+    R: Responder + Send + 'static,
+{
+    let (watch_time, tracker, opt_outs, client) = {
+        let state = context.state().await;
+        (
+            state.expect_get::<WatchTime>()?.clone(),
+            state.expect_get::<Tracker>()?.clone(),
+            state.expect_get::<OptOuts>()?.clone(),
+            state.expect_get::<TwitchClient>()?.clone(),
+        )
+    };
+
+    // only live streams count, otherwise lurkers would pile up time
+    let room = context.room();
+    let live = client
+        .get_streams_from_id(&[room.id])
+        .await?
+        .iter()
+        .any(|stream| stream.user_id == room.id);
+    if !live {
+        return Ok(());
+    }
+
+    let chatters = client.get_users_for(room.remove_hashes()).await?;
+    let logins = chatters
+        .vips
+        .iter()
+        .chain(&chatters.moderators)
+        .chain(&chatters.staff)
+        .chain(&chatters.admins)
+        .chain(&chatters.global_mods)
+        .chain(&chatters.viewers)
+        .filter(|login| !login.eq_ignore_ascii_case(&context.config.user_name))
+        .map(|login| login.as_str())
+        .collect::<Vec<_>>();
+
+    let users = resolve_logins(&tracker, &opt_outs, &client, &logins)
+        .await?
+        .into_iter()
+        .filter(|&id| id != room.id && !opt_outs.contains(id))
+        .collect::<Vec<_>>();
+
+    let seconds = SAMPLE_INTERVAL.as_secs();
+    if !users.is_empty() {
+        watch_time
+            .record(room.id, &users, seconds, time::OffsetDateTime::now())
+            .await?;
+        log::debug!("gave {} users watch time in {}", users.len(), room);
+    }
+
+    // this is published even if nobody is watching, so others know the room is live
+    context.bus().publish(crate::bus::events::WatchTimeSampled {
+        room: room.id,
+        users,
+        seconds,
+    });
+    Ok(())
+}
+
+/// The ids of these users, only asking twitch about the ones that haven't been seen
+async fn resolve_logins(
+    tracker: &Tracker,
+    opt_outs: &OptOuts,
+    client: &TwitchClient,
+    logins: &[&str],
+) -> anyhow::Result<Vec<u64>> {
+    let mut ids = vec![];
+    let mut unknown = vec![];
+    for &login in logins {
+        match tracker.get_by_login(login) {
+            Some(identity) => ids.push(identity.id as u64),
+            None => unknown.push(login),
+        }
+    }
+
+    // twitch only allows 100 users per request
+    for chunk in unknown.chunks(100) {
+        for user in client.get_users(chunk).await? {
+            // they won't be sampled, so there's no reason to remember them
+            if opt_outs.contains(user.id) {
+                continue;
+            }
+            tracker
+                .observe(user.id, &user.login, &user.display_name)
+                .await?;
+            ids.push(user.id);
+        }
+    }
+    Ok(ids)
+}
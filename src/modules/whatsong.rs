@@ -90,16 +90,16 @@ where
 }
 
 #[derive(Debug, serde::Deserialize)]
-struct Song {
+pub(super) struct Song {
     id: i64,
     vid: String,
     timestamp: u64,
     duration: u64,
-    title: String,
+    pub(super) title: String,
 }
 
 #[derive(Clone)]
-struct Client {
+pub(super) struct Client {
     address: String,
     client: reqwest::Client,
 }
@@ -112,7 +112,7 @@ impl Client {
         }
     }
 
-    async fn current(&self) -> anyhow::Result<Option<(u64, Song)>> {
+    pub(super) async fn current(&self) -> anyhow::Result<Option<(u64, Song)>> {
         let now = crate::util::timestamp();
 
         let song: Song = self
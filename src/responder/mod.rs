@@ -4,7 +4,7 @@ use futures::prelude::*;
 use template::Template;
 use twitchchat::Writer;
 
-use crate::{Context, Resolver};
+use crate::{Channels, Context, Resolver};
 
 mod logging;
 pub use logging::LoggingResponder;
@@ -38,19 +38,3 @@ pub trait Responder: Clone {
         T: Template + Send + Sync,
         K: RespondableContext + Send + Sync + 'static;
 }
-
-// pub(crate) async fn get_user(&self, target: u64) -> Result<String, ResponderError> {
-//     self.tracker
-//         .users
-//         .get(target)
-//         .await
-//         .ok_or_else(|| ResponderError::InvalidRoom(target))
-// }
-
-// pub(crate) async fn get_room(&self, target: u64) -> Result<String, ResponderError> {
-//     self.tracker
-//         .rooms
-//         .get(target)
-//         .await
-//         .ok_or_else(|| ResponderError::InvalidRoom(target))
-// }
@@ -1,14 +1,21 @@
 use super::*;
 
+use crate::store::Permit;
+
 #[derive(Clone)]
 pub struct WriterResponder {
     writer: Writer,
     resolver: Resolver,
+    channels: Channels,
 }
 
 impl WriterResponder {
-    pub fn new(writer: Writer, resolver: Resolver) -> Self {
-        Self { writer, resolver }
+    pub fn new(writer: Writer, resolver: Resolver, channels: Channels) -> Self {
+        Self {
+            writer,
+            resolver,
+            channels,
+        }
     }
 }
 
@@ -18,15 +25,13 @@ impl Responder for WriterResponder {
         T: Template + Send + Sync,
         K: RespondableContext + Send + Sync + 'static,
     {
-        let resolver = self.resolver.clone();
-        let mut writer = self.writer.clone();
+        let this = self.clone();
 
         async move {
             let room = context.args.room();
-            let data = Self::resolve_template(resolver, template).await?;
+            let data = Self::resolve_template(this.resolver.clone(), &room, template).await?;
             let resp = Self::apply_template(template, &data)?;
-            writer.privmsg(&room.name, &resp).await?;
-            Ok(())
+            this.deliver(&room.name, Delivery::Privmsg, &resp).await
         }
         .boxed()
     }
@@ -36,19 +41,19 @@ impl Responder for WriterResponder {
         T: Template + Send + Sync,
         K: RespondableContext + Send + Sync + 'static,
     {
-        let resolver = self.resolver.clone();
-        let mut writer = self.writer.clone();
+        let this = self.clone();
 
         async move {
             let room = context.args.room();
             let user = context.args.user();
 
-            let data = Self::resolve_template(resolver, template).await?;
+            let data = Self::resolve_template(this.resolver.clone(), &room, template).await?;
             let resp = Self::apply_template(template, &data)?;
-            writer
-                .privmsg(&room.name, &format!("@{}: {}", user.name, &resp))
-                .await?;
-            Ok(())
+            if resp.is_empty() {
+                return Ok(());
+            }
+            let resp = format!("@{}: {}", user.name, &resp);
+            this.deliver(&room.name, Delivery::Privmsg, &resp).await
         }
         .boxed()
     }
@@ -58,32 +63,82 @@ impl Responder for WriterResponder {
         T: Template + Send + Sync,
         K: RespondableContext + Send + Sync + 'static,
     {
-        let resolver = self.resolver.clone();
-        let mut writer = self.writer.clone();
+        let this = self.clone();
 
         async move {
             let target = context.args.room();
-            let data = Self::resolve_template(resolver, template).await?;
+            let data = Self::resolve_template(this.resolver.clone(), &target, template).await?;
             let resp = Self::apply_template(template, &data)?;
-            writer.me(&target.name, &resp).await?;
-            Ok(())
+            this.deliver(&target.name, Delivery::Action, &resp).await
         }
         .boxed()
     }
 }
 
+#[derive(Copy, Clone, Debug)]
+enum Delivery {
+    Privmsg,
+    Action,
+}
+
+impl WriterResponder {
+    /// Send the message if the room will accept it, then wait briefly to see
+    /// if Twitch rejected it
+    ///
+    /// NOTICEs don't say which message they're about, so only one message is
+    /// delivered to a room at a time.
+    async fn deliver(mut self, room: &str, kind: Delivery, data: &str) -> anyhow::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let sending = self.channels.sending(room);
+        let _sending = sending.lock().await;
+
+        match self.channels.permit(room) {
+            Permit::Silent => {
+                log::debug!("{} is in emote-only mode, not sending: {}", room, data);
+                return Ok(());
+            }
+            Permit::After(wait) if wait > std::time::Duration::from_secs(0) => {
+                log::debug!("waiting {:.2?} before sending to {}", wait, room);
+                tokio::time::delay_for(wait).await;
+            }
+            Permit::After(..) => {}
+        }
+
+        // subscribe before sending so the NOTICE can't be missed
+        let mut failures = self.channels.failures(room);
+        match kind {
+            Delivery::Privmsg => self.writer.privmsg(room, data).await?,
+            Delivery::Action => self.writer.me(room, data).await?,
+        }
+
+        match tokio::time::timeout(Channels::NOTICE_WINDOW, failures.recv()).await {
+            Ok(Ok(err)) => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
 impl WriterResponder {
+    /// Resolve the template, preferring the room's override if it has one
+    ///
+    /// Rooms can override templates with a `["namespace#room"]` table. An
+    /// empty template means nothing will be sent to that room.
     pub(crate) async fn resolve_template<T: Template>(
         resolver: Resolver,
+        room: &Room<'_>,
         template: &T,
     ) -> anyhow::Result<String> {
+        let namespace = T::namespace(Default::default());
+        let variant = template.variant(Default::default());
+        let room_namespace = format!("{}#{}", namespace, room.remove_hashes());
+
+        let resolver = resolver.lock().await;
         resolver
-            .lock()
-            .await
-            .resolve(
-                T::namespace(Default::default()),
-                template.variant(Default::default()),
-            )
+            .resolve(&room_namespace, variant)
+            .or_else(|| resolver.resolve(namespace, variant))
             .cloned()
             .ok_or_else(|| {
                 anyhow::anyhow!(
@@ -0,0 +1,151 @@
+use twitchchat::{messages::Privmsg, BadgeKind};
+
+/// What a user is allowed to do in a room, from least to most privileged
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    Everyone,
+    Subscriber,
+    Vip,
+    Moderator,
+    Broadcaster,
+}
+
+impl Default for Role {
+    fn default() -> Self {
+        Self::Everyone
+    }
+}
+
+impl Role {
+    /// The role of the user who sent this message
+    pub fn of(msg: &Privmsg<'_>) -> Self {
+        msg.badges()
+            .iter()
+            .map(|badge| match &badge.kind {
+                BadgeKind::Broadcaster => Self::Broadcaster,
+                BadgeKind::Moderator => Self::Moderator,
+                BadgeKind::VIP => Self::Vip,
+                BadgeKind::Subscriber => Self::Subscriber,
+                _ => Self::Everyone,
+            })
+            .chain(std::iter::once(if msg.is_moderator() {
+                Self::Moderator
+            } else {
+                Self::Everyone
+            }))
+            .max()
+            .unwrap_or_default()
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Everyone => "everyone",
+            Self::Subscriber => "subscriber",
+            Self::Vip => "vip",
+            Self::Moderator => "moderator",
+            Self::Broadcaster => "broadcaster",
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = anyhow::Error;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let role = match input.to_ascii_lowercase().as_str() {
+            "everyone" | "all" => Self::Everyone,
+            "sub" | "subs" | "subscriber" | "subscribers" => Self::Subscriber,
+            "vip" | "vips" => Self::Vip,
+            "mod" | "mods" | "moderator" | "moderators" => Self::Moderator,
+            "broadcaster" | "streamer" => Self::Broadcaster,
+            role => anyhow::bail!(
+                "unknown role '{}'. use everyone, sub, vip, mod or broadcaster",
+                role
+            ),
+        };
+        Ok(role)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twitchchat::Parse as _;
+
+    fn role_of(tags: &str) -> Role {
+        let raw = format!(
+            "@{} :museun!museun@museun.tmi.twitch.tv PRIVMSG #museun :hello\r\n",
+            tags
+        );
+        let (_, msg) = twitchchat::decode_one(&raw).unwrap();
+        Role::of(&Privmsg::parse(&msg).unwrap())
+    }
+
+    #[test]
+    fn of() {
+        assert_eq!(role_of("badges=;mod=0"), Role::Everyone);
+        assert_eq!(role_of("badges=subscriber/12;mod=0"), Role::Subscriber);
+        assert_eq!(role_of("badges=vip/1;mod=0"), Role::Vip);
+        assert_eq!(role_of("badges=moderator/1;mod=1"), Role::Moderator);
+        assert_eq!(role_of("badges=broadcaster/1;mod=0"), Role::Broadcaster);
+
+        // the highest badge wins, whatever order they're in
+        assert_eq!(
+            role_of("badges=subscriber/0,vip/1,premium/1;mod=0"),
+            Role::Vip
+        );
+        assert_eq!(
+            role_of("badges=subscriber/0,broadcaster/1,moderator/1;mod=0"),
+            Role::Broadcaster
+        );
+
+        // the tag is enough without the badge
+        assert_eq!(role_of("badges=subscriber/0;mod=1"), Role::Moderator);
+        assert_eq!(role_of("badges=glhf-pledge/1;mod=0"), Role::Everyone);
+    }
+
+    #[test]
+    fn from_str() {
+        for (input, role) in &[
+            ("everyone", Role::Everyone),
+            ("all", Role::Everyone),
+            ("sub", Role::Subscriber),
+            ("Subscribers", Role::Subscriber),
+            ("vips", Role::Vip),
+            ("MOD", Role::Moderator),
+            ("moderators", Role::Moderator),
+            ("streamer", Role::Broadcaster),
+            ("broadcaster", Role::Broadcaster),
+        ] {
+            assert_eq!(input.parse::<Role>().unwrap(), *role, "{}", input);
+        }
+
+        assert!("admin".parse::<Role>().is_err());
+        assert!("".parse::<Role>().is_err());
+
+        // what's displayed can be parsed back
+        for &role in &[
+            Role::Everyone,
+            Role::Subscriber,
+            Role::Vip,
+            Role::Moderator,
+            Role::Broadcaster,
+        ] {
+            assert_eq!(role.to_string().parse::<Role>().unwrap(), role);
+        }
+    }
+
+    #[test]
+    fn ordering() {
+        assert_eq!(Role::default(), Role::Everyone);
+        assert!(Role::Everyone < Role::Subscriber);
+        assert!(Role::Subscriber < Role::Vip);
+        assert!(Role::Vip < Role::Moderator);
+        assert!(Role::Moderator < Role::Broadcaster);
+    }
+}
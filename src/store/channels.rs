@@ -0,0 +1,393 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// What we know about each joined room
+///
+/// This is updated from ROOMSTATE, USERSTATE and NOTICE messages and is
+/// shared between the bot and the responder
+#[derive(Clone, Default)]
+pub struct Channels {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    rooms: HashMap<String, ChannelState>,
+    failures: HashMap<String, broadcast::Sender<DeliveryError>>,
+    sending: HashMap<String, Arc<tokio::sync::Mutex<()>>>,
+}
+
+impl std::fmt::Debug for Channels {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Channels")
+            .field("rooms", &self.inner.lock().unwrap().rooms)
+            .finish()
+    }
+}
+
+impl Channels {
+    /// How long to wait for a NOTICE after sending a message
+    pub const NOTICE_WINDOW: Duration = Duration::from_millis(500);
+
+    /// Get the state for a room
+    pub fn get(&self, room: &str) -> Option<ChannelState> {
+        self.inner.lock().unwrap().rooms.get(room).cloned()
+    }
+
+    /// Reserve a slot for sending a message to this room
+    pub fn permit(&self, room: &str) -> Permit {
+        self.inner
+            .lock()
+            .unwrap()
+            .rooms
+            .entry(room.to_string())
+            .or_default()
+            .permit(Instant::now())
+    }
+
+    /// Subscribe to delivery failures for this room
+    pub fn failures(&self, room: &str) -> broadcast::Receiver<DeliveryError> {
+        self.inner
+            .lock()
+            .unwrap()
+            .failures
+            .entry(room.to_string())
+            .or_insert_with(|| broadcast::channel(16).0)
+            .subscribe()
+    }
+
+    /// Held while delivering to this room, so a NOTICE can only be about the one message
+    pub fn sending(&self, room: &str) -> Arc<tokio::sync::Mutex<()>> {
+        self.inner
+            .lock()
+            .unwrap()
+            .sending
+            .entry(room.to_string())
+            .or_insert_with(|| Arc::new(tokio::sync::Mutex::new(())))
+            .clone()
+    }
+
+    pub(crate) fn update_room_state<F>(&self, room: &str, tag: F)
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut inner = self.inner.lock().unwrap();
+        let state = inner.rooms.entry(room.to_string()).or_default();
+        state.update_room_state(tag);
+        log::debug!("room state for {}: {:?}", room, state);
+    }
+
+    pub(crate) fn update_user_state<F>(&self, room: &str, tag: F)
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut inner = self.inner.lock().unwrap();
+        let state = inner.rooms.entry(room.to_string()).or_default();
+        state.update_user_state(tag);
+        log::debug!("our state in {}: moderator: {}", room, state.moderator);
+    }
+
+    pub(crate) fn notice(&self, room: &str, id: &str, message: &str) {
+        let err = match DeliveryError::from_notice(id, message) {
+            Some(err) => err,
+            None => return,
+        };
+
+        log::warn!("message to {} was rejected: {}", room, err);
+        if let Some(tx) = self.inner.lock().unwrap().failures.get(room) {
+            // nothing might be waiting on it
+            let _ = tx.send(err);
+        }
+    }
+
+    pub(crate) fn remove(&self, room: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.rooms.remove(room);
+        inner.failures.remove(room);
+        inner.sending.remove(room);
+    }
+}
+
+/// Whether a message can be sent to a room
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Permit {
+    /// Send the message after waiting this long
+    After(Duration),
+    /// The message would be rejected, so don't send it
+    Silent,
+}
+
+/// The state of a room, and our state in it
+#[derive(Debug, Clone, Default)]
+pub struct ChannelState {
+    pub emote_only: bool,
+    /// `None` if disabled, otherwise the minimum follow time in minutes
+    pub followers_only: Option<u64>,
+    pub subs_only: bool,
+    pub r9k: bool,
+    /// Slow mode in seconds, `0` if disabled
+    pub slow: u64,
+    /// Whether we are a moderator (or the broadcaster) in this room
+    pub moderator: bool,
+    /// Whether we are a VIP in this room
+    pub vip: bool,
+
+    sent: VecDeque<Instant>,
+}
+
+impl ChannelState {
+    const WINDOW: Duration = Duration::from_secs(30);
+    const LIMIT: usize = 20;
+    const PRIVILEGED_LIMIT: usize = 100;
+
+    /// Moderators and VIPs aren't affected by slow mode and have a higher rate limit
+    pub fn is_privileged(&self) -> bool {
+        self.moderator || self.vip
+    }
+
+    fn permit(&mut self, now: Instant) -> Permit {
+        if self.emote_only && !self.moderator {
+            return Permit::Silent;
+        }
+
+        let limit = if self.is_privileged() {
+            Self::PRIVILEGED_LIMIT
+        } else {
+            Self::LIMIT
+        };
+
+        // forget about anything that was sent outside of the window
+        while self
+            .sent
+            .front()
+            .filter(|&&sent| sent + Self::WINDOW <= now)
+            .is_some()
+        {
+            self.sent.pop_front();
+        }
+
+        let mut at = now;
+        if self.sent.len() >= limit {
+            at = at.max(self.sent[self.sent.len() - limit] + Self::WINDOW);
+        }
+
+        if !self.is_privileged() && self.slow > 0 {
+            if let Some(&last) = self.sent.back() {
+                at = at.max(last + Duration::from_secs(self.slow));
+            }
+        }
+
+        self.sent.push_back(at);
+        Permit::After(at - now)
+    }
+
+    // ROOMSTATE only contains the tags that changed after the initial one
+    fn update_room_state<F>(&mut self, tag: F)
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let flag = |key: &str| tag(key).map(|s| s == "1");
+        let num = |key: &str| tag(key).and_then(|s| s.parse::<i64>().ok());
+
+        if let Some(emote_only) = flag("emote-only") {
+            self.emote_only = emote_only
+        }
+        if let Some(followers) = num("followers-only") {
+            self.followers_only = Some(followers).filter(|&n| n >= 0).map(|n| n as _);
+        }
+        if let Some(subs_only) = flag("subs-only") {
+            self.subs_only = subs_only
+        }
+        if let Some(r9k) = flag("r9k") {
+            self.r9k = r9k
+        }
+        if let Some(slow) = num("slow") {
+            self.slow = slow.max(0) as _
+        }
+    }
+
+    fn update_user_state<F>(&mut self, tag: F)
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let badges = tag("badges").unwrap_or_default();
+        let has_badge = |badge: &str| {
+            badges
+                .split(',')
+                .filter_map(|s| s.split('/').next())
+                .any(|s| s == badge)
+        };
+
+        self.moderator = tag("mod").as_deref() == Some("1")
+            || has_badge("moderator")
+            || has_badge("broadcaster");
+        self.vip = has_badge("vip");
+    }
+}
+
+/// A message was rejected by Twitch
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeliveryError {
+    RateLimited,
+    Banned,
+    Duplicate,
+    Rejected { id: String, message: String },
+}
+
+impl DeliveryError {
+    fn from_notice(id: &str, message: &str) -> Option<Self> {
+        let err = match id {
+            "msg_ratelimit" => Self::RateLimited,
+            "msg_banned" => Self::Banned,
+            "msg_duplicate" => Self::Duplicate,
+            id if id.starts_with("msg_") => Self::Rejected {
+                id: id.to_string(),
+                message: message.to_string(),
+            },
+            _ => return None,
+        };
+        Some(err)
+    }
+}
+
+impl std::fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RateLimited => write!(f, "we are sending messages too quickly"),
+            Self::Banned => write!(f, "we are banned from the room"),
+            Self::Duplicate => write!(f, "the message was identical to the previous one"),
+            Self::Rejected { id, message } => write!(f, "{} ({})", message, id),
+        }
+    }
+}
+
+impl std::error::Error for DeliveryError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags<'a>(tags: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |key| {
+            tags.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.to_string())
+        }
+    }
+
+    #[test]
+    fn room_state() {
+        let mut state = ChannelState::default();
+        state.update_room_state(tags(&[
+            ("emote-only", "0"),
+            ("followers-only", "-1"),
+            ("r9k", "0"),
+            ("slow", "0"),
+            ("subs-only", "0"),
+        ]));
+        assert!(!state.emote_only);
+        assert_eq!(state.followers_only, None);
+        assert_eq!(state.slow, 0);
+
+        state.update_room_state(tags(&[("slow", "30")]));
+        assert_eq!(state.slow, 30);
+
+        state.update_room_state(tags(&[("followers-only", "10"), ("emote-only", "1")]));
+        assert_eq!(state.followers_only, Some(10));
+        assert!(state.emote_only);
+        assert_eq!(state.slow, 30);
+    }
+
+    #[test]
+    fn user_state() {
+        let mut state = ChannelState::default();
+        state.update_user_state(tags(&[("mod", "0"), ("badges", "premium/1")]));
+        assert!(!state.moderator);
+        assert!(!state.is_privileged());
+
+        state.update_user_state(tags(&[("mod", "1"), ("badges", "moderator/1")]));
+        assert!(state.moderator);
+
+        state.update_user_state(tags(&[("mod", "0"), ("badges", "broadcaster/1")]));
+        assert!(state.moderator);
+
+        state.update_user_state(tags(&[("mod", "0"), ("badges", "vip/1")]));
+        assert!(!state.moderator);
+        assert!(state.is_privileged());
+    }
+
+    #[test]
+    fn permit() {
+        let now = Instant::now();
+
+        let mut state = ChannelState {
+            emote_only: true,
+            ..Default::default()
+        };
+        assert_eq!(state.permit(now), Permit::Silent);
+
+        state.moderator = true;
+        assert_eq!(state.permit(now), Permit::After(Duration::from_secs(0)));
+
+        let mut state = ChannelState {
+            slow: 10,
+            ..Default::default()
+        };
+        assert_eq!(state.permit(now), Permit::After(Duration::from_secs(0)));
+        assert_eq!(state.permit(now), Permit::After(Duration::from_secs(10)));
+        assert_eq!(state.permit(now), Permit::After(Duration::from_secs(20)));
+
+        // moderators don't care about slow mode
+        let mut state = ChannelState {
+            slow: 10,
+            moderator: true,
+            ..Default::default()
+        };
+        assert_eq!(state.permit(now), Permit::After(Duration::from_secs(0)));
+        assert_eq!(state.permit(now), Permit::After(Duration::from_secs(0)));
+
+        let mut state = ChannelState::default();
+        for _ in 0..ChannelState::LIMIT {
+            assert_eq!(state.permit(now), Permit::After(Duration::from_secs(0)));
+        }
+        assert_eq!(state.permit(now), Permit::After(ChannelState::WINDOW));
+
+        // after the window, its open again
+        let later = now + ChannelState::WINDOW + Duration::from_secs(1);
+        let mut state = ChannelState::default();
+        for _ in 0..ChannelState::LIMIT {
+            state.permit(now);
+        }
+        assert_eq!(state.permit(later), Permit::After(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn delivery_error() {
+        assert_eq!(
+            DeliveryError::from_notice("msg_ratelimit", "slow down"),
+            Some(DeliveryError::RateLimited)
+        );
+        assert_eq!(
+            DeliveryError::from_notice("msg_slowmode", "this room is in slow mode"),
+            Some(DeliveryError::Rejected {
+                id: "msg_slowmode".into(),
+                message: "this room is in slow mode".into()
+            })
+        );
+        assert_eq!(DeliveryError::from_notice("host_on", "now hosting"), None);
+    }
+
+    #[test]
+    fn sending() {
+        let channels = Channels::default();
+        let sending = channels.sending("#museun");
+        assert!(Arc::ptr_eq(&sending, &channels.sending("#museun")));
+        assert!(!Arc::ptr_eq(&sending, &channels.sending("#shaken_bot")));
+
+        // leaving the room forgets it
+        channels.remove("#museun");
+        assert!(!Arc::ptr_eq(&sending, &channels.sending("#museun")));
+    }
+}
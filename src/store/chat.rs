@@ -0,0 +1,300 @@
+use regex::Regex;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+use twitchchat::messages::Privmsg;
+
+/// The tags kept from each message
+const KEPT_TAGS: &[&str] = &[
+    "badge-info",
+    "badges",
+    "bits",
+    "color",
+    "display-name",
+    "emotes",
+    "flags",
+    "id",
+    "mod",
+    "subscriber",
+    "tmi-sent-ts",
+    "vip",
+];
+
+/// A message that was sent to a room
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub room: u64,
+    /// The name of the room, e.g. `#museun`
+    pub channel: String,
+    pub user_id: u64,
+    /// The login of the sender
+    pub login: String,
+    /// The display name of the sender, or their login if they don't have one
+    pub name: String,
+    /// The message id, if twitch sent one
+    pub id: Option<String>,
+    pub data: String,
+    pub tags: HashMap<String, String>,
+    /// When twitch received the message
+    pub at: OffsetDateTime,
+}
+
+impl ChatMessage {
+    /// This returns `None` if the message doesn't have a room id or a user id
+    pub fn from_privmsg(msg: &Privmsg<'_>) -> Option<Self> {
+        let tags = KEPT_TAGS
+            .iter()
+            .filter_map(|&key| Some((key.to_string(), msg.tags.get(key)?.to_string())))
+            .collect::<HashMap<_, _>>();
+
+        let at = tags
+            .get("tmi-sent-ts")
+            .and_then(|ts| ts.parse::<i64>().ok())
+            .map(|ms| {
+                OffsetDateTime::from_unix_timestamp(ms / 1000)
+                    + time::Duration::milliseconds(ms % 1000)
+            })
+            .unwrap_or_else(OffsetDateTime::now);
+
+        Some(Self {
+            room: msg.room_id()?,
+            channel: msg.channel.to_string(),
+            user_id: msg.user_id()?,
+            login: msg.name.to_string(),
+            name: msg
+                .display_name()
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| msg.name.to_string()),
+            id: tags.get("id").cloned(),
+            data: msg.data.to_string(),
+            tags,
+            at,
+        })
+    }
+}
+
+/// Which messages to find in the [`ChatHistory`](./struct.ChatHistory.html)
+#[derive(Debug, Default, Clone)]
+pub struct ChatQuery {
+    user: Option<String>,
+    since: Option<OffsetDateTime>,
+    until: Option<OffsetDateTime>,
+    pattern: Option<Regex>,
+    limit: Option<usize>,
+}
+
+impl ChatQuery {
+    /// Only messages from this login (ignoring case, and an `@` in front of it)
+    pub fn user(mut self, login: &str) -> Self {
+        self.user = Some(login.trim_start_matches('@').to_lowercase());
+        self
+    }
+
+    /// Only messages sent at or after this time
+    pub fn since(mut self, since: OffsetDateTime) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Only messages sent before this time
+    pub fn until(mut self, until: OffsetDateTime) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Only messages matching this pattern
+    pub fn pattern(mut self, pattern: Regex) -> Self {
+        self.pattern = Some(pattern);
+        self
+    }
+
+    /// At most this many messages
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn matches(&self, msg: &ChatMessage) -> bool {
+        self.user.as_ref().map_or(true, |user| msg.login == *user)
+            && self.since.map_or(true, |since| msg.at >= since)
+            && self.until.map_or(true, |until| msg.at < until)
+            && self
+                .pattern
+                .as_ref()
+                .map_or(true, |re| re.is_match(&msg.data))
+    }
+}
+
+/// The recent messages of each room
+///
+/// Each room keeps up to `capacity` messages, the oldest are dropped first.
+/// The bot adds every message it sees to the one in the `State`.
+#[derive(Clone)]
+pub struct ChatHistory {
+    rooms: Arc<Mutex<HashMap<u64, VecDeque<ChatMessage>>>>,
+    capacity: usize,
+}
+
+impl Default for ChatHistory {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl std::fmt::Debug for ChatHistory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChatHistory")
+            .field("rooms", &self.rooms.lock().unwrap().len())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl ChatHistory {
+    /// How many messages are kept for each room by default
+    pub const DEFAULT_CAPACITY: usize = 1000;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            rooms: Default::default(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Add a message, dropping the oldest one in the room if it's full
+    pub fn push(&self, msg: ChatMessage) {
+        let mut rooms = self.rooms.lock().unwrap();
+        let room = rooms
+            .entry(msg.room)
+            .or_insert_with(|| VecDeque::with_capacity(self.capacity));
+        if room.len() == self.capacity {
+            room.pop_front();
+        }
+        room.push_back(msg);
+    }
+
+    /// The messages in the room that match the query, newest first
+    pub fn query(&self, room: u64, query: &ChatQuery) -> Vec<ChatMessage> {
+        let rooms = self.rooms.lock().unwrap();
+        let messages = match rooms.get(&room) {
+            Some(messages) => messages,
+            None => return vec![],
+        };
+
+        messages
+            .iter()
+            .rev()
+            .filter(|msg| query.matches(msg))
+            .take(query.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+
+    /// The last message this user sent to the room
+    pub fn last_from(&self, room: u64, login: &str) -> Option<ChatMessage> {
+        let query = ChatQuery::default().user(login).limit(1);
+        self.query(room, &query).pop()
+    }
+
+    /// Forget the user's messages in every room
+    pub fn forget_user(&self, user_id: u64) {
+        for messages in self.rooms.lock().unwrap().values_mut() {
+            messages.retain(|msg| msg.user_id != user_id);
+        }
+    }
+
+    /// Forget the room's messages
+    pub fn clear(&self, room: u64) {
+        self.rooms.lock().unwrap().remove(&room);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_message(room: u64, login: &str, data: &str, at: i64) -> ChatMessage {
+        ChatMessage {
+            room,
+            channel: format!("#room{}", room),
+            user_id: 1234,
+            login: login.into(),
+            name: login.into(),
+            id: None,
+            data: data.into(),
+            tags: HashMap::new(),
+            at: OffsetDateTime::from_unix_timestamp(at),
+        }
+    }
+
+    fn data(messages: Vec<ChatMessage>) -> Vec<String> {
+        messages.into_iter().map(|msg| msg.data).collect()
+    }
+
+    #[test]
+    fn ring_buffer() {
+        let history = ChatHistory::new(3);
+        for n in 0..5 {
+            history.push(make_message(1, "museun", &n.to_string(), n));
+        }
+        history.push(make_message(2, "museun", "other room", 0));
+
+        let all = history.query(1, &ChatQuery::default());
+        assert_eq!(data(all), vec!["4", "3", "2"]);
+
+        history.forget_user(1234);
+        assert!(history.query(1, &ChatQuery::default()).is_empty());
+        assert!(history.query(2, &ChatQuery::default()).is_empty());
+
+        history.push(make_message(1, "museun", "again", 5));
+        history.push(make_message(2, "museun", "other room", 0));
+        history.clear(1);
+        assert!(history.query(1, &ChatQuery::default()).is_empty());
+        assert_eq!(history.query(2, &ChatQuery::default()).len(), 1);
+    }
+
+    #[test]
+    fn query() {
+        let history = ChatHistory::default();
+        for (n, (login, msg)) in [
+            ("museun", "hello world"),
+            ("shaken_bot", "hello museun"),
+            ("museun", "what keyboard is that?"),
+            ("someone", "HELLO"),
+        ]
+        .iter()
+        .enumerate()
+        {
+            history.push(make_message(1, login, msg, n as i64 * 10));
+        }
+
+        let query = ChatQuery::default().user("@MUSEUN");
+        assert_eq!(
+            data(history.query(1, &query)),
+            vec!["what keyboard is that?", "hello world"]
+        );
+
+        let query = ChatQuery::default()
+            .since(OffsetDateTime::from_unix_timestamp(10))
+            .until(OffsetDateTime::from_unix_timestamp(30));
+        assert_eq!(
+            data(history.query(1, &query)),
+            vec!["what keyboard is that?", "hello museun"]
+        );
+
+        let query = ChatQuery::default()
+            .pattern(Regex::new("(?i)^hello").unwrap())
+            .limit(2);
+        assert_eq!(
+            data(history.query(1, &query)),
+            vec!["HELLO", "hello museun"]
+        );
+
+        assert_eq!(
+            history.last_from(1, "museun").unwrap().data,
+            "what keyboard is that?"
+        );
+        assert!(history.last_from(1, "nobody").is_none());
+        assert!(history.last_from(2, "museun").is_none());
+    }
+}
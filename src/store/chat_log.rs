@@ -0,0 +1,249 @@
+use super::ChatMessage;
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use time::OffsetDateTime;
+
+/// A message from the chat log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ArchivedMessage {
+    pub id: i64,
+    pub room: i64,
+    pub channel: String,
+    pub user_id: i64,
+    pub login: String,
+    pub name: String,
+    pub msg_id: Option<String>,
+    pub data: String,
+    /// Unix timestamp of when it was sent
+    pub at: i64,
+}
+
+const COLUMNS: &str = "chat_log.id, room, channel, user_id, login, name, msg_id, chat_log.data, at";
+
+/// The archived chat of the rooms that have logging enabled
+#[derive(Clone)]
+pub struct ChatArchive {
+    pool: SqlitePool,
+}
+
+impl std::fmt::Debug for ChatArchive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChatArchive").finish()
+    }
+}
+
+impl ChatArchive {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn append(&self, msg: &ChatMessage) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO chat_log (room, channel, user_id, login, name, msg_id, data, at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(msg.room as i64)
+        .bind(&msg.channel)
+        .bind(msg.user_id as i64)
+        .bind(&msg.login)
+        .bind(&msg.name)
+        .bind(msg.id.as_deref())
+        .bind(&msg.data)
+        .bind(msg.at.timestamp())
+        .execute(&mut &self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The last message this user sent to the room
+    pub async fn last_from(
+        &self,
+        room: u64,
+        login: &str,
+    ) -> anyhow::Result<Option<ArchivedMessage>> {
+        sqlx::query_as::<_, ArchivedMessage>(&format!(
+            "SELECT {} FROM chat_log
+             WHERE room = ? AND login = ?
+             ORDER BY at DESC, id DESC
+             LIMIT 1",
+            COLUMNS
+        ))
+        .bind(room as i64)
+        .bind(login.trim_start_matches('@').to_lowercase())
+        .fetch_optional(&mut &self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// The room's messages sent in `[from, to)` containing all of the words, newest first
+    pub async fn search(
+        &self,
+        channel: &str,
+        words: &str,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+        limit: i64,
+    ) -> anyhow::Result<Vec<ArchivedMessage>> {
+        let query = match fts_query(words) {
+            Some(query) => query,
+            None => return Ok(vec![]),
+        };
+
+        sqlx::query_as::<_, ArchivedMessage>(&format!(
+            "SELECT {} FROM chat_log_fts
+             JOIN chat_log ON chat_log.id = chat_log_fts.rowid
+             WHERE chat_log_fts MATCH ? AND channel = ? AND at >= ? AND at < ?
+             ORDER BY at DESC, chat_log.id DESC
+             LIMIT ?",
+            COLUMNS
+        ))
+        .bind(query)
+        .bind(channel)
+        .bind(from.timestamp())
+        .bind(to.timestamp())
+        .bind(limit)
+        .fetch_all(&mut &self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// The room's messages sent in `[from, to)`, oldest first
+    pub async fn range(
+        &self,
+        channel: &str,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> anyhow::Result<Vec<ArchivedMessage>> {
+        sqlx::query_as::<_, ArchivedMessage>(&format!(
+            "SELECT {} FROM chat_log
+             WHERE channel = ? AND at >= ? AND at < ?
+             ORDER BY at, id",
+            COLUMNS
+        ))
+        .bind(channel)
+        .bind(from.timestamp())
+        .bind(to.timestamp())
+        .fetch_all(&mut &self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Delete the room's messages sent before `before`, returning how many were deleted
+    pub async fn prune(&self, room: u64, before: OffsetDateTime) -> anyhow::Result<u64> {
+        sqlx::query("DELETE FROM chat_log WHERE room = ? AND at < ?")
+            .bind(room as i64)
+            .bind(before.timestamp())
+            .execute(&mut &self.pool)
+            .await
+            .map_err(Into::into)
+    }
+}
+
+/// Quote each word so FTS5 doesn't treat any of them as syntax
+fn fts_query(words: &str) -> Option<String> {
+    let words = words
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>();
+    Some(words.join(" ")).filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn make_message(login: &str, data: &str, at: i64) -> ChatMessage {
+        ChatMessage {
+            room: 1234,
+            channel: "#museun".into(),
+            user_id: 42,
+            login: login.into(),
+            name: login.into(),
+            id: None,
+            data: data.into(),
+            tags: HashMap::new(),
+            at: OffsetDateTime::from_unix_timestamp(at),
+        }
+    }
+
+    #[tokio::test]
+    async fn archive() {
+        let pool = crate::database::in_memory().await.unwrap();
+        let archive = ChatArchive::new(pool);
+
+        for (login, data, at) in &[
+            ("museun", "hello world", 100),
+            ("shaken_bot", "hello museun", 200),
+            ("museun", "what \"keyboard\" is that?", 300),
+        ] {
+            archive
+                .append(&make_message(login, data, *at))
+                .await
+                .unwrap();
+        }
+
+        let last = archive.last_from(1234, "@Museun").await.unwrap().unwrap();
+        assert_eq!(last.data, "what \"keyboard\" is that?");
+        assert!(archive.last_from(1234, "nobody").await.unwrap().is_none());
+
+        let (start, now) = (
+            OffsetDateTime::from_unix_timestamp(0),
+            OffsetDateTime::now(),
+        );
+        let found = |words| {
+            let archive = archive.clone();
+            async move {
+                archive
+                    .search("#museun", words, start, now, 10)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|msg| msg.at)
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(found("hello").await, vec![200, 100]);
+        assert_eq!(found("HELLO museun").await, vec![200]);
+        assert_eq!(found("\"keyboard").await, vec![300]);
+        assert!(found("").await.is_empty());
+        assert!(archive
+            .search("#other", "hello", start, now, 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // the limit applies to what's in the range, not to the newest in the room
+        let before = OffsetDateTime::from_unix_timestamp(150);
+        let older = archive
+            .search("#museun", "hello", start, before, 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            older.iter().map(|msg| msg.at).collect::<Vec<_>>(),
+            vec![100]
+        );
+
+        let range = archive
+            .range(
+                "#museun",
+                OffsetDateTime::from_unix_timestamp(100),
+                OffsetDateTime::from_unix_timestamp(300),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            range.iter().map(|msg| msg.at).collect::<Vec<_>>(),
+            vec![100, 200]
+        );
+
+        let pruned = archive
+            .prune(1234, OffsetDateTime::from_unix_timestamp(250))
+            .await
+            .unwrap();
+        assert_eq!(pruned, 2);
+        assert_eq!(found("hello").await, Vec::<i64>::new());
+        assert_eq!(found("keyboard").await, vec![300]);
+    }
+}
@@ -0,0 +1,329 @@
+use crate::{bus::events::CounterChanged, EventBus};
+use sqlx::{prelude::*, SqlitePool};
+
+/// Named counters, per room
+///
+/// Every change is published on the bus as a
+/// [`CounterChanged`](../bus/events/struct.CounterChanged.html)
+#[derive(Clone)]
+pub struct Counters {
+    pool: SqlitePool,
+    bus: EventBus,
+}
+
+impl std::fmt::Debug for Counters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Counters").finish()
+    }
+}
+
+/// A counter in a room
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Counter {
+    pub room: i64,
+    pub name: String,
+    pub value: i64,
+    /// Whether it goes back to 0 when a stream starts
+    pub per_stream: bool,
+    /// Unix timestamp of when it was last reset
+    pub reset_at: i64,
+}
+
+impl Counters {
+    pub fn new(pool: SqlitePool, bus: EventBus) -> Self {
+        Self { pool, bus }
+    }
+
+    /// Whether this can be the name of a counter
+    ///
+    /// Counters answer to `!name`, and bodies refer to them with `${counter name}`
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+    }
+
+    /// Get the value of the counter, if it exists
+    pub async fn get(&self, room: u64, name: &str) -> anyhow::Result<Option<i64>> {
+        let row = sqlx::query("SELECT value FROM counters WHERE room = ? AND name = ?")
+            .bind(room as i64)
+            .bind(name)
+            .fetch_optional(&mut &self.pool)
+            .await?;
+        Ok(row.map(|row| row.get::<i64, _>(0)))
+    }
+
+    /// All of the counters in the room, by name
+    pub async fn all(&self, room: u64) -> anyhow::Result<Vec<Counter>> {
+        sqlx::query_as::<_, Counter>(
+            "SELECT room, name, value, per_stream, reset_at FROM counters
+             WHERE room = ?
+             ORDER BY name",
+        )
+        .bind(room as i64)
+        .fetch_all(&mut &self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Create a counter starting at 0
+    ///
+    /// Returns `false` if the room already has a counter or a command with this name
+    pub async fn create(&self, room: u64, name: &str, per_stream: bool) -> anyhow::Result<bool> {
+        let command = format!("!{}", name);
+        let n = sqlx::query(
+            "INSERT OR IGNORE INTO counters (room, name, value, per_stream, reset_at)
+             SELECT ?, ?, 0, ?, ?
+             WHERE NOT EXISTS (SELECT 1 FROM builtin_commands WHERE name = ?)
+               AND NOT EXISTS (SELECT 1 FROM user_commands WHERE name = ? AND room = ?)",
+        )
+        .bind(room as i64)
+        .bind(name)
+        .bind(per_stream)
+        .bind(time::OffsetDateTime::now().timestamp())
+        .bind(&command)
+        .bind(&command)
+        .bind(room as i64)
+        .execute(&mut &self.pool)
+        .await?;
+
+        if n == 1 {
+            self.publish(room, name, None, 0);
+        }
+        Ok(n == 1)
+    }
+
+    /// Returns `false` if the counter doesn't exist
+    pub async fn remove(&self, room: u64, name: &str) -> anyhow::Result<bool> {
+        let n = sqlx::query("DELETE FROM counters WHERE room = ? AND name = ?")
+            .bind(room as i64)
+            .bind(name)
+            .execute(&mut &self.pool)
+            .await?;
+        Ok(n == 1)
+    }
+
+    /// Add `delta` (which can be negative) to the counter, returning its new value
+    ///
+    /// Fails with a [`CounterOverflow`](./struct.CounterOverflow.html) if the value can't hold it
+    pub async fn add(&self, room: u64, name: &str, delta: i64) -> anyhow::Result<Option<i64>> {
+        self.change(room, name, false, |value| {
+            value
+                .checked_add(delta)
+                .ok_or_else(|| CounterOverflow { value, delta }.into())
+        })
+        .await
+    }
+
+    /// Set the counter, returning its new value
+    pub async fn set(&self, room: u64, name: &str, value: i64) -> anyhow::Result<Option<i64>> {
+        self.change(room, name, false, |_| Ok(value)).await
+    }
+
+    /// Set the counter back to 0, returning its new value
+    pub async fn reset(&self, room: u64, name: &str) -> anyhow::Result<Option<i64>> {
+        self.change(room, name, true, |_| Ok(0)).await
+    }
+
+    /// Reset the room's per-stream counters that haven't been reset since the stream started
+    ///
+    /// Returns the names of the counters that were reset
+    pub async fn reset_for_stream(
+        &self,
+        room: u64,
+        started_at: time::OffsetDateTime,
+    ) -> anyhow::Result<Vec<String>> {
+        let stale = self
+            .all(room)
+            .await?
+            .into_iter()
+            .filter(|counter| counter.per_stream && counter.reset_at < started_at.timestamp())
+            .map(|counter| counter.name)
+            .collect::<Vec<_>>();
+
+        for name in &stale {
+            self.reset(room, name).await?;
+        }
+        Ok(stale)
+    }
+
+    async fn change(
+        &self,
+        room: u64,
+        name: &str,
+        reset: bool,
+        next: impl FnOnce(i64) -> anyhow::Result<i64>,
+    ) -> anyhow::Result<Option<i64>> {
+        let mut tx = self.pool.begin().await?;
+
+        let previous = sqlx::query("SELECT value FROM counters WHERE room = ? AND name = ?")
+            .bind(room as i64)
+            .bind(name)
+            .fetch_optional(&mut tx)
+            .await?
+            .map(|row| row.get::<i64, _>(0));
+        let previous = match previous {
+            Some(previous) => previous,
+            None => return Ok(None),
+        };
+
+        // dropping the transaction rolls it back
+        let value = next(previous)?;
+
+        let query = if reset {
+            sqlx::query("UPDATE counters SET value = ?, reset_at = ? WHERE room = ? AND name = ?")
+                .bind(value)
+                .bind(time::OffsetDateTime::now().timestamp())
+        } else {
+            sqlx::query("UPDATE counters SET value = ? WHERE room = ? AND name = ?").bind(value)
+        };
+        query.bind(room as i64).bind(name).execute(&mut tx).await?;
+
+        tx.commit().await?;
+
+        self.publish(room, name, Some(previous), value);
+        Ok(Some(value))
+    }
+
+    fn publish(&self, room: u64, name: &str, previous: Option<i64>, value: i64) {
+        self.bus.publish(CounterChanged {
+            room,
+            name: name.to_string(),
+            previous,
+            value,
+        });
+    }
+}
+
+/// Adding to a counter would go past what it can hold
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CounterOverflow {
+    pub value: i64,
+    pub delta: i64,
+}
+
+impl std::fmt::Display for CounterOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} can't be added to a counter of {}",
+            self.delta, self.value
+        )
+    }
+}
+
+impl std::error::Error for CounterOverflow {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::prelude::*;
+
+    #[test]
+    fn valid_name() {
+        assert!(Counters::is_valid_name("deaths"));
+        assert!(Counters::is_valid_name("boss_deaths2"));
+        assert!(!Counters::is_valid_name(""));
+        assert!(!Counters::is_valid_name("!deaths"));
+        assert!(!Counters::is_valid_name("dea-ths"));
+    }
+
+    #[tokio::test]
+    async fn counters() {
+        let pool = crate::database::in_memory().await.unwrap();
+        let bus = EventBus::default();
+        let mut events = bus.subscribe::<CounterChanged>();
+        let counters = Counters::new(pool, bus);
+
+        assert!(counters.create(1, "deaths", false).await.unwrap());
+        assert!(!counters.create(1, "deaths", false).await.unwrap());
+        assert!(counters.create(2, "deaths", true).await.unwrap());
+
+        assert_eq!(counters.add(1, "deaths", 1).await.unwrap(), Some(1));
+        assert_eq!(counters.add(1, "deaths", 2).await.unwrap(), Some(3));
+        assert_eq!(counters.add(1, "deaths", -1).await.unwrap(), Some(2));
+        assert_eq!(counters.set(2, "deaths", 10).await.unwrap(), Some(10));
+        assert_eq!(counters.add(1, "wins", 1).await.unwrap(), None);
+
+        assert_eq!(counters.get(1, "deaths").await.unwrap(), Some(2));
+        assert_eq!(counters.get(2, "deaths").await.unwrap(), Some(10));
+
+        // only the per-stream counters, and only once per stream
+        let started_at = time::OffsetDateTime::now() + time::Duration::seconds(10);
+        assert_eq!(
+            counters.reset_for_stream(2, started_at).await.unwrap(),
+            vec!["deaths"]
+        );
+        assert!(counters
+            .reset_for_stream(1, started_at)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(counters.get(1, "deaths").await.unwrap(), Some(2));
+        assert_eq!(counters.get(2, "deaths").await.unwrap(), Some(0));
+
+        assert!(counters.remove(1, "deaths").await.unwrap());
+        assert!(!counters.remove(1, "deaths").await.unwrap());
+        assert_eq!(counters.get(1, "deaths").await.unwrap(), None);
+
+        let event = events.next().await.unwrap();
+        assert_eq!(
+            *event,
+            CounterChanged {
+                room: 1,
+                name: "deaths".into(),
+                previous: None,
+                value: 0,
+            }
+        );
+        let event = events.next().await.unwrap();
+        assert_eq!((event.previous, event.value), (Some(0), 1));
+    }
+
+    #[tokio::test]
+    async fn commands_are_taken() {
+        let pool = crate::database::in_memory().await.unwrap();
+        sqlx::query(
+            "INSERT INTO user_commands (name, body, room, uses, owner, disabled, created_at)
+             VALUES ('!wins', 'hello', 1, 0, 1, 0, 0)",
+        )
+        .execute(&mut &pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO builtin_commands (name) VALUES ('!counter')")
+            .execute(&mut &pool)
+            .await
+            .unwrap();
+
+        let counters = Counters::new(pool, EventBus::default());
+        assert!(!counters.create(1, "wins", false).await.unwrap());
+        assert!(!counters.create(1, "counter", false).await.unwrap());
+        assert!(counters.create(2, "wins", false).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn huge_amounts() {
+        let pool = crate::database::in_memory().await.unwrap();
+        let counters = Counters::new(pool, EventBus::default());
+        counters.create(1, "deaths", false).await.unwrap();
+
+        assert_eq!(
+            counters.set(1, "deaths", i64::MAX - 1).await.unwrap(),
+            Some(i64::MAX - 1)
+        );
+        assert_eq!(counters.add(1, "deaths", 1).await.unwrap(), Some(i64::MAX));
+
+        let err = counters.add(1, "deaths", 1).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<CounterOverflow>(),
+            Some(&CounterOverflow {
+                value: i64::MAX,
+                delta: 1
+            })
+        );
+
+        counters.set(1, "deaths", i64::MIN).await.unwrap();
+        assert!(counters.add(1, "deaths", -1).await.is_err());
+
+        // nothing changed, so the column is still an integer
+        assert_eq!(counters.get(1, "deaths").await.unwrap(), Some(i64::MIN));
+    }
+}
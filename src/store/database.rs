@@ -0,0 +1,80 @@
+use crate::Directories;
+use sqlx::{prelude::*, SqlitePool};
+use std::path::{Path, PathBuf};
+
+/// The default database file, in the data directory
+pub fn default_path() -> anyhow::Result<PathBuf> {
+    Directories::data().map(|dir| dir.join("shaken.db"))
+}
+
+/// Open (creating if needed) the database at this path, and apply any pending migrations
+///
+/// The database is backed up before any migrations are applied
+pub async fn open(path: impl Into<PathBuf>) -> anyhow::Result<SqlitePool> {
+    let path = path.into();
+    let pool = connect(&path).await?;
+    for migration in super::migrations::upgrade(&pool, Some(&path)).await? {
+        log::info!(
+            "migrated database to version {} ({})",
+            migration.version,
+            migration.name
+        );
+    }
+    Ok(pool)
+}
+
+/// Open (creating if needed) the database at this path, without migrating it
+pub async fn connect(path: impl AsRef<Path>) -> anyhow::Result<SqlitePool> {
+    let path = path.as_ref();
+    log::debug!("opening database at: {}", path.display());
+    SqlitePool::new(&format!("sqlite://{}", path.to_string_lossy()))
+        .await
+        .map_err(Into::into)
+}
+
+/// Open an existing database at this path read-only, so nothing can change it
+pub async fn connect_read_only(path: impl AsRef<Path>) -> anyhow::Result<SqlitePool> {
+    let path = path.as_ref();
+    log::debug!("opening database (read-only) at: {}", path.display());
+    // the path is part of a uri here, so these would be taken as its query or fragment
+    let path = path
+        .to_string_lossy()
+        .replace('%', "%25")
+        .replace('?', "%3f")
+        .replace('#', "%23");
+    SqlitePool::new(&format!("file:{}?mode=ro", path))
+        .await
+        .map_err(Into::into)
+}
+
+/// Open a new, empty in-memory database with the latest schema
+///
+/// Each call gets a uniquely named database which is shared between all of
+/// the connections in the pool
+pub async fn in_memory() -> anyhow::Result<SqlitePool> {
+    let pool = connect_in_memory().await?;
+    super::migrations::upgrade(&pool, None).await?;
+    Ok(pool)
+}
+
+pub(crate) async fn connect_in_memory() -> anyhow::Result<SqlitePool> {
+    use rand::prelude::*;
+    let name = thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(16)
+        .collect::<String>();
+    SqlitePool::new(&format!("file:{}?mode=memory&cache=shared", name))
+        .await
+        .map_err(Into::into)
+}
+
+/// Write a consistent copy of the database to `to`
+///
+/// This is safe to do while the database is in use
+pub async fn backup(pool: &SqlitePool, to: &Path) -> anyhow::Result<()> {
+    anyhow::ensure!(!to.exists(), "backup file already exists: {}", to.display());
+
+    let sql = format!("VACUUM INTO '{}'", to.to_string_lossy().replace('\'', "''"));
+    pool.acquire().await?.execute(sql.as_str()).await?;
+    Ok(())
+}
@@ -0,0 +1,293 @@
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+/// The user-created data in the database, in a form that can be kept in version control
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Export {
+    pub schema_version: i64,
+    /// Unix timestamp of when this was exported
+    pub exported_at: i64,
+    #[serde(default)]
+    pub commands: Vec<ExportedCommand>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shares: Vec<ExportedShare>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<ExportedTrigger>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub counters: Vec<ExportedCounter>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quotes: Vec<ExportedQuote>,
+}
+
+/// A user-defined command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedCommand {
+    pub name: String,
+    pub body: String,
+    pub room: i64,
+    pub uses: i64,
+    pub owner: i64,
+    pub disabled: bool,
+    pub created_at: i64,
+    #[serde(default = "default_role")]
+    pub role: String,
+    #[serde(default)]
+    pub cooldown: i64,
+    #[serde(default)]
+    pub global: bool,
+    /// The other bodies of the command
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<String>,
+    #[serde(default = "default_mode")]
+    pub mode: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct CommandRow {
+    name: String,
+    body: String,
+    room: i64,
+    uses: i64,
+    owner: i64,
+    disabled: bool,
+    created_at: i64,
+    role: String,
+    cooldown: i64,
+    global: bool,
+    variants: String,
+    mode: String,
+}
+
+/// A user-defined command that is shared with another room
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ExportedShare {
+    /// The name of the command
+    pub name: String,
+    /// The room the command belongs to
+    pub room: i64,
+    /// The room it is shared with
+    pub shared_with: i64,
+}
+
+/// A phrase that gets an automatic response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ExportedTrigger {
+    pub name: String,
+    /// Either `keyword` or `regex`
+    pub kind: String,
+    pub pattern: String,
+    pub body: String,
+    pub room: i64,
+    pub owner: i64,
+    pub cooldown: i64,
+    pub disabled: bool,
+    pub created_at: i64,
+}
+
+/// A named counter in a room
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ExportedCounter {
+    pub name: String,
+    pub room: i64,
+    pub value: i64,
+    #[serde(default)]
+    pub per_stream: bool,
+}
+
+/// A quote, numbered per room
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ExportedQuote {
+    pub room: i64,
+    pub number: i64,
+    pub body: String,
+    /// Who said it
+    pub author: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author_id: Option<i64>,
+    pub added_by: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub created_at: i64,
+}
+
+fn default_role() -> String {
+    crate::Role::default().to_string()
+}
+
+fn default_mode() -> String {
+    "random".into()
+}
+
+impl Export {
+    /// Read everything out of the database
+    pub async fn from_database(pool: &SqlitePool) -> anyhow::Result<Self> {
+        let schema_version = super::migrations::read_status(pool).await?.version();
+
+        let commands = sqlx::query_as::<_, CommandRow>(
+            "SELECT name, body, room, uses, owner, disabled, created_at, role, cooldown, global,
+                    variants, mode
+             FROM user_commands
+             ORDER BY room, name",
+        )
+        .fetch_all(&mut &*pool)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(ExportedCommand {
+                name: row.name,
+                body: row.body,
+                room: row.room,
+                uses: row.uses,
+                owner: row.owner,
+                disabled: row.disabled,
+                created_at: row.created_at,
+                role: row.role,
+                cooldown: row.cooldown,
+                global: row.global,
+                variants: serde_json::from_str(&row.variants)?,
+                mode: row.mode,
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+        let shares = sqlx::query_as::<_, ExportedShare>(
+            "SELECT user_commands.name AS name, user_commands.room AS room, command_shares.room AS shared_with
+             FROM command_shares
+             JOIN user_commands ON user_commands.id = command_shares.command_id
+             ORDER BY user_commands.room, user_commands.name, command_shares.room",
+        )
+        .fetch_all(&mut &*pool)
+        .await?;
+
+        let triggers = sqlx::query_as::<_, ExportedTrigger>(
+            "SELECT name, kind, pattern, body, room, owner, cooldown, disabled, created_at
+             FROM user_triggers
+             ORDER BY room, name",
+        )
+        .fetch_all(&mut &*pool)
+        .await?;
+
+        let counters = sqlx::query_as::<_, ExportedCounter>(
+            "SELECT name, room, value, per_stream FROM counters ORDER BY room, name",
+        )
+        .fetch_all(&mut &*pool)
+        .await?;
+
+        let quotes = sqlx::query_as::<_, ExportedQuote>(
+            "SELECT room, number, body, author, author_id, added_by, game, title, created_at
+             FROM quotes
+             ORDER BY room, number",
+        )
+        .fetch_all(&mut &*pool)
+        .await?;
+
+        Ok(Self {
+            schema_version,
+            exported_at: time::OffsetDateTime::now().timestamp(),
+            commands,
+            shares,
+            triggers,
+            counters,
+            quotes,
+        })
+    }
+
+    pub fn to_string(&self, format: Format) -> anyhow::Result<String> {
+        match format {
+            Format::Json => serde_json::to_string_pretty(self).map_err(Into::into),
+            Format::Toml => toml::to_string_pretty(self).map_err(Into::into),
+        }
+    }
+
+    pub fn parse(data: &str, format: Format) -> anyhow::Result<Self> {
+        match format {
+            Format::Json => serde_json::from_str(data).map_err(Into::into),
+            Format::Toml => toml::from_str(data).map_err(Into::into),
+        }
+    }
+}
+
+/// The format to export to
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            format => anyhow::bail!("unknown format: '{}'. use 'json' or 'toml'", format),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn export() {
+        let pool = crate::database::in_memory().await.unwrap();
+        sqlx::query(
+            "INSERT INTO user_commands (name, body, room, uses, owner, disabled, created_at)
+             VALUES ('!hello', 'hello ${user}', 1234, 3, 4321, 0, 1585744496)",
+        )
+        .execute(&mut &pool)
+        .await
+        .unwrap();
+
+        sqlx::query(
+            "INSERT INTO quotes (room, number, body, author, author_id, added_by, game, title, created_at)
+             VALUES (1234, 1, 'hello world', 'someone', NULL, 4321, 'Just Chatting', NULL, 1585744496)",
+        )
+        .execute(&mut &pool)
+        .await
+        .unwrap();
+
+        let export = Export::from_database(&pool).await.unwrap();
+        assert_eq!(export.schema_version, crate::migrations::latest());
+        assert_eq!(
+            export.commands,
+            vec![ExportedCommand {
+                name: "!hello".into(),
+                body: "hello ${user}".into(),
+                room: 1234,
+                uses: 3,
+                owner: 4321,
+                disabled: false,
+                created_at: 1585744496,
+                role: "everyone".into(),
+                cooldown: 0,
+                global: false,
+                variants: vec![],
+                mode: "random".into(),
+            }]
+        );
+
+        assert_eq!(
+            export.quotes,
+            vec![ExportedQuote {
+                room: 1234,
+                number: 1,
+                body: "hello world".into(),
+                author: "someone".into(),
+                author_id: None,
+                added_by: 4321,
+                game: Some("Just Chatting".into()),
+                title: None,
+                created_at: 1585744496,
+            }]
+        );
+
+        for &format in &[Format::Json, Format::Toml] {
+            let data = export.to_string(format).unwrap();
+            assert_eq!(Export::parse(&data, format).unwrap(), export);
+        }
+    }
+}
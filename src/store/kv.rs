@@ -1,151 +1,289 @@
-use super::database;
-use rusqlite::Connection;
-use std::borrow::Cow;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{prelude::*, SqlitePool};
+use std::{sync::Arc, time::Duration};
 
-pub struct KeyValueStore<'a> {
-    table: Cow<'a, str>,
-    conn: Connection,
+/// A typed key-value store, backed by a table in the database
+///
+/// Keys and values are anything that can be serialized. Each store gets its
+/// own table (prefixed with `kv_`) so modules won't clobber each other.
+#[derive(Clone)]
+pub struct KeyValueStore {
+    table: Arc<str>,
+    pool: SqlitePool,
 }
 
-impl<'a> std::fmt::Debug for KeyValueStore<'a> {
+impl std::fmt::Debug for KeyValueStore {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("KeyValuStore")
+        f.debug_struct("KeyValueStore")
             .field("table", &self.table)
             .finish()
     }
 }
 
-// TODO better error handling
-impl<'a> KeyValueStore<'a> {
-    fn create_table(name: &str, conn: &Connection) -> anyhow::Result<()> {
-        let sql = format!(
-            "CREATE TABLE IF NOT EXISTS {} (key BLOB UNIQUE, value BLOB)",
+impl KeyValueStore {
+    /// Open the store named `name`, creating its table if needed
+    pub async fn open(pool: SqlitePool, name: &str) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            "invalid name for a key-value store: '{}'",
             name
         );
 
-        conn.execute(&sql, rusqlite::NO_PARAMS)
-            .map(|_| ())
-            .map_err(Into::into)
+        let table = format!("kv_{}", name);
+        let sql = format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                key        BLOB NOT NULL PRIMARY KEY,
+                value      BLOB NOT NULL,
+                expires_at INTEGER
+            )",
+            table
+        );
+        sqlx::query(&sql).execute(&mut &pool).await?;
+
+        Ok(Self {
+            table: table.into(),
+            pool,
+        })
     }
 
-    pub fn fetch(table: &'a str) -> anyhow::Result<Self> {
-        database::get_global_connection()
-            .and_then(|conn| Self::create_table(table, &conn).map(|_| conn))
-            .map(|conn| Self {
-                conn,
-                table: table.into(),
-            })
+    /// Open the store in a new in-memory database
+    pub async fn in_memory(name: &str) -> anyhow::Result<Self> {
+        Self::open(super::database::in_memory().await?, name).await
+    }
+
+    /// Get the value for this key, if it exists and hasn't expired
+    pub async fn get<K, V>(&self, key: &K) -> anyhow::Result<Option<V>>
+    where
+        K: Serialize + ?Sized,
+        V: DeserializeOwned,
+    {
+        let sql = format!(
+            "SELECT value FROM {} WHERE key = ? AND {}",
+            self.table, LIVE
+        );
+        let row = sqlx::query(&sql)
+            .bind(encode(key)?)
+            .bind(now())
+            .fetch_optional(&mut &self.pool)
+            .await?;
+
+        row.map(|row| decode(&row.get::<Vec<u8>, _>(0))).transpose()
     }
 
-    #[allow(dead_code)]
-    pub fn in_memory(table: impl Into<Cow<'a, str>>) -> anyhow::Result<Self> {
-        let table = table.into();
-        let conn = database::get_in_memory(&table)?;
-        Self::create_table(&table, &conn)?;
-        Ok(Self { conn, table })
+    /// Set the value for this key, replacing any previous value
+    pub async fn set<K, V>(&self, key: &K, value: &V) -> anyhow::Result<()>
+    where
+        K: Serialize + ?Sized,
+        V: Serialize + ?Sized,
+    {
+        self.replace(key, value, None).await
     }
 
-    pub fn get<K: ?Sized, V>(&self, key: &K) -> Option<V>
+    /// Set the value for this key, it'll be removed after `ttl`
+    pub async fn set_with_ttl<K, V>(&self, key: &K, value: &V, ttl: Duration) -> anyhow::Result<()>
     where
-        K: serde::Serialize + std::fmt::Debug,
-        for<'de> V: serde::Deserialize<'de>,
+        K: Serialize + ?Sized,
+        V: Serialize + ?Sized,
     {
-        let k = serde_cbor::to_vec(&key).expect("valid key repr");
-        self.conn
-            .query_row_named(
-                &format!("SELECT value FROM {} WHERE key = :key", &self.table),
-                rusqlite::named_params![":key": &k],
-                |row| {
-                    let data = &row.get_unwrap::<_, Vec<u8>>("value");
-                    let val = serde_cbor::from_slice(data).expect("valid cbor");
-                    Ok(val)
-                },
-            )
-            .map_err(|err| {
-                log::warn!("cannot get key: {:?} -> {}", key, err);
-                err
-            })
-            .ok()
+        self.replace(key, value, Some(now() + ttl.as_secs() as i64))
+            .await
     }
 
-    pub fn set<K: ?Sized, V: ?Sized>(&self, key: &K, val: &V) -> anyhow::Result<()>
+    /// Remove this key, returning whether it existed
+    pub async fn remove<K>(&self, key: &K) -> anyhow::Result<bool>
     where
-        K: serde::Serialize + std::fmt::Debug,
-        V: serde::Serialize,
+        K: Serialize + ?Sized,
     {
-        let k = serde_cbor::to_vec(&key).expect("valid key repr");
-        let v = serde_cbor::to_vec(&val).expect("valid value repr");
-
-        self.conn
-            .execute_named(
-                &format!(
-                    "REPLACE INTO {} (key, value) values (:key, :value)",
-                    &self.table
-                ),
-                rusqlite::named_params! {
-                    ":key": &k,
-                    ":value": &v,
-                },
-            )
-            .map(|_| ())
-            .map_err(|err| {
-                log::warn!("cannot set key: {:?} -> ", err);
-                anyhow::Error::from(err)
-            })
+        let sql = format!("DELETE FROM {} WHERE key = ? AND {}", self.table, LIVE);
+        let n = sqlx::query(&sql)
+            .bind(encode(key)?)
+            .bind(now())
+            .execute(&mut &self.pool)
+            .await?;
+        Ok(n > 0)
     }
 
-    #[allow(dead_code)]
-    pub fn remove<K: ?Sized>(&self, key: &K) -> bool
+    /// Atomically set the value for this key if the current value is `expected`
+    ///
+    /// An `expected` of `None` means the key must not exist. Returns whether the value was set.
+    pub async fn compare_and_set<K, V>(
+        &self,
+        key: &K,
+        expected: Option<&V>,
+        value: &V,
+    ) -> anyhow::Result<bool>
     where
-        K: serde::Serialize + std::fmt::Debug,
+        K: Serialize + ?Sized,
+        V: Serialize + ?Sized,
     {
-        let k = serde_cbor::to_vec(&key).expect("valid key repr");
-        match self.conn.execute_named(
-            &format!("DELETE FROM {} WHERE key = :key", &self.table),
-            rusqlite::named_params! {
-                ":key": &k
-            },
-        ) {
-            Err(..) => {
-                log::debug!("error while removing key: {:?}", key);
-                false
-            }
-            Ok(0) => {
-                log::debug!("no row was removed for key: {:?}", key);
-                false
+        let key = encode(key)?;
+        let value = encode(value)?;
+
+        let mut tx = self.pool.begin().await?;
+
+        // an expired value is the same as a missing one
+        let sql = format!(
+            "DELETE FROM {} WHERE key = ? AND expires_at <= ?",
+            self.table
+        );
+        sqlx::query(&sql)
+            .bind(key.clone())
+            .bind(now())
+            .execute(&mut tx)
+            .await?;
+
+        let n = match expected {
+            Some(expected) => {
+                let sql = format!(
+                    "UPDATE {} SET value = ?, expires_at = NULL WHERE key = ? AND value = ?",
+                    self.table
+                );
+                sqlx::query(&sql)
+                    .bind(value)
+                    .bind(key)
+                    .bind(encode(expected)?)
+                    .execute(&mut tx)
+                    .await?
             }
-            Ok(..) => {
-                log::trace!("removed key: {:?}", key);
-                true
+            None => {
+                let sql = format!(
+                    "INSERT OR IGNORE INTO {} (key, value) VALUES (?, ?)",
+                    self.table
+                );
+                sqlx::query(&sql)
+                    .bind(key)
+                    .bind(value)
+                    .execute(&mut tx)
+                    .await?
             }
-        }
+        };
+
+        tx.commit().await?;
+        Ok(n == 1)
+    }
+
+    /// Get all of the keys and values
+    pub async fn iter<K, V>(&self) -> anyhow::Result<Vec<(K, V)>>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+    {
+        let sql = format!("SELECT key, value FROM {} WHERE {}", self.table, LIVE);
+        sqlx::query(&sql)
+            .bind(now())
+            .fetch_all(&mut &self.pool)
+            .await?
+            .into_iter()
+            .map(|row| {
+                let key = decode(&row.get::<Vec<u8>, _>(0))?;
+                let value = decode(&row.get::<Vec<u8>, _>(1))?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    /// Get all of the keys
+    pub async fn keys<K>(&self) -> anyhow::Result<Vec<K>>
+    where
+        K: DeserializeOwned,
+    {
+        self.column("key").await
+    }
+
+    /// Get all of the values
+    pub async fn values<V>(&self) -> anyhow::Result<Vec<V>>
+    where
+        V: DeserializeOwned,
+    {
+        self.column("value").await
+    }
+
+    /// How many (unexpired) keys are in the store
+    pub async fn len(&self) -> anyhow::Result<usize> {
+        let sql = format!("SELECT COUNT(*) FROM {} WHERE {}", self.table, LIVE);
+        let row = sqlx::query(&sql)
+            .bind(now())
+            .fetch_one(&mut &self.pool)
+            .await?;
+        Ok(row.get::<i64, _>(0) as _)
+    }
+
+    /// Whether the store has no (unexpired) keys
+    pub async fn is_empty(&self) -> anyhow::Result<bool> {
+        self.len().await.map(|len| len == 0)
     }
 
-    // iter
-    // select key, value from _table_
-    //
-    // values
-    // select value from _table_
-    //
-    // keys
-    // select key from _table_
-    //
-    // len
-    // select count(*) from _table_
+    /// Delete any expired keys, returning how many were removed
+    pub async fn purge_expired(&self) -> anyhow::Result<u64> {
+        let sql = format!("DELETE FROM {} WHERE expires_at <= ?", self.table);
+        sqlx::query(&sql)
+            .bind(now())
+            .execute(&mut &self.pool)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn replace<K, V>(&self, key: &K, value: &V, expires_at: Option<i64>) -> anyhow::Result<()>
+    where
+        K: Serialize + ?Sized,
+        V: Serialize + ?Sized,
+    {
+        let sql = format!(
+            "REPLACE INTO {} (key, value, expires_at) VALUES (?, ?, ?)",
+            self.table
+        );
+        sqlx::query(&sql)
+            .bind(encode(key)?)
+            .bind(encode(value)?)
+            .bind(expires_at)
+            .execute(&mut &self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn column<T>(&self, column: &str) -> anyhow::Result<Vec<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let sql = format!("SELECT {} FROM {} WHERE {}", column, self.table, LIVE);
+        sqlx::query(&sql)
+            .bind(now())
+            .fetch_all(&mut &self.pool)
+            .await?
+            .into_iter()
+            .map(|row| decode(&row.get::<Vec<u8>, _>(0)))
+            .collect()
+    }
+}
+
+// filters out expired rows, binds the current time
+const LIVE: &str = "(expires_at IS NULL OR expires_at > ?)";
+
+fn now() -> i64 {
+    time::OffsetDateTime::now().timestamp()
+}
+
+fn encode<T: Serialize + ?Sized>(data: &T) -> anyhow::Result<Vec<u8>> {
+    serde_cbor::to_vec(data).map_err(Into::into)
+}
+
+fn decode<T: DeserializeOwned>(data: &[u8]) -> anyhow::Result<T> {
+    serde_cbor::from_slice(data).map_err(Into::into)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    #[test]
-    fn key_value() {
-        #[derive(Debug, serde::Deserialize, serde::Serialize, PartialEq)]
-        struct Foo {
-            name: String,
-            size: usize,
-        }
 
-        let kv = KeyValueStore::fetch("testing").unwrap();
+    #[derive(Debug, serde::Deserialize, serde::Serialize, PartialEq)]
+    struct Foo {
+        name: String,
+        size: usize,
+    }
+
+    #[tokio::test]
+    async fn key_value() {
+        let kv = KeyValueStore::in_memory("testing").await.unwrap();
         kv.set(
             &42,
             &Foo {
@@ -153,25 +291,96 @@ mod tests {
                 size: 42,
             },
         )
+        .await
         .unwrap();
 
-        kv.set(&"asdf", &42).unwrap();
+        kv.set("asdf", &42).await.unwrap();
 
         assert_eq!(
-            kv.get::<_, Foo>(&42).unwrap(),
+            kv.get::<_, Foo>(&42).await.unwrap().unwrap(),
             Foo {
                 name: "this is a name".to_string(),
                 size: 42,
             }
         );
 
-        assert_eq!(kv.get::<_, i64>(&"asdf").unwrap(), 42);
+        assert_eq!(kv.get::<_, i64>("asdf").await.unwrap().unwrap(), 42);
+
+        assert!(kv.remove("asdf").await.unwrap());
+        assert!(kv.get::<_, i64>("asdf").await.unwrap().is_none());
+        assert!(!kv.remove("asdf").await.unwrap());
+
+        kv.set("asdf", &42).await.unwrap();
+        kv.set("asdf", &43).await.unwrap();
+        assert_eq!(kv.get::<_, i64>("asdf").await.unwrap().unwrap(), 43);
+    }
+
+    #[tokio::test]
+    async fn iter() {
+        let kv = KeyValueStore::in_memory("testing").await.unwrap();
+        assert!(kv.is_empty().await.unwrap());
+
+        for (k, v) in &[("a", 1), ("b", 2), ("c", 3)] {
+            kv.set(*k, v).await.unwrap();
+        }
+        assert_eq!(kv.len().await.unwrap(), 3);
+
+        let mut pairs = kv.iter::<String, i32>().await.unwrap();
+        pairs.sort();
+        assert_eq!(
+            pairs,
+            vec![("a".into(), 1), ("b".into(), 2), ("c".into(), 3)]
+        );
+
+        let mut keys = kv.keys::<String>().await.unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["a", "b", "c"]);
 
-        assert!(kv.remove(&"asdf"));
-        assert!(kv.get::<_, i64>(&"asdf").is_none());
-        assert!(!kv.remove(&"asdf"));
+        let mut values = kv.values::<i32>().await.unwrap();
+        values.sort();
+        assert_eq!(values, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn ttl() {
+        let kv = KeyValueStore::in_memory("testing").await.unwrap();
+        kv.set_with_ttl("gone", &1, Duration::from_secs(0))
+            .await
+            .unwrap();
+        kv.set_with_ttl("here", &2, Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        assert!(kv.get::<_, i32>("gone").await.unwrap().is_none());
+        assert_eq!(kv.get::<_, i32>("here").await.unwrap(), Some(2));
+        assert_eq!(kv.len().await.unwrap(), 1);
+
+        assert_eq!(kv.purge_expired().await.unwrap(), 1);
+        assert_eq!(kv.len().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn compare_and_set() {
+        let kv = KeyValueStore::in_memory("testing").await.unwrap();
+
+        assert!(kv.compare_and_set("count", None, &1).await.unwrap());
+        assert!(!kv.compare_and_set("count", None, &1).await.unwrap());
+
+        assert!(!kv.compare_and_set("count", Some(&0), &2).await.unwrap());
+        assert!(kv.compare_and_set("count", Some(&1), &2).await.unwrap());
+        assert_eq!(kv.get::<_, i32>("count").await.unwrap(), Some(2));
+
+        // expired keys are treated as missing
+        kv.set_with_ttl("count", &3, Duration::from_secs(0))
+            .await
+            .unwrap();
+        assert!(kv.compare_and_set("count", None, &4).await.unwrap());
+        assert_eq!(kv.get::<_, i32>("count").await.unwrap(), Some(4));
+    }
 
-        kv.set(&"asdf", &42).unwrap();
-        kv.set(&"asdf", &42).unwrap();
+    #[tokio::test]
+    async fn invalid_name() {
+        assert!(KeyValueStore::in_memory("drop table").await.is_err());
+        assert!(KeyValueStore::in_memory("").await.is_err());
     }
 }
@@ -0,0 +1,29 @@
+-- store the owner as an integer and the creation time as a unix timestamp
+CREATE TABLE user_commands_new (
+    id         INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT UNIQUE,
+    name       TEXT NOT NULL,
+    body       TEXT NOT NULL,
+    room       INTEGER NOT NULL,
+    uses       INTEGER NOT NULL,
+    owner      INTEGER NOT NULL,
+    disabled   BOOLEAN NOT NULL,
+    created_at INTEGER NOT NULL,
+    UNIQUE(room, name)
+);
+
+-- created_at was formatted as '%F %T %z', the offset was always UTC
+INSERT INTO user_commands_new (id, name, body, room, uses, owner, disabled, created_at)
+SELECT
+    id,
+    name,
+    body,
+    room,
+    uses,
+    CAST(owner AS INTEGER),
+    disabled,
+    CAST(strftime('%s', substr(CAST(created_at AS TEXT), 1, 19)) AS INTEGER)
+FROM
+    user_commands;
+
+DROP TABLE user_commands;
+ALTER TABLE user_commands_new RENAME TO user_commands;
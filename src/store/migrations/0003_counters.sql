@@ -0,0 +1,6 @@
+CREATE TABLE counters (
+    room  INTEGER NOT NULL,
+    name  TEXT NOT NULL,
+    value INTEGER NOT NULL,
+    PRIMARY KEY(room, name)
+);
@@ -0,0 +1,3 @@
+-- the minimum role needed to use a user command, and its cooldown in seconds
+ALTER TABLE user_commands ADD COLUMN role TEXT NOT NULL DEFAULT 'everyone';
+ALTER TABLE user_commands ADD COLUMN cooldown INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,19 @@
+-- every change made to a user command, with the command as it was before the change
+CREATE TABLE command_history (
+    id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+    room                INTEGER NOT NULL,
+    name                TEXT NOT NULL,
+    action              TEXT NOT NULL,
+    actor               INTEGER NOT NULL,
+    at                  INTEGER NOT NULL,
+    previous_name       TEXT,
+    previous_body       TEXT,
+    previous_uses       INTEGER,
+    previous_owner      INTEGER,
+    previous_disabled   BOOLEAN,
+    previous_created_at INTEGER,
+    previous_role       TEXT,
+    previous_cooldown   INTEGER
+);
+
+CREATE INDEX command_history_name ON command_history (room, name);
@@ -0,0 +1,14 @@
+-- global commands apply to every room that doesn't have its own command with that name
+ALTER TABLE user_commands ADD COLUMN global BOOLEAN NOT NULL DEFAULT 0;
+
+-- the other rooms a command is shared with
+CREATE TABLE command_shares (
+    command_id INTEGER NOT NULL,
+    room       INTEGER NOT NULL,
+    PRIMARY KEY (command_id, room)
+);
+
+CREATE TRIGGER command_shares_cleanup AFTER DELETE ON user_commands
+BEGIN
+    DELETE FROM command_shares WHERE command_id = old.id;
+END;
@@ -0,0 +1,15 @@
+-- phrases that get an automatic response in a room
+CREATE TABLE user_triggers (
+    id         INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT UNIQUE,
+    name       TEXT NOT NULL,
+    -- either 'keyword' or 'regex'
+    kind       TEXT NOT NULL,
+    pattern    TEXT NOT NULL,
+    body       TEXT NOT NULL,
+    room       INTEGER NOT NULL,
+    owner      INTEGER NOT NULL,
+    cooldown   INTEGER NOT NULL,
+    disabled   BOOLEAN NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL,
+    UNIQUE(room, name)
+);
@@ -0,0 +1,6 @@
+-- more bodies for a command, stored as a json array of strings, and how they're used
+ALTER TABLE user_commands ADD COLUMN variants TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE user_commands ADD COLUMN mode TEXT NOT NULL DEFAULT 'random';
+
+ALTER TABLE command_history ADD COLUMN previous_variants TEXT;
+ALTER TABLE command_history ADD COLUMN previous_mode TEXT;
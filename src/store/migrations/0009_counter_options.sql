@@ -0,0 +1,3 @@
+-- counters that go back to 0 when a stream starts, and when they were last reset
+ALTER TABLE counters ADD COLUMN per_stream BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE counters ADD COLUMN reset_at INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,16 @@
+-- quotes are numbered per room, in the order they were added
+CREATE TABLE quotes (
+    id         INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT UNIQUE,
+    room       INTEGER NOT NULL,
+    number     INTEGER NOT NULL,
+    body       TEXT NOT NULL,
+    -- who said it, and their user id when we know it
+    author     TEXT NOT NULL,
+    author_id  INTEGER,
+    added_by   INTEGER NOT NULL,
+    -- what the stream was doing when it was added
+    game       TEXT,
+    title      TEXT,
+    created_at INTEGER NOT NULL,
+    UNIQUE(room, number)
+);
@@ -0,0 +1,33 @@
+-- the chat of the rooms that opted in to being archived
+CREATE TABLE chat_log (
+    id      INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT UNIQUE,
+    room    INTEGER NOT NULL,
+    channel TEXT NOT NULL,
+    user_id INTEGER NOT NULL,
+    login   TEXT NOT NULL,
+    name    TEXT NOT NULL,
+    -- the id twitch gave the message
+    msg_id  TEXT,
+    data    TEXT NOT NULL,
+    at      INTEGER NOT NULL
+);
+
+CREATE INDEX chat_log_room_at ON chat_log (room, at);
+CREATE INDEX chat_log_login ON chat_log (room, login, at);
+
+-- the full-text index only has the messages, the rows stay in chat_log
+CREATE VIRTUAL TABLE chat_log_fts USING fts5 (
+    data,
+    content = 'chat_log',
+    content_rowid = 'id'
+);
+
+CREATE TRIGGER chat_log_insert AFTER INSERT ON chat_log
+BEGIN
+    INSERT INTO chat_log_fts (rowid, data) VALUES (new.id, new.data);
+END;
+
+CREATE TRIGGER chat_log_delete AFTER DELETE ON chat_log
+BEGIN
+    INSERT INTO chat_log_fts (chat_log_fts, rowid, data) VALUES ('delete', old.id, old.data);
+END;
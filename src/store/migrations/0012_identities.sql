@@ -0,0 +1,23 @@
+-- the twitch users (and rooms) the bot has seen, by their user id
+CREATE TABLE identities (
+    id           INTEGER NOT NULL PRIMARY KEY,
+    login        TEXT NOT NULL,
+    display_name TEXT NOT NULL,
+    first_seen   INTEGER NOT NULL,
+    -- when the current name was first seen
+    renamed_at   INTEGER NOT NULL
+);
+
+CREATE INDEX identities_login ON identities (login);
+
+-- the names each user had before their current one
+CREATE TABLE identity_names (
+    id           INTEGER NOT NULL,
+    login        TEXT NOT NULL,
+    display_name TEXT NOT NULL,
+    -- when this name stopped being used
+    until        INTEGER NOT NULL
+);
+
+CREATE INDEX identity_names_id ON identity_names (id);
+CREATE INDEX identity_names_login ON identity_names (login);
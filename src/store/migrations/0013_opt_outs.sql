@@ -0,0 +1,5 @@
+-- the users that don't want the bot to keep anything about them
+CREATE TABLE opt_outs (
+    user_id INTEGER NOT NULL PRIMARY KEY,
+    at      INTEGER NOT NULL
+);
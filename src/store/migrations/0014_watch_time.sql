@@ -0,0 +1,11 @@
+-- how long each user has been in a room while it was live, in seconds
+CREATE TABLE watch_time (
+    room       INTEGER NOT NULL,
+    user_id    INTEGER NOT NULL,
+    seconds    INTEGER NOT NULL DEFAULT 0,
+    first_seen INTEGER NOT NULL,
+    last_seen  INTEGER NOT NULL,
+    PRIMARY KEY (room, user_id)
+);
+
+CREATE INDEX watch_time_seconds ON watch_time (room, seconds);
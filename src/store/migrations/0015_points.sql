@@ -0,0 +1,26 @@
+-- the points each user has in a room
+CREATE TABLE points (
+    room    INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    balance INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (room, user_id)
+);
+
+CREATE INDEX points_balance ON points (room, balance);
+
+-- every change made to the points, so the balances can be audited
+CREATE TABLE points_ledger (
+    id      INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT UNIQUE,
+    room    INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    amount  INTEGER NOT NULL,
+    -- the balance after the change
+    balance INTEGER NOT NULL,
+    -- why it changed (e.g. 'watching', 'chatting', 'give')
+    reason  TEXT NOT NULL,
+    -- who made the change, if it wasn't the bot
+    actor   INTEGER,
+    at      INTEGER NOT NULL
+);
+
+CREATE INDEX points_ledger_user ON points_ledger (room, user_id, at);
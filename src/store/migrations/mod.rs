@@ -0,0 +1,256 @@
+use anyhow::Context as _;
+use sqlx::{prelude::*, SqlitePool};
+use std::path::{Path, PathBuf};
+
+/// A change to the database schema
+#[derive(Debug)]
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    sql: &'static str,
+}
+
+macro_rules! migration {
+    ($version:expr, $name:expr) => {
+        Migration {
+            version: $version,
+            name: $name,
+            sql: include_str!(concat!($name, ".sql")),
+        }
+    };
+}
+
+/// All of the migrations, in the order they are applied
+///
+/// Never change or reorder these once they've been released, add a new one instead
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_user_commands"),
+    migration!(2, "0002_integer_owner"),
+    migration!(3, "0003_counters"),
+    migration!(4, "0004_command_restrictions"),
+    migration!(5, "0005_command_history"),
+    migration!(6, "0006_shared_commands"),
+    migration!(7, "0007_user_triggers"),
+    migration!(8, "0008_command_variants"),
+    migration!(9, "0009_counter_options"),
+    migration!(10, "0010_quotes"),
+    migration!(11, "0011_chat_log"),
+    migration!(12, "0012_identities"),
+    migration!(13, "0013_opt_outs"),
+    migration!(14, "0014_watch_time"),
+    migration!(15, "0015_points"),
+];
+
+/// The schema version this build expects
+pub fn latest() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or_default()
+}
+
+/// A migration that has been applied to the database
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Applied {
+    pub version: i64,
+    pub name: String,
+    /// Unix timestamp of when it was applied
+    pub applied_at: i64,
+}
+
+/// The migration state of a database
+#[derive(Debug)]
+pub struct Status {
+    pub applied: Vec<Applied>,
+    pub pending: Vec<&'static Migration>,
+}
+
+impl Status {
+    /// The current schema version, `0` if nothing has been applied
+    pub fn version(&self) -> i64 {
+        self.applied.last().map(|m| m.version).unwrap_or_default()
+    }
+}
+
+/// Get the migration status of the database
+pub async fn status(pool: &SqlitePool) -> anyhow::Result<Status> {
+    pool.acquire()
+        .await?
+        .execute(include_str!("create_schema_version.sql"))
+        .await?;
+    read_status(pool).await
+}
+
+/// Get the migration status of the database, without changing it
+///
+/// This works on a database that was opened read-only
+pub async fn read_status(pool: &SqlitePool) -> anyhow::Result<Status> {
+    let row = sqlx::query(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'schema_version'",
+    )
+    .fetch_one(&mut &*pool)
+    .await?;
+
+    let applied = if row.get::<i64, _>(0) > 0 {
+        sqlx::query_as::<_, Applied>(
+            "SELECT version, name, applied_at FROM schema_version ORDER BY version",
+        )
+        .fetch_all(&mut &*pool)
+        .await?
+    } else {
+        vec![]
+    };
+
+    let version = applied.last().map(|m| m.version).unwrap_or_default();
+    anyhow::ensure!(
+        version <= latest(),
+        "the database is at schema version {} but this build only knows up to version {}",
+        version,
+        latest()
+    );
+
+    let pending = MIGRATIONS.iter().filter(|m| m.version > version).collect();
+    Ok(Status { applied, pending })
+}
+
+/// Apply any pending migrations, returning the ones that were applied
+///
+/// If `db_file` is provided and it has existing data, it'll be backed up next
+/// to the database file before anything is changed.
+pub async fn upgrade(
+    pool: &SqlitePool,
+    db_file: Option<&Path>,
+) -> anyhow::Result<Vec<&'static Migration>> {
+    let has_data = has_data(pool).await?;
+    let status = status(pool).await?;
+    if status.pending.is_empty() {
+        return Ok(vec![]);
+    }
+
+    if let (Some(db_file), true) = (db_file, has_data) {
+        let backup = backup_path(db_file, status.version());
+        log::info!(
+            "backing up the database to {} before migrating",
+            backup.display()
+        );
+        super::database::backup(pool, &backup).await?;
+    }
+
+    for migration in &status.pending {
+        log::info!(
+            "applying migration {}: {}",
+            migration.version,
+            migration.name
+        );
+
+        let mut tx = pool.begin().await?;
+        tx.execute(migration.sql)
+            .await
+            .with_context(|| format!("cannot apply migration: {}", migration.name))?;
+
+        sqlx::query("INSERT INTO schema_version (version, name, applied_at) VALUES (?, ?, ?)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .bind(time::OffsetDateTime::now().timestamp())
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+    }
+
+    Ok(status.pending)
+}
+
+async fn has_data(pool: &SqlitePool) -> anyhow::Result<bool> {
+    let row = sqlx::query(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name != 'schema_version'",
+    )
+    .fetch_one(&mut &*pool)
+    .await?;
+    Ok(row.get::<i64, _>(0) > 0)
+}
+
+fn backup_path(db_file: &Path, version: i64) -> PathBuf {
+    let mut name = db_file.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(
+        ".v{}-{}.bak",
+        version,
+        time::OffsetDateTime::now().timestamp()
+    ));
+    db_file.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn upgrade_fresh() {
+        let pool = crate::database::connect_in_memory().await.unwrap();
+        assert_eq!(status(&pool).await.unwrap().version(), 0);
+
+        let applied = upgrade(&pool, None).await.unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+
+        let status = status(&pool).await.unwrap();
+        assert_eq!(status.version(), latest());
+        assert!(status.pending.is_empty());
+
+        // nothing left to do
+        assert!(upgrade(&pool, None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn upgrade_existing() {
+        let pool = crate::database::connect_in_memory().await.unwrap();
+
+        // a database from before migrations existed
+        let mut conn = pool.acquire().await.unwrap();
+        conn.execute(MIGRATIONS[0].sql).await.unwrap();
+        sqlx::query(
+            "INSERT INTO user_commands (name, body, room, uses, owner, disabled, created_at)
+             VALUES ('!hello', 'world', 1234, 3, '4321', 0, '2020-04-01 12:34:56 +0000')",
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        drop(conn);
+
+        assert!(has_data(&pool).await.unwrap());
+        upgrade(&pool, None).await.unwrap();
+
+        let row = sqlx::query("SELECT owner, created_at FROM user_commands WHERE name = '!hello'")
+            .fetch_one(&mut &pool)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<i64, _>(0), 4321);
+        assert_eq!(row.get::<i64, _>(1), 1_585_744_496);
+    }
+
+    #[tokio::test]
+    async fn read_status_unchanged() {
+        let pool = crate::database::connect_in_memory().await.unwrap();
+        assert_eq!(read_status(&pool).await.unwrap().version(), 0);
+        assert_eq!(
+            read_status(&pool).await.unwrap().pending.len(),
+            MIGRATIONS.len()
+        );
+
+        let row = sqlx::query("SELECT COUNT(*) FROM sqlite_master")
+            .fetch_one(&mut &pool)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<i64, _>(0), 0);
+
+        upgrade(&pool, None).await.unwrap();
+        let status = read_status(&pool).await.unwrap();
+        assert_eq!(status.version(), latest());
+        assert!(status.pending.is_empty());
+    }
+
+    #[test]
+    fn ordered() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i64 + 1, "{}", migration.name);
+            assert!(migration
+                .name
+                .starts_with(&format!("{:04}_", migration.version)));
+        }
+    }
+}
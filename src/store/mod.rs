@@ -1,4 +1,21 @@
-// pub mod kv;
+pub mod database;
+pub mod export;
+pub mod migrations;
+
+mod kv;
+pub use kv::KeyValueStore;
+
+mod channels;
+pub use channels::{ChannelState, Channels, DeliveryError, Permit};
+
+mod chat;
+pub use chat::{ChatHistory, ChatMessage, ChatQuery};
+
+mod chat_log;
+pub use chat_log::{ArchivedMessage, ChatArchive};
+
+mod counters;
+pub use counters::{Counter, CounterOverflow, Counters};
 
 pub mod resolver;
 pub use resolver::Resolver;
@@ -6,5 +23,14 @@ pub use resolver::Resolver;
 mod state;
 pub use state::State;
 
-// mod tracker;
-// pub use tracker::Tracker;
+mod tracker;
+pub use tracker::{Identity, PastName, Tracker};
+
+mod privacy;
+pub use privacy::{forget_user, Forgotten, OptOuts, FORGOTTEN_USER};
+
+mod watch_time;
+pub use watch_time::{Presence, WatchTime};
+
+mod points;
+pub use points::{Balance, LedgerEntry, Wallets};
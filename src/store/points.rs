@@ -0,0 +1,380 @@
+use sqlx::{prelude::*, SqlitePool};
+use std::convert::TryFrom;
+
+/// A change made to someone's points
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct LedgerEntry {
+    pub id: i64,
+    pub room: i64,
+    pub user_id: i64,
+    pub amount: i64,
+    /// The balance after the change
+    pub balance: i64,
+    pub reason: String,
+    /// Who made the change, if it wasn't the bot
+    pub actor: Option<i64>,
+    /// Unix timestamp of when it was changed
+    pub at: i64,
+}
+
+/// How many points a user has in a room
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Balance {
+    pub room: i64,
+    pub user_id: i64,
+    pub balance: i64,
+}
+
+/// The points of each user, per room
+///
+/// Every change is written to a ledger in the same transaction as the balance,
+/// and a balance never goes below 0.
+#[derive(Clone)]
+pub struct Wallets {
+    pool: SqlitePool,
+}
+
+impl std::fmt::Debug for Wallets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Wallets").finish()
+    }
+}
+
+/// One change to apply in [`Wallets::apply`](./struct.Wallets.html#method.apply)
+#[derive(Debug, Clone)]
+struct Change<'a> {
+    user: u64,
+    amount: i64,
+    reason: &'a str,
+    actor: Option<u64>,
+    /// Take what's there instead of failing when there isn't enough
+    clamp: bool,
+}
+
+impl Wallets {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn balance(&self, room: u64, user: u64) -> anyhow::Result<i64> {
+        let row = sqlx::query("SELECT balance FROM points WHERE room = ? AND user_id = ?")
+            .bind(room as i64)
+            .bind(user as i64)
+            .fetch_optional(&mut &self.pool)
+            .await?;
+        Ok(row.map(|row| row.get::<i64, _>(0)).unwrap_or(0))
+    }
+
+    /// Give the user points, returning their new balance
+    pub async fn award(
+        &self,
+        room: u64,
+        user: u64,
+        amount: u64,
+        reason: &str,
+        actor: Option<u64>,
+    ) -> anyhow::Result<i64> {
+        let change = Change {
+            user,
+            amount: signed(amount)?,
+            reason,
+            actor,
+            clamp: false,
+        };
+        let balances = self.apply(room, &[change]).await?;
+        Ok(balances.map(|b| b[0]).unwrap_or_default())
+    }
+
+    /// Give each of the users their points, all at once
+    pub async fn award_many(
+        &self,
+        room: u64,
+        awards: &[(u64, u64)],
+        reason: &str,
+    ) -> anyhow::Result<()> {
+        let changes = awards
+            .iter()
+            .map(|&(user, amount)| {
+                Ok(Change {
+                    user,
+                    amount: signed(amount)?,
+                    reason,
+                    actor: None,
+                    clamp: false,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.apply(room, &changes).await.map(drop)
+    }
+
+    /// Spend the user's points, returning their new balance
+    ///
+    /// This returns `None`, and changes nothing, if they don't have enough
+    pub async fn charge(
+        &self,
+        room: u64,
+        user: u64,
+        amount: u64,
+        reason: &str,
+        actor: Option<u64>,
+    ) -> anyhow::Result<Option<i64>> {
+        let change = Change {
+            user,
+            amount: -signed(amount)?,
+            reason,
+            actor,
+            clamp: false,
+        };
+        Ok(self.apply(room, &[change]).await?.map(|b| b[0]))
+    }
+
+    /// Take up to `amount` of the user's points, returning their new balance
+    pub async fn take(
+        &self,
+        room: u64,
+        user: u64,
+        amount: u64,
+        reason: &str,
+        actor: Option<u64>,
+    ) -> anyhow::Result<i64> {
+        let change = Change {
+            user,
+            amount: -signed(amount)?,
+            reason,
+            actor,
+            clamp: true,
+        };
+        let balances = self.apply(room, &[change]).await?;
+        Ok(balances.map(|b| b[0]).unwrap_or_default())
+    }
+
+    /// Move points from one user to another, returning both new balances
+    ///
+    /// This returns `None`, and changes nothing, if `from` doesn't have enough
+    pub async fn transfer(
+        &self,
+        room: u64,
+        from: u64,
+        to: u64,
+        amount: u64,
+    ) -> anyhow::Result<Option<(i64, i64)>> {
+        let changes = [
+            Change {
+                user: from,
+                amount: -signed(amount)?,
+                reason: "give",
+                actor: Some(from),
+                clamp: false,
+            },
+            Change {
+                user: to,
+                amount: signed(amount)?,
+                reason: "give",
+                actor: Some(from),
+                clamp: false,
+            },
+        ];
+        Ok(self.apply(room, &changes).await?.map(|b| (b[0], b[1])))
+    }
+
+    /// The users with the most points in the room, most first
+    pub async fn top(&self, room: u64, limit: i64) -> anyhow::Result<Vec<Balance>> {
+        sqlx::query_as::<_, Balance>(
+            "SELECT room, user_id, balance FROM points
+             WHERE room = ? AND balance > 0
+             ORDER BY balance DESC, user_id
+             LIMIT ?",
+        )
+        .bind(room as i64)
+        .bind(limit)
+        .fetch_all(&mut &self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// The most recent changes to the user's points, newest first
+    pub async fn ledger(
+        &self,
+        room: u64,
+        user: u64,
+        limit: i64,
+    ) -> anyhow::Result<Vec<LedgerEntry>> {
+        sqlx::query_as::<_, LedgerEntry>(
+            "SELECT id, room, user_id, amount, balance, reason, actor, at FROM points_ledger
+             WHERE room = ? AND user_id = ?
+             ORDER BY id DESC
+             LIMIT ?",
+        )
+        .bind(room as i64)
+        .bind(user as i64)
+        .bind(limit)
+        .fetch_all(&mut &self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Apply all of the changes in one transaction, returning the new balances in order
+    ///
+    /// If any of them would leave a balance below 0 nothing is changed and this returns `None`
+    async fn apply(&self, room: u64, changes: &[Change<'_>]) -> anyhow::Result<Option<Vec<i64>>> {
+        let now = time::OffsetDateTime::now().timestamp();
+        let mut tx = self.pool.begin().await?;
+        let mut balances = Vec::with_capacity(changes.len());
+
+        for change in changes {
+            let balance = sqlx::query("SELECT balance FROM points WHERE room = ? AND user_id = ?")
+                .bind(room as i64)
+                .bind(change.user as i64)
+                .fetch_optional(&mut tx)
+                .await?
+                .map(|row| row.get::<i64, _>(0))
+                .unwrap_or(0);
+
+            let next = balance.checked_add(change.amount).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} points can't be added to a balance of {}",
+                    change.amount,
+                    balance
+                )
+            })?;
+            let amount = match next {
+                n if n >= 0 => change.amount,
+                _ if change.clamp => -balance,
+                // dropping the transaction rolls it back
+                _ => return Ok(None),
+            };
+            let balance = balance + amount;
+
+            sqlx::query("INSERT OR REPLACE INTO points (room, user_id, balance) VALUES (?, ?, ?)")
+                .bind(room as i64)
+                .bind(change.user as i64)
+                .bind(balance)
+                .execute(&mut tx)
+                .await?;
+
+            sqlx::query(
+                "INSERT INTO points_ledger (room, user_id, amount, balance, reason, actor, at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(room as i64)
+            .bind(change.user as i64)
+            .bind(amount)
+            .bind(balance)
+            .bind(change.reason)
+            .bind(change.actor.map(|id| id as i64))
+            .bind(now)
+            .execute(&mut tx)
+            .await?;
+
+            balances.push(balance);
+        }
+
+        tx.commit().await?;
+        Ok(Some(balances))
+    }
+}
+
+/// Amounts are stored as an `i64`, anything bigger is an error rather than wrapping around
+fn signed(amount: u64) -> anyhow::Result<i64> {
+    i64::try_from(amount).map_err(|_| anyhow::anyhow!("{} is too many points", amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn wallets() {
+        let pool = crate::database::in_memory().await.unwrap();
+        let wallets = Wallets::new(pool);
+
+        assert_eq!(wallets.balance(1, 42).await.unwrap(), 0);
+        assert_eq!(
+            wallets.award(1, 42, 100, "watching", None).await.unwrap(),
+            100
+        );
+        wallets
+            .award_many(1, &[(42, 10), (43, 5)], "chatting")
+            .await
+            .unwrap();
+        assert_eq!(wallets.balance(1, 42).await.unwrap(), 110);
+        assert_eq!(wallets.balance(1, 43).await.unwrap(), 5);
+        assert_eq!(wallets.balance(2, 42).await.unwrap(), 0);
+
+        assert_eq!(wallets.charge(1, 43, 10, "song", None).await.unwrap(), None);
+        assert_eq!(
+            wallets.charge(1, 43, 5, "song", None).await.unwrap(),
+            Some(0)
+        );
+
+        assert_eq!(wallets.transfer(1, 43, 42, 1).await.unwrap(), None);
+        assert_eq!(
+            wallets.transfer(1, 42, 43, 10).await.unwrap(),
+            Some((100, 10))
+        );
+
+        assert_eq!(wallets.take(1, 43, 50, "remove", Some(1)).await.unwrap(), 0);
+
+        let top = wallets.top(1, 10).await.unwrap();
+        assert_eq!(
+            top.iter()
+                .map(|b| (b.user_id, b.balance))
+                .collect::<Vec<_>>(),
+            vec![(42, 100)]
+        );
+
+        // the failed changes aren't in the ledger, and a clamped one only has what was taken
+        let ledger = wallets.ledger(1, 43, 10).await.unwrap();
+        assert_eq!(
+            ledger
+                .iter()
+                .map(|e| (e.reason.as_str(), e.amount, e.balance))
+                .collect::<Vec<_>>(),
+            vec![
+                ("remove", -10, 0),
+                ("give", 10, 10),
+                ("song", -5, 0),
+                ("chatting", 5, 5)
+            ]
+        );
+        assert_eq!(ledger[0].actor, Some(1));
+        assert_eq!(wallets.ledger(1, 42, 10).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn huge_amounts() {
+        let pool = crate::database::in_memory().await.unwrap();
+        let wallets = Wallets::new(pool);
+        wallets.award(1, 42, 100, "watching", None).await.unwrap();
+
+        // these would wrap around to negative amounts
+        let huge = u64::MAX - 99;
+        assert!(wallets.award(1, 42, huge, "moderator", None).await.is_err());
+        assert!(wallets.transfer(1, 42, 43, huge).await.is_err());
+        assert!(wallets.charge(1, 42, huge, "song", None).await.is_err());
+        assert!(wallets
+            .take(1, 42, u64::MAX, "moderator", None)
+            .await
+            .is_err());
+        assert!(wallets
+            .award_many(1, &[(43, 1), (42, huge)], "watching")
+            .await
+            .is_err());
+
+        // these fit, but the balance can't hold them
+        let max = i64::MAX as u64;
+        assert!(wallets.award(1, 42, max, "moderator", None).await.is_err());
+        assert_eq!(wallets.transfer(1, 42, 43, max).await.unwrap(), None);
+        assert_eq!(
+            wallets.award(1, 43, max, "moderator", None).await.unwrap(),
+            i64::MAX
+        );
+        assert!(wallets.transfer(1, 42, 43, 1).await.is_err());
+
+        // nothing changed, and nothing was written to the ledger
+        assert_eq!(wallets.balance(1, 42).await.unwrap(), 100);
+        assert_eq!(wallets.balance(1, 43).await.unwrap(), i64::MAX);
+        assert_eq!(wallets.ledger(1, 42, 10).await.unwrap().len(), 1);
+        assert_eq!(wallets.ledger(1, 43, 10).await.unwrap().len(), 1);
+    }
+}
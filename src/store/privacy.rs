@@ -0,0 +1,261 @@
+use sqlx::{prelude::*, SqlitePool};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+/// The users that opted out of the bot keeping anything about them
+///
+/// Their chat isn't logged, isn't used to generate text and they aren't greeted.
+#[derive(Clone)]
+pub struct OptOuts {
+    pool: SqlitePool,
+    users: Arc<Mutex<HashSet<u64>>>,
+}
+
+impl std::fmt::Debug for OptOuts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OptOuts")
+            .field("users", &self.users.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl OptOuts {
+    pub async fn load(pool: SqlitePool) -> anyhow::Result<Self> {
+        let users = sqlx::query("SELECT user_id FROM opt_outs")
+            .fetch_all(&mut &pool)
+            .await?
+            .into_iter()
+            .map(|row| row.get::<i64, _>(0) as u64)
+            .collect();
+
+        Ok(Self {
+            pool,
+            users: Arc::new(Mutex::new(users)),
+        })
+    }
+
+    pub fn contains(&self, user: u64) -> bool {
+        self.users.lock().unwrap().contains(&user)
+    }
+
+    /// This returns `false` if the user had already opted out
+    pub async fn opt_out(&self, user: u64) -> anyhow::Result<bool> {
+        if self.contains(user) {
+            return Ok(false);
+        }
+
+        sqlx::query("INSERT OR IGNORE INTO opt_outs (user_id, at) VALUES (?, ?)")
+            .bind(user as i64)
+            .bind(time::OffsetDateTime::now().timestamp())
+            .execute(&mut &self.pool)
+            .await?;
+        Ok(self.users.lock().unwrap().insert(user))
+    }
+
+    /// This returns `false` if the user hadn't opted out
+    pub async fn opt_in(&self, user: u64) -> anyhow::Result<bool> {
+        if !self.contains(user) {
+            return Ok(false);
+        }
+
+        sqlx::query("DELETE FROM opt_outs WHERE user_id = ?")
+            .bind(user as i64)
+            .execute(&mut &self.pool)
+            .await?;
+        Ok(self.users.lock().unwrap().remove(&user))
+    }
+}
+
+/// Stands in for a forgotten user in the rows that are kept for someone else
+///
+/// Twitch doesn't give out 0 as a user id, and these columns can't be `NULL`
+pub const FORGOTTEN_USER: i64 = 0;
+
+/// Every stored row that belongs to a user, as `(table, column)`
+///
+/// The opt-out itself is kept, so the user stays forgotten.
+const USER_COLUMNS: &[(&str, &str)] = &[
+    ("chat_log", "user_id"),
+    ("quotes", "author_id"),
+    ("user_commands", "owner"),
+    ("user_triggers", "owner"),
+    ("identities", "id"),
+    ("identity_names", "id"),
+    ("watch_time", "user_id"),
+    ("points", "user_id"),
+    ("points_ledger", "user_id"),
+];
+
+/// The rows that only say what a user did to someone else's data, as `(table, column, replacement)`
+///
+/// These are kept for the other user, with the reference to this one replaced.
+const ACTOR_COLUMNS: &[(&str, &str, Option<i64>)] = &[
+    ("quotes", "added_by", Some(FORGOTTEN_USER)),
+    ("command_history", "actor", Some(FORGOTTEN_USER)),
+    // `NULL` would mean the actor owned it
+    ("command_history", "previous_owner", Some(FORGOTTEN_USER)),
+    ("points_ledger", "actor", None),
+];
+
+/// How many rows in each table were changed by [`forget_user`](./fn.forget_user.html)
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Forgotten {
+    pub deleted: Vec<(&'static str, u64)>,
+    pub anonymized: Vec<(&'static str, u64)>,
+}
+
+/// Delete every stored row that belongs to the user, and remove them from the rest
+///
+/// The counts are in the order the tables were changed.
+pub async fn forget_user(pool: &SqlitePool, user: u64) -> anyhow::Result<Forgotten> {
+    fn add(counts: &mut Vec<(&'static str, u64)>, table: &'static str, n: u64) {
+        match counts.iter_mut().find(|(t, _)| *t == table) {
+            Some((_, count)) => *count += n,
+            None => counts.push((table, n)),
+        }
+    }
+
+    let mut tx = pool.begin().await?;
+    let mut forgotten = Forgotten::default();
+
+    for (table, column) in USER_COLUMNS {
+        let n = sqlx::query(&format!("DELETE FROM {} WHERE {} = ?", table, column))
+            .bind(user as i64)
+            .execute(&mut tx)
+            .await?;
+        add(&mut forgotten.deleted, table, n);
+    }
+
+    for (table, column, replacement) in ACTOR_COLUMNS {
+        let query = format!("UPDATE {} SET {} = ? WHERE {} = ?", table, column, column);
+        let n = sqlx::query(&query)
+            .bind(*replacement)
+            .bind(user as i64)
+            .execute(&mut tx)
+            .await?;
+        add(&mut forgotten.anonymized, table, n);
+    }
+
+    tx.commit().await?;
+    Ok(forgotten)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn opt_out() {
+        let pool = crate::database::in_memory().await.unwrap();
+        let opt_outs = OptOuts::load(pool.clone()).await.unwrap();
+
+        assert!(!opt_outs.contains(42));
+        assert!(opt_outs.opt_out(42).await.unwrap());
+        assert!(!opt_outs.opt_out(42).await.unwrap());
+        assert!(opt_outs.contains(42));
+
+        let opt_outs = OptOuts::load(pool.clone()).await.unwrap();
+        assert!(opt_outs.contains(42));
+        assert!(opt_outs.opt_in(42).await.unwrap());
+        assert!(!opt_outs.opt_in(42).await.unwrap());
+
+        let opt_outs = OptOuts::load(pool).await.unwrap();
+        assert!(!opt_outs.contains(42));
+    }
+
+    #[tokio::test]
+    async fn forget() {
+        let pool = crate::database::in_memory().await.unwrap();
+        let tracker = crate::Tracker::load(pool.clone()).await.unwrap();
+        tracker.observe(42, "museun", "Museun").await.unwrap();
+        tracker.observe(42, "museun2", "Museun2").await.unwrap();
+        tracker
+            .observe(43, "shaken_bot", "shaken_bot")
+            .await
+            .unwrap();
+
+        for (user_id, data) in &[(42, "hello"), (43, "hello museun"), (42, "bye")] {
+            sqlx::query(
+                "INSERT INTO chat_log (room, channel, user_id, login, name, data, at)
+                 VALUES (1, '#museun', ?, 'someone', 'someone', ?, 0)",
+            )
+            .bind(user_id)
+            .bind(data)
+            .execute(&mut &pool)
+            .await
+            .unwrap();
+        }
+
+        // someone else's quote that they added, and one of theirs
+        for (number, author_id, added_by) in &[(1, 43, 42), (2, 42, 43)] {
+            sqlx::query(
+                "INSERT INTO quotes (room, number, body, author, author_id, added_by, created_at)
+                 VALUES (1, ?, 'hello', 'someone', ?, ?, 0)",
+            )
+            .bind(number)
+            .bind(author_id)
+            .bind(added_by)
+            .execute(&mut &pool)
+            .await
+            .unwrap();
+        }
+
+        // they gave someone else points
+        let wallets = crate::Wallets::new(pool.clone());
+        wallets.award(1, 42, 10, "watching", None).await.unwrap();
+        wallets.transfer(1, 42, 43, 5).await.unwrap();
+
+        let forgotten = forget_user(&pool, 42).await.unwrap();
+        let count =
+            |counts: &[(&str, u64)], table| counts.iter().find(|(t, _)| *t == table).unwrap().1;
+        assert_eq!(count(&forgotten.deleted, "chat_log"), 2);
+        assert_eq!(count(&forgotten.deleted, "identities"), 1);
+        assert_eq!(count(&forgotten.deleted, "identity_names"), 1);
+        assert_eq!(count(&forgotten.deleted, "quotes"), 1);
+        assert_eq!(count(&forgotten.deleted, "points_ledger"), 2);
+        assert_eq!(count(&forgotten.anonymized, "quotes"), 1);
+        assert_eq!(count(&forgotten.anonymized, "points_ledger"), 1);
+        assert_eq!(count(&forgotten.anonymized, "command_history"), 0);
+
+        let quote = sqlx::query("SELECT number, added_by FROM quotes")
+            .fetch_one(&mut &pool)
+            .await
+            .unwrap();
+        assert_eq!(quote.get::<i64, _>(0), 1);
+        assert_eq!(quote.get::<i64, _>(1), FORGOTTEN_USER);
+
+        let ledger = wallets.ledger(1, 43, 10).await.unwrap();
+        assert_eq!(ledger.len(), 1);
+        assert_eq!(ledger[0].actor, None);
+        assert_eq!(wallets.balance(1, 43).await.unwrap(), 5);
+
+        let left = sqlx::query("SELECT COUNT(*) FROM chat_log")
+            .fetch_one(&mut &pool)
+            .await
+            .unwrap()
+            .get::<i64, _>(0);
+        assert_eq!(left, 1);
+
+        // the full-text index forgot them too
+        let archive = crate::ChatArchive::new(pool.clone());
+        let (start, now) = (
+            time::OffsetDateTime::from_unix_timestamp(0),
+            time::OffsetDateTime::now(),
+        );
+        assert!(archive
+            .search("#museun", "bye", start, now, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            archive
+                .search("#museun", "hello", start, now, 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
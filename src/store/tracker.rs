@@ -1,83 +1,288 @@
-// use super::kv::KeyValueStore;
+use sqlx::SqlitePool;
 use std::{
     collections::HashMap,
     sync::{Arc, Mutex},
 };
 
+/// Who a twitch user id belongs to
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Identity {
+    pub id: i64,
+    pub login: String,
+    pub display_name: String,
+    /// Unix timestamp of when the user was first seen
+    pub first_seen: i64,
+    /// Unix timestamp of when the current name was first seen
+    pub renamed_at: i64,
+}
+
+/// A name that a user had before
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct PastName {
+    pub id: i64,
+    pub login: String,
+    pub display_name: String,
+    /// Unix timestamp of when the name stopped being used
+    pub until: i64,
+}
+
+/// Maps user (and room) ids to their logins and display names
+///
+/// The bot teaches this every name it sees, so lookups don't have to ask twitch.
+/// Everything is kept in memory and only changes are written to the database.
 #[derive(Clone)]
 pub struct Tracker {
-    pub users: todo!(),
-    pub rooms: todo!(),
+    pool: SqlitePool,
+    inner: Arc<Mutex<Inner>>,
 }
 
-impl Default for Tracker {
-    fn default() -> Self {
-        Self::new()
-    }
+#[derive(Default)]
+struct Inner {
+    users: HashMap<u64, Identity>,
+    logins: HashMap<String, u64>,
 }
 
-impl Tracker {
-    pub fn new() -> Self {
-        Self {
-            users: todo!(),
-            rooms: todo!(),
+impl Inner {
+    fn insert(&mut self, identity: Identity) {
+        if let Some(old) = self.users.get(&(identity.id as u64)) {
+            self.logins.remove(&old.login);
         }
+        self.logins
+            .insert(identity.login.clone(), identity.id as u64);
+        self.users.insert(identity.id as u64, identity);
     }
 }
 
-/*
-#[derive(Clone)]
-pub struct Map {
-     kv: Arc<Mutex<KeyValueStore<'static>>>,
+impl std::fmt::Debug for Tracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tracker")
+            .field("users", &self.inner.lock().unwrap().users.len())
+            .finish()
+    }
 }
 
-impl Map {
-    pub fn new(table_name: &'static str) -> Self {
-        KeyValueStore::fetch(table_name)
-            .map(Mutex::new)
-            .map(Arc::new)
-            .map(|kv| Self { kv })
-            .unwrap()
+impl Tracker {
+    /// Load everyone that has been seen before
+    pub async fn load(pool: SqlitePool) -> anyhow::Result<Self> {
+        let identities = sqlx::query_as::<_, Identity>(
+            "SELECT id, login, display_name, first_seen, renamed_at FROM identities",
+        )
+        .fetch_all(&mut &pool)
+        .await?;
+
+        let mut inner = Inner::default();
+        for identity in identities {
+            inner.insert(identity);
+        }
+
+        Ok(Self {
+            pool,
+            inner: Arc::new(Mutex::new(inner)),
+        })
     }
 
-    pub async fn extend<I>(&self, iter: I)
-    where
-        I: IntoIterator<Item = (u64, String)>,
-        I::IntoIter: Send + 'static,
-        I::Item: Send,
-    {
-        let iter = iter.into_iter();
-        let kv = Arc::clone(&self.kv);
-        tokio::task::spawn_blocking(move || {
-            let kv = kv.lock().unwrap();
-            for (key, val) in iter {
-                kv.set(&key, &val).unwrap();
+    /// Learn the names of a user
+    ///
+    /// If they had a different login before, it's kept in their name history.
+    /// This returns the previous identity if anything changed.
+    pub async fn observe(
+        &self,
+        id: u64,
+        login: &str,
+        display_name: &str,
+    ) -> anyhow::Result<Option<Identity>> {
+        let login = login.to_lowercase();
+        let previous = match self.get(id) {
+            Some(known) if known.login == login && known.display_name == display_name => {
+                return Ok(None);
             }
-        })
-        .await
-        .unwrap();
+            previous => previous,
+        };
+
+        let now = time::OffsetDateTime::now().timestamp();
+        let (first_seen, renamed_at) = match &previous {
+            Some(previous) if previous.login == login => (previous.first_seen, previous.renamed_at),
+            Some(previous) => (previous.first_seen, now),
+            None => (now, now),
+        };
+        let identity = Identity {
+            id: id as i64,
+            login,
+            display_name: display_name.to_string(),
+            first_seen,
+            renamed_at,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        if let Some(previous) = previous.as_ref().filter(|p| p.login != identity.login) {
+            sqlx::query(
+                "INSERT INTO identity_names (id, login, display_name, until) VALUES (?, ?, ?, ?)",
+            )
+            .bind(previous.id)
+            .bind(&previous.login)
+            .bind(&previous.display_name)
+            .bind(now)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO identities (id, login, display_name, first_seen, renamed_at)
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(identity.id)
+        .bind(&identity.login)
+        .bind(&identity.display_name)
+        .bind(identity.first_seen)
+        .bind(identity.renamed_at)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        self.inner.lock().unwrap().insert(identity);
+        Ok(previous)
     }
 
-    pub async fn get(&self, key: u64) -> Option<String> {
-        let kv = Arc::clone(&self.kv);
-        tokio::task::spawn_blocking(move || {
-            log::trace!("getting id: {}", key);
-            kv.lock().unwrap().get(&key)
-        })
-        .await
-        .unwrap()
+    /// Learn the name of a room, which is the login of its broadcaster
+    pub async fn observe_room(&self, id: u64, channel: &str) -> anyhow::Result<()> {
+        let login = channel.trim_start_matches('#').to_lowercase();
+        let display_name = match self.get(id) {
+            Some(known) if known.login == login => return Ok(()),
+            // the display name only differs by case, so this is the best guess
+            _ => login.clone(),
+        };
+        self.observe(id, &login, &display_name).await.map(drop)
     }
 
-    pub async fn set(&self, key: u64, val: impl ToString) {
-        let val = val.to_string();
-        let kv = Arc::clone(&self.kv);
-        tokio::task::spawn_blocking(move || {
-            log::trace!("setting id {} to '{}'", key, val.escape_debug());
-            let kv = kv.lock().unwrap();
-            kv.set(&key, &val).unwrap();
-        })
+    pub fn get(&self, id: u64) -> Option<Identity> {
+        self.inner.lock().unwrap().users.get(&id).cloned()
+    }
+
+    /// Find the user that currently has this login
+    pub fn get_by_login(&self, login: &str) -> Option<Identity> {
+        let login = login.trim_start_matches(|c| c == '@' || c == '#');
+        let inner = self.inner.lock().unwrap();
+        let id = inner.logins.get(&login.to_lowercase())?;
+        inner.users.get(id).cloned()
+    }
+
+    /// The display name of the user, if they have been seen
+    pub fn user_name(&self, id: u64) -> Option<String> {
+        self.get(id).map(|identity| identity.display_name)
+    }
+
+    /// The name of the room (e.g. `#museun`), if it has been seen
+    pub fn room_name(&self, id: u64) -> Option<String> {
+        self.get(id).map(|identity| format!("#{}", identity.login))
+    }
+
+    /// The names the user had before their current one, newest first
+    pub async fn past_names(&self, id: u64) -> anyhow::Result<Vec<PastName>> {
+        sqlx::query_as::<_, PastName>(
+            "SELECT id, login, display_name, until FROM identity_names
+             WHERE id = ?
+             ORDER BY until DESC",
+        )
+        .bind(id as i64)
+        .fetch_all(&mut &self.pool)
         .await
-        .unwrap();
+        .map_err(Into::into)
+    }
+
+    /// Find the id of a user from a configured name
+    ///
+    /// This can be their id, their current login or a login they had before,
+    /// so renaming doesn't break the configuration.
+    pub async fn resolve(&self, name: &str) -> anyhow::Result<Option<u64>> {
+        let name = name.trim_start_matches(|c| c == '@' || c == '#');
+        if let Ok(id) = name.parse() {
+            return Ok(Some(id));
+        }
+
+        if let Some(identity) = self.get_by_login(name) {
+            return Ok(Some(identity.id as u64));
+        }
+
+        let previous = sqlx::query_as::<_, PastName>(
+            "SELECT id, login, display_name, until FROM identity_names
+             WHERE login = ?
+             ORDER BY until DESC
+             LIMIT 1",
+        )
+        .bind(name.to_lowercase())
+        .fetch_optional(&mut &self.pool)
+        .await?;
+        Ok(previous.map(|name| name.id as u64))
+    }
+
+    /// The current name of a configured room (e.g. `#museun`)
+    ///
+    /// A room can be configured with its id or an old name, if it has been seen
+    pub async fn resolve_room(&self, room: &str) -> anyhow::Result<Option<String>> {
+        Ok(self.resolve(room).await?.and_then(|id| self.room_name(id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn tracker() {
+        let pool = crate::database::in_memory().await.unwrap();
+        let tracker = Tracker::load(pool.clone()).await.unwrap();
+
+        assert!(tracker
+            .observe(1, "museun", "Museun")
+            .await
+            .unwrap()
+            .is_none());
+        // nothing changed
+        assert!(tracker
+            .observe(1, "museun", "Museun")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(tracker.user_name(1).unwrap(), "Museun");
+        assert_eq!(tracker.get_by_login("@MUSEUN").unwrap().id, 1);
+
+        // only the display name changed
+        let previous = tracker.observe(1, "museun", "MUSEUN").await.unwrap();
+        assert_eq!(previous.unwrap().display_name, "Museun");
+        assert!(tracker.past_names(1).await.unwrap().is_empty());
+
+        // renamed
+        tracker.observe(1, "museun2", "Museun2").await.unwrap();
+        assert!(tracker.get_by_login("museun").is_none());
+        assert_eq!(tracker.get_by_login("museun2").unwrap().id, 1);
+        assert_eq!(tracker.room_name(1).unwrap(), "#museun2");
+
+        let past = tracker.past_names(1).await.unwrap();
+        assert_eq!(past.len(), 1);
+        assert_eq!(past[0].login, "museun");
+
+        // old names, logins and ids all resolve to the same user
+        assert_eq!(tracker.resolve("museun").await.unwrap(), Some(1));
+        assert_eq!(tracker.resolve("#museun2").await.unwrap(), Some(1));
+        assert_eq!(tracker.resolve("1").await.unwrap(), Some(1));
+        assert_eq!(tracker.resolve("nobody").await.unwrap(), None);
+        assert_eq!(
+            tracker.resolve_room("#museun").await.unwrap().as_deref(),
+            Some("#museun2")
+        );
+
+        // rooms don't clobber a known display name
+        tracker.observe_room(1, "#museun2").await.unwrap();
+        assert_eq!(tracker.user_name(1).unwrap(), "Museun2");
+        tracker.observe_room(2, "#shaken_bot").await.unwrap();
+        assert_eq!(tracker.room_name(2).unwrap(), "#shaken_bot");
+
+        // it's all still there after a restart
+        let tracker = Tracker::load(pool).await.unwrap();
+        assert_eq!(
+            tracker.get_by_login("museun2").unwrap().display_name,
+            "Museun2"
+        );
+        assert_eq!(tracker.resolve("museun").await.unwrap(), Some(1));
     }
 }
-*/
@@ -0,0 +1,126 @@
+use sqlx::SqlitePool;
+use time::OffsetDateTime;
+
+/// How long a user has watched a room
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Presence {
+    pub room: i64,
+    pub user_id: i64,
+    pub seconds: i64,
+    /// Unix timestamp of the first time they were seen watching
+    pub first_seen: i64,
+    /// Unix timestamp of the last time they were seen watching
+    pub last_seen: i64,
+}
+
+/// The watch time of each user, per room
+#[derive(Clone)]
+pub struct WatchTime {
+    pool: SqlitePool,
+}
+
+impl std::fmt::Debug for WatchTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchTime").finish()
+    }
+}
+
+impl WatchTime {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Give each of the users `seconds` of watch time, seen at `at`
+    pub async fn record(
+        &self,
+        room: u64,
+        users: &[u64],
+        seconds: u64,
+        at: OffsetDateTime,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for &user in users {
+            sqlx::query(
+                "INSERT OR IGNORE INTO watch_time (room, user_id, seconds, first_seen, last_seen)
+                 VALUES (?, ?, 0, ?, ?)",
+            )
+            .bind(room as i64)
+            .bind(user as i64)
+            .bind(at.timestamp())
+            .bind(at.timestamp())
+            .execute(&mut tx)
+            .await?;
+
+            sqlx::query(
+                "UPDATE watch_time SET seconds = seconds + ?, last_seen = ?
+                 WHERE room = ? AND user_id = ?",
+            )
+            .bind(seconds as i64)
+            .bind(at.timestamp())
+            .bind(room as i64)
+            .bind(user as i64)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, room: u64, user: u64) -> anyhow::Result<Option<Presence>> {
+        sqlx::query_as::<_, Presence>(
+            "SELECT room, user_id, seconds, first_seen, last_seen FROM watch_time
+             WHERE room = ? AND user_id = ?",
+        )
+        .bind(room as i64)
+        .bind(user as i64)
+        .fetch_optional(&mut &self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// The users that have watched the room the longest, longest first
+    pub async fn top(&self, room: u64, limit: i64) -> anyhow::Result<Vec<Presence>> {
+        sqlx::query_as::<_, Presence>(
+            "SELECT room, user_id, seconds, first_seen, last_seen FROM watch_time
+             WHERE room = ?
+             ORDER BY seconds DESC, first_seen
+             LIMIT ?",
+        )
+        .bind(room as i64)
+        .bind(limit)
+        .fetch_all(&mut &self.pool)
+        .await
+        .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn watch_time() {
+        let pool = crate::database::in_memory().await.unwrap();
+        let watch_time = WatchTime::new(pool);
+        let at = OffsetDateTime::from_unix_timestamp;
+
+        watch_time.record(1, &[42, 43], 300, at(100)).await.unwrap();
+        watch_time.record(1, &[42], 300, at(400)).await.unwrap();
+        watch_time.record(2, &[43], 300, at(400)).await.unwrap();
+
+        let presence = watch_time.get(1, 42).await.unwrap().unwrap();
+        assert_eq!(presence.seconds, 600);
+        assert_eq!(presence.first_seen, 100);
+        assert_eq!(presence.last_seen, 400);
+        assert!(watch_time.get(2, 42).await.unwrap().is_none());
+
+        let top = watch_time.top(1, 10).await.unwrap();
+        assert_eq!(
+            top.iter()
+                .map(|p| (p.user_id, p.seconds))
+                .collect::<Vec<_>>(),
+            vec![(42, 600), (43, 300)]
+        );
+        assert_eq!(watch_time.top(1, 1).await.unwrap().len(), 1);
+    }
+}